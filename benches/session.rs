@@ -0,0 +1,260 @@
+//! Latency of the requests answered while typing, measured against the server binary over
+//! stdio on a generated package whose `main.nr` is about 5000 lines long. Run with
+//! `cargo bench`.
+//!
+//! The budget is 50 ms for a hover or a completion, including right after an edit. Once the
+//! measurements are done, the run fails if the median of any of them is over it.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde_json::{json, Value};
+
+/// How many functions the generated file has, each 10 lines long.
+const FUNCTIONS: usize = 500;
+
+/// The most a hover or a completion may take, edit included.
+const BUDGET: Duration = Duration::from_millis(50);
+
+/// How many times each request is timed for the budget check.
+const SAMPLES: usize = 21;
+
+const MANIFEST: &str =
+    "[package]\nauthors = [\"\"]\ncompiler_version = \"0.1\"\n\n[dependencies]\n";
+
+/// A file of `FUNCTIONS` functions, each calling into `std::hash`, and a `main`.
+fn generate() -> String {
+    let mut text = String::from("use dep::std::hash;\n\n");
+    for i in 0..FUNCTIONS {
+        text.push_str(&format!(
+            "fn helper_{i}(x: Field, y: Field) -> Field {{\n    \
+             let a = x + y;\n    \
+             let b = a * {i};\n    \
+             let c = hash::pedersen([a, b]);\n    \
+             constrain c[0] != 0;\n    \
+             b\n\
+             }}\n\n\n\n",
+        ));
+    }
+    text.push_str("fn main(x: Field, y: pub Field) {\n    constrain helper_0(x, y) == y;\n}\n");
+    text
+}
+
+/// Where in the generated file `hash::pedersen` of the function in the middle is.
+fn pedersen_line() -> u32 {
+    (2 + FUNCTIONS / 2 * 10 + 3) as u32
+}
+
+/// The column of `pedersen` on its line.
+const PEDERSEN_COLUMN: u32 = 18;
+
+/// A running server with the generated package open.
+struct Session {
+    server: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    root: PathBuf,
+    uri: String,
+    next_id: u64,
+    version: i32,
+}
+
+impl Session {
+    fn start() -> Session {
+        let root = std::env::temp_dir().join(format!("noir-lsp-bench-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("Nargo.toml"), MANIFEST).unwrap();
+        let text = generate();
+        let path = root.join("src").join("main.nr");
+        fs::write(&path, &text).unwrap();
+
+        let mut server = Command::new(env!("CARGO_BIN_EXE_noir-language-server"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut session = Session {
+            input: server.stdin.take().unwrap(),
+            output: BufReader::new(server.stdout.take().unwrap()),
+            server,
+            uri: format!("file://{}", path.display()),
+            root: root.clone(),
+            next_id: 0,
+            version: 0,
+        };
+        let root_uri = format!("file://{}", root.display());
+        session.request(
+            "initialize",
+            json!({ "processId": null, "rootUri": root_uri, "capabilities": {} }),
+        );
+        session.notify("initialized", json!({}));
+        session.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": {
+                "uri": session.uri,
+                "languageId": "noir",
+                "version": 0,
+                "text": text,
+            } }),
+        );
+        // The first hover waits for the standard library to be indexed, which isn't measured.
+        session.hover();
+        session
+    }
+
+    fn hover(&mut self) -> Value {
+        let params = self.position(pedersen_line(), PEDERSEN_COLUMN + 2);
+        self.request("textDocument/hover", params)
+    }
+
+    /// Completes `hash::` in front of `pedersen`.
+    fn complete(&mut self) -> Value {
+        let params = self.position(pedersen_line(), PEDERSEN_COLUMN);
+        self.request("textDocument/completion", params)
+    }
+
+    /// Types a space at the start of the line above `pedersen`'s.
+    fn edit(&mut self) {
+        self.version += 1;
+        let start = json!({ "line": pedersen_line() - 1, "character": 0 });
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": self.uri, "version": self.version },
+                "contentChanges": [{ "range": { "start": start, "end": start }, "text": " " }],
+            }),
+        );
+    }
+
+    fn position(&self, line: u32, character: u32) -> Value {
+        json!({
+            "textDocument": { "uri": self.uri },
+            "position": { "line": line, "character": character },
+        })
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            match (message.get("method"), message.get("id")) {
+                (None, Some(response_id)) if *response_id == json!(id) => {
+                    return message["result"].clone();
+                }
+                // The server waits for answers to its own requests, such as for progress.
+                (Some(_), Some(request_id)) => {
+                    let request_id = request_id.clone();
+                    self.send(json!({ "jsonrpc": "2.0", "id": request_id, "result": null }));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.output.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.server.kill();
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn requests(c: &mut Criterion) {
+    let mut session = Session::start();
+    c.bench_function("hover", |b| b.iter(|| session.hover()));
+    c.bench_function("completion", |b| b.iter(|| session.complete()));
+    c.bench_function("edit then hover", |b| {
+        b.iter(|| {
+            session.edit();
+            session.hover()
+        })
+    });
+    c.bench_function("edit then completion", |b| {
+        b.iter(|| {
+            session.edit();
+            session.complete()
+        })
+    });
+}
+
+/// Times each request `SAMPLES` times, outside of criterion, and fails if the median of any
+/// of them is over `BUDGET`.
+fn budget(_: &mut Criterion) {
+    let mut session = Session::start();
+    let scenarios: [(&str, fn(&mut Session)); 4] = [
+        ("hover", |session| {
+            session.hover();
+        }),
+        ("completion", |session| {
+            session.complete();
+        }),
+        ("edit then hover", |session| {
+            session.edit();
+            session.hover();
+        }),
+        ("edit then completion", |session| {
+            session.edit();
+            session.complete();
+        }),
+    ];
+    let over = scenarios
+        .iter()
+        .filter_map(|(name, scenario)| {
+            let mut times = (0..SAMPLES)
+                .map(|_| {
+                    let started = Instant::now();
+                    scenario(&mut session);
+                    started.elapsed()
+                })
+                .collect::<Vec<_>>();
+            times.sort();
+            let median = times[SAMPLES / 2];
+            println!("{}: median {:?}, budget {:?}", name, median, BUDGET);
+            (median > BUDGET).then(|| format!("{} took {:?}", name, median))
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        over.is_empty(),
+        "over the {:?} budget: {}",
+        BUDGET,
+        over.join(", ")
+    );
+}
+
+criterion_group!(benches, requests, budget);
+criterion_main!(benches);
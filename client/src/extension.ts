@@ -74,9 +74,12 @@ export async function activate(context: ExtensionContext) {
     // Register the server for plain text documents
     documentSelector: [{ scheme: "file", language: "noir" }],
     synchronize: {
+      configurationSection: "noir-language-server",
       // Notify the server about file changes to '.clientrc files contained in the workspace
       fileEvents: workspace.createFileSystemWatcher("**/.clientrc"),
     },
+    // Settings the server only reads on start, such as the number of threads.
+    initializationOptions: workspace.getConfiguration("noir-language-server"),
     traceOutputChannel,
   };
 
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{TextDocumentIdentifier, Url};
+
+use crate::workspace::Project;
+
+pub const VIEW_ACIR: &str = "noir/viewAcir";
+
+/// Sent when a package whose ACIR was viewed has been saved, so the client can request it
+/// again.
+pub const ACIR_CHANGED: &str = "noir/acirChanged";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewAcirParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// The ACIR of a package, as a read-only document for the client to show.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AcirDocument {
+    pub uri: Url,
+    /// The opcodes as nargo prints them, or the errors that kept the package from compiling.
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AcirChangedParams {
+    pub uri: Url,
+}
+
+/// `noir-acir:` followed by the package's directory, e.g. `noir-acir:/home/me/circuit/main.acir`.
+pub fn acir_uri(project: &Project) -> Option<Url> {
+    let name = project.name.as_deref().unwrap_or("main");
+    Url::parse(&format!(
+        "noir-acir:{}/{}.acir",
+        project.root.display(),
+        name
+    ))
+    .ok()
+}
@@ -0,0 +1,314 @@
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use noirc_frontend::{parse_program, ParsedModule};
+
+use crate::index::SymbolIndex;
+use crate::interner::Interner;
+use crate::{diagnostics, memory};
+
+/// How many trees of files that aren't open are kept.
+pub const CLOSED_FILES_CAPACITY: usize = 1000;
+
+/// The syntax trees of the documents the server knows, keyed by URI, and what has been
+/// derived from them.
+///
+/// Trees are immutable once stored and handed out behind an `Arc`, so a request reads a
+/// snapshot of the document as it was parsed, from any thread and alongside other requests,
+/// while edits store new trees next to it.
+///
+/// Each tree also carries the version of the document it was parsed from, so results can be
+/// checked against the version the client has now, see `get_version`.
+///
+/// Every stored tree gets a new revision. Results derived from a tree through `query` are
+/// kept with the revision they were computed from and computed again only once the tree has
+/// changed, and text that is the same as what was last parsed doesn't make a new revision.
+///
+/// Trees of files that aren't open are dropped when too many of them pile up, least recently
+/// used first, see `evict`.
+#[derive(Default)]
+pub struct AnalysisStore {
+    files: DashMap<String, File>,
+    /// Version and text hash each document was last given, while it is being parsed.
+    pending: DashMap<String, (Option<i32>, u64)>,
+    derived: DashMap<(String, &'static str), (u64, Arc<dyn Any + Send + Sync>)>,
+    revision: AtomicU64,
+    /// Ticks on every read of a tree, to tell which were used last.
+    clock: AtomicU64,
+    /// Names derived from the trees, shared with the indexes of the standard library and
+    /// the dependencies.
+    names: Interner,
+    /// What every tree declares and refers to, updated as trees are stored.
+    symbols: SymbolIndex,
+}
+
+#[derive(Debug)]
+struct File {
+    revision: u64,
+    text_hash: u64,
+    /// The document version parsed, or `None` for files read from disk.
+    version: Option<i32>,
+    module: Arc<ParsedModule>,
+    last_used: AtomicU64,
+}
+
+impl AnalysisStore {
+    pub fn interner(&self) -> &Interner {
+        &self.names
+    }
+
+    pub fn symbols(&self) -> &SymbolIndex {
+        &self.symbols
+    }
+
+    /// The tree of the document at `uri` as last parsed.
+    pub fn get(&self, uri: &str) -> Option<Arc<ParsedModule>> {
+        let file = self.files.get(uri)?;
+        file.last_used.store(self.tick(), Ordering::Relaxed);
+        Some(Arc::clone(&file.module))
+    }
+
+    /// The tree of the document at `uri`, only if it was parsed from `version`. Positions
+    /// computed from an older tree would land in the wrong place of the current text.
+    pub fn get_version(&self, uri: &str, version: Option<i32>) -> Option<Arc<ParsedModule>> {
+        let file = self.files.get(uri)?;
+        if file.version != version {
+            return None;
+        }
+        file.last_used.store(self.tick(), Ordering::Relaxed);
+        Some(Arc::clone(&file.module))
+    }
+
+    /// Parses `text`, the `version` of the document at `uri`, as its new contents, on a blocking thread so
+    /// the async workers stay free to answer requests, which keep reading the previous tree
+    /// until the new one replaces it. Returns whether it parsed without errors, or `None`
+    /// when nothing changed: the text is what was parsed last time, or a newer version came
+    /// in while this was parsed.
+    pub async fn set_text(&self, uri: &str, text: String, version: Option<i32>) -> Option<bool> {
+        let text_hash = hash(&text);
+        if let Some(mut file) = self.files.get_mut(uri) {
+            if file.text_hash == text_hash {
+                // The same text under a new version: the tree stands for that version too,
+                // and whatever other text is still being parsed is older than it.
+                file.version = version;
+                self.pending.remove(uri);
+                return None;
+            }
+        }
+        // Keyed on the version too, so text edited away and back again is not mistaken for
+        // the older parse of the same text.
+        let key = (version, text_hash);
+        self.pending.insert(uri.to_string(), key);
+        let (module, parses) = tokio::task::spawn_blocking(move || {
+            let (module, errors) = parse_program(&text);
+            (module, errors.is_empty())
+        })
+        .await
+        .ok()?;
+        // Text given since is parsed by its own call, whose tree is the one to keep.
+        self.pending.remove_if(uri, |_, pending| *pending == key)?;
+        self.store(uri.to_string(), text_hash, version, module);
+        Some(parses)
+    }
+
+    /// Stores a tree already parsed from `text`.
+    pub fn insert(&self, uri: String, text: &str, module: ParsedModule) {
+        self.store(uri, hash(text), None, module);
+    }
+
+    pub fn remove(&self, uri: &str) {
+        self.files.remove(uri);
+        self.pending.remove(uri);
+        self.derived.retain(|(key, _), _| key != uri);
+        self.symbols.remove(uri);
+    }
+
+    /// Drops the least recently used trees, other than the ones to `keep`, until at most
+    /// `capacity` of those are left and, with a `budget`, until they take at most `budget`
+    /// bytes along with their documents, whose size `document_bytes` tells. Returns the URIs
+    /// of the documents dropped.
+    pub fn evict(
+        &self,
+        capacity: usize,
+        budget: Option<usize>,
+        keep: impl Fn(&str) -> bool,
+        document_bytes: impl Fn(&str) -> usize,
+    ) -> Vec<String> {
+        let mut candidates = self
+            .files
+            .iter()
+            .filter(|entry| !keep(entry.key()))
+            .map(|entry| {
+                let last_used = entry.last_used.load(Ordering::Relaxed);
+                (last_used, entry.key().clone())
+            })
+            .collect::<Vec<_>>();
+        let mut total = match budget {
+            Some(_) => candidates
+                .iter()
+                .map(|(_, uri)| self.bytes(uri, &document_bytes))
+                .sum::<usize>(),
+            None => 0,
+        };
+        let over = |count: usize, total: usize| {
+            count > capacity || budget.is_some_and(|budget| total > budget)
+        };
+        if !over(candidates.len(), total) {
+            return Vec::new();
+        }
+        candidates.sort();
+        let mut left = candidates.len();
+        let mut evicted = Vec::new();
+        for (_, uri) in candidates {
+            if !over(left, total) {
+                break;
+            }
+            if budget.is_some() {
+                total -= self.bytes(&uri, &document_bytes);
+            }
+            self.remove(&uri);
+            left -= 1;
+            evicted.push(uri);
+        }
+        self.names.collect();
+        evicted
+    }
+
+    pub fn clear(&self) {
+        self.files.clear();
+        self.pending.clear();
+        self.derived.clear();
+        self.symbols.clear();
+    }
+
+    /// Every stored tree with its URI. The map isn't locked while they are read.
+    pub fn snapshot(&self) -> Vec<(String, Arc<ParsedModule>)> {
+        self.files
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(&entry.module)))
+            .collect()
+    }
+
+    /// What each function of the document at `uri` calls or refers to by path.
+    pub fn function_references(&self, uri: &str) -> Option<Arc<HashMap<String, HashSet<String>>>> {
+        self.query(uri, "function_references", diagnostics::function_references)
+    }
+
+    /// An estimate of the bytes the tree of the document at `uri` takes.
+    pub fn syntax_tree_bytes(&self, uri: &str) -> Option<Arc<usize>> {
+        self.query(uri, "syntax_tree_bytes", memory::syntax_tree_bytes)
+    }
+
+    /// What `compute` derives from the tree of the document at `uri`, reusing the result
+    /// computed for the same revision of the tree under the same `name`.
+    fn query<T: Send + Sync + 'static>(
+        &self,
+        uri: &str,
+        name: &'static str,
+        compute: impl FnOnce(&ParsedModule) -> T,
+    ) -> Option<Arc<T>> {
+        let (revision, module) = {
+            let file = self.files.get(uri)?;
+            (file.revision, Arc::clone(&file.module))
+        };
+        let key = (uri.to_string(), name);
+        let cached = self
+            .derived
+            .get(&key)
+            .filter(|entry| entry.0 == revision)
+            .and_then(|entry| Arc::clone(&entry.1).downcast::<T>().ok());
+        if let Some(cached) = cached {
+            return Some(cached);
+        }
+        let result = Arc::new(compute(&module));
+        self.derived.insert(
+            key,
+            (revision, Arc::clone(&result) as Arc<dyn Any + Send + Sync>),
+        );
+        Some(result)
+    }
+
+    fn store(&self, uri: String, text_hash: u64, version: Option<i32>, module: ParsedModule) {
+        let revision = self.revision.fetch_add(1, Ordering::Relaxed);
+        // What was derived from the previous tree can't be used again.
+        self.derived.retain(|(key, _), _| *key != uri);
+        self.symbols.update(&uri, &module, &self.names);
+        self.files.insert(
+            uri,
+            File {
+                revision,
+                text_hash,
+                version,
+                module: Arc::new(module),
+                last_used: AtomicU64::new(self.tick()),
+            },
+        );
+    }
+
+    /// The bytes the tree of the document at `uri` and its text take.
+    fn bytes(&self, uri: &str, document_bytes: impl Fn(&str) -> usize) -> usize {
+        self.syntax_tree_bytes(uri).map_or(0, |bytes| *bytes) + document_bytes(uri)
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Debug for AnalysisStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnalysisStore")
+            .field("files", &self.files)
+            .field("derived", &self.derived.len())
+            .finish()
+    }
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///src/main.nr";
+
+    #[tokio::test]
+    async fn same_text_takes_the_newer_version() {
+        let store = AnalysisStore::default();
+        let text = "fn main() {}".to_string();
+        assert_eq!(store.set_text(URI, text.clone(), Some(1)).await, Some(true));
+        assert_eq!(store.set_text(URI, text, Some(2)).await, None);
+        assert!(store.get_version(URI, Some(1)).is_none());
+        assert!(store.get_version(URI, Some(2)).is_some());
+    }
+
+    #[tokio::test]
+    async fn text_edited_back_outdates_the_parse_in_between() {
+        let store = AnalysisStore::default();
+        let original = "fn main() {}".to_string();
+        store.set_text(URI, original.clone(), Some(1)).await;
+
+        let edited = store.set_text(URI, "fn main(x: Field) {}".to_string(), Some(2));
+        futures::pin_mut!(edited);
+        // Starts parsing the edit, then reverts it before the parse is done.
+        assert!(futures::poll!(&mut edited).is_pending());
+        let reverted = store.set_text(URI, original, Some(3)).await;
+        let edited = edited.await;
+
+        assert_eq!(edited, None);
+        assert_eq!(reverted, None);
+        assert!(store.get_version(URI, Some(3)).is_some());
+        assert!(store
+            .get(URI)
+            .is_some_and(|module| module.functions[0].def.parameters.is_empty()));
+    }
+}
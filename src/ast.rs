@@ -0,0 +1,30 @@
+use noirc_frontend::ParsedModule;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{TextDocumentIdentifier, Url};
+
+/// Returns the syntax tree the server has of a document, as text.
+pub const VIEW_AST: &str = "noir/viewAst";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewAstParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// The syntax tree of a document, as a read-only document for the client to show.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AstDocument {
+    pub uri: Url,
+    /// The tree as the frontend's types print it, spans included.
+    pub text: String,
+}
+
+/// `noir-ast:` followed by the document's path, e.g. `noir-ast:/home/me/circuit/src/main.nr`.
+pub fn ast_uri(uri: &Url) -> Option<Url> {
+    Url::parse(&format!("noir-ast:{}", uri.to_file_path().ok()?.display())).ok()
+}
+
+/// The text of `module`, the tree of the document at `uri`, headed by where it comes from.
+pub fn render(uri: &Url, module: &ParsedModule) -> String {
+    format!("// Syntax tree of {}\n\n{:#?}\n", uri, module)
+}
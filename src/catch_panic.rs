@@ -0,0 +1,91 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+
+use futures::future::{BoxFuture, FutureExt};
+use tokio::sync::mpsc;
+use tower::Service;
+use tower_lsp::jsonrpc::{Error, Request, Response};
+use tower_lsp::lsp_types::MessageType;
+use tower_lsp::Client;
+
+/// Where panics are reported on their way to the user, once `report_panics` connected them.
+static REPORTS: OnceLock<mpsc::UnboundedSender<String>> = OnceLock::new();
+
+/// Logs every panic with its backtrace and shows the user an error pointing to `log_file`,
+/// or to the server's output without one. The panic only fails what it happened in, a
+/// request through `CatchPanic` or a task of its own, and the server keeps serving.
+pub fn report_panics(client: Client, log_file: Option<PathBuf>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    if REPORTS.set(sender).is_err() {
+        return;
+    }
+    // The hook runs on whichever thread panicked, so a task of its own sends the message.
+    tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            client.show_message(MessageType::ERROR, message).await;
+        }
+    });
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!("{}\n{}", info, Backtrace::force_capture());
+        let details = match &log_file {
+            Some(path) => format!("see {} for details", path.display()),
+            None => "see the server's output for details".to_string(),
+        };
+        if let Some(sender) = REPORTS.get() {
+            let _ = sender.send(format!(
+                "The Noir language server ran into a bug ({}), {}.",
+                message(info.payload()),
+                details
+            ));
+        }
+    }));
+}
+
+/// What the code that panicked said, from the payload it panicked with.
+fn message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".to_string())
+}
+
+/// The server's service, with handlers that panic answered by an internal error rather than
+/// taking the whole server down with them. Notifications that panic are dropped.
+#[derive(Debug)]
+pub struct CatchPanic<S>(pub S);
+
+impl<S> Service<Request> for CatchPanic<S>
+where
+    S: Service<Request, Response = Option<Response>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Option<Response>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let id = request.id().cloned();
+        let method = request.method().to_string();
+        let response = self.0.call(request);
+        async move {
+            match AssertUnwindSafe(response).catch_unwind().await {
+                Ok(response) => response,
+                Err(_) => {
+                    tracing::error!("the handler of {} panicked", method);
+                    Ok(id.map(|id| Response::from_error(id, Error::internal_error())))
+                }
+            }
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{
+    ArrayLiteral, BinaryOpKind, Expression, ExpressionKind, Literal, NoirFunction, ParsedModule,
+    Path, PathKind, Statement,
+};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, TextDocumentIdentifier, Url};
+
+use crate::inlay_hints::{eval_constant, global_constants};
+use crate::utils::{import_path, module_path, span_range, PositionEncoding};
+
+/// Reports the opcodes of a package's circuit as `nargo info` counts them, with an estimate
+/// of every function's share, the package being the one of the document whose URI is the
+/// command's argument.
+pub const GATE_REPORT: &str = "noir.gateReport";
+
+/// What `noir.gateReport` returns, and sends as a `noir/gateReport` notification for clients
+/// to show in a panel or as decorations.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GateReport {
+    pub package: String,
+    /// Opcodes of the compiled circuit, as nargo counts them.
+    pub acir_opcodes: Option<usize>,
+    /// Functions by decreasing estimated cost.
+    pub functions: Vec<FunctionCost>,
+    /// Why nargo couldn't count the circuit's opcodes.
+    pub error: Option<String>,
+}
+
+/// Measures a package's circuit with its proving backend and estimates each function's share
+/// of it. The command's argument is the URI of one of the package's documents.
+pub const PROFILE: &str = "noir.profile";
+
+/// What `noir.profile` returns, and sends as a `noir/profile` notification for clients to
+/// decorate the hot functions with.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub package: String,
+    /// Opcodes of the compiled circuit, as nargo counts them.
+    pub acir_opcodes: Option<usize>,
+    /// Gates the proving backend builds the circuit into.
+    pub backend_gates: Option<usize>,
+    /// Functions by decreasing estimated cost.
+    pub hotspots: Vec<Hotspot>,
+    /// Why the backend couldn't measure the circuit.
+    pub error: Option<String>,
+}
+
+/// Returns a summary of the compiled circuit of the package of a document.
+pub const CIRCUIT_INFO: &str = "noir/circuitInfo";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitInfoParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// What `noir/circuitInfo` returns. Counts nargo didn't report are missing.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitInfo {
+    pub package: String,
+    pub acir_opcodes: Option<usize>,
+    pub backend_gates: Option<usize>,
+    pub witnesses: Option<usize>,
+    pub public_inputs: Option<usize>,
+    pub return_values: Option<usize>,
+    /// What nargo printed when the package failed to compile.
+    pub error: Option<String>,
+}
+
+impl CircuitInfo {
+    /// Fills in the witnesses, public inputs and return values from the header of the ACIR
+    /// nargo prints, e.g.
+    ///
+    /// ```text
+    /// current witness index : 5
+    /// public parameters indices : [1, 2]
+    /// return value indices : [5]
+    /// ```
+    ///
+    /// Witnesses are numbered from 1, so the current index is how many there are.
+    pub fn read_acir(&mut self, acir: &str) {
+        let field = |label: &str| {
+            acir.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                (name.trim() == label).then(|| value.trim().to_string())
+            })
+        };
+        let indices = |value: String| {
+            value
+                .trim_matches(|c| c == '[' || c == ']')
+                .split(',')
+                .filter(|index| !index.trim().is_empty())
+                .count()
+        };
+        self.witnesses = field("current witness index").and_then(|value| value.parse().ok());
+        self.public_inputs = field("public parameters indices").map(indices);
+        self.return_values = field("return value indices").map(indices);
+    }
+}
+
+/// A function with its estimated share of the backend's gates.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hotspot {
+    #[serde(flatten)]
+    pub function: FunctionCost,
+    /// The backend's gates scaled by the function's estimated opcodes relative to `main`'s,
+    /// which has every call inlined. Neither nargo nor the backend report a cost per source
+    /// location, so this is an estimate, and only the circuit's total is measured.
+    pub estimated_gates: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionCost {
+    pub name: String,
+    pub uri: Url,
+    /// The function's name.
+    pub range: Range,
+    /// nargo only counts the opcodes of the whole circuit, so these are counted from the
+    /// syntax tree, see [`estimate_opcodes`]. Calls to functions of the same file are inlined
+    /// into this, as the compiler does.
+    pub estimated_opcodes: usize,
+}
+
+/// Width assumed for comparisons, which are range checks over the operand bits. Types are not
+/// resolved here, so this is the common `u32` case rather than the real width.
+const COMPARISON_BITS: usize = 32;
+
+/// A function as calls reach it: the path of the module declaring it, such as
+/// `crate::hash`, the type whose `impl` it is a method of, if any, and its name. Functions
+/// of different modules or impls can share a name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FunctionKey {
+    pub module: String,
+    pub impl_type: Option<String>,
+    pub name: String,
+}
+
+impl std::fmt::Display for FunctionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.impl_type {
+            Some(typ) => write!(f, "{}::{}", typ, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Every function of `module`, the module at `module_path`, with its key.
+pub fn function_keys<'m>(
+    module: &'m ParsedModule,
+    module_path: &str,
+) -> Vec<(FunctionKey, &'m NoirFunction)> {
+    let key = |impl_type: Option<String>, func: &'m NoirFunction| {
+        let key = FunctionKey {
+            module: module_path.to_string(),
+            impl_type,
+            name: func.def.name.0.contents.clone(),
+        };
+        (key, func)
+    };
+    let methods = module.impls.iter().flat_map(|imp| {
+        let typ = imp
+            .type_path
+            .segments
+            .last()
+            .map(|typ| typ.0.contents.clone());
+        imp.methods.iter().map(move |func| key(typ.clone(), func))
+    });
+    module
+        .functions
+        .iter()
+        .map(|func| key(None, func))
+        .chain(methods)
+        .collect()
+}
+
+/// Approximate number of ACIR opcodes each function of `module`, the module at
+/// `module_path`, contributes.
+///
+/// This is a syntactic estimate: additions fold into linear combinations and are free, each
+/// multiplication or equality costs one opcode, comparisons cost a range check, loops with
+/// constant bounds are unrolled and calls to functions in this module are inlined.
+pub fn estimate_opcodes(module: &ParsedModule, module_path: &str) -> HashMap<FunctionKey, usize> {
+    let functions = function_keys(module, module_path)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let mut estimator = Estimator {
+        module_path,
+        functions: &functions,
+        constants: global_constants(module),
+        memo: HashMap::new(),
+        in_progress: HashSet::new(),
+    };
+    functions
+        .keys()
+        .map(|key| (key.clone(), estimator.function(key)))
+        .collect()
+}
+
+/// The estimated cost of each function in one file, for a `GateReport`.
+pub fn function_costs(
+    uri: &Url,
+    module: &ParsedModule,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<FunctionCost> {
+    let module_path = module_path(uri).unwrap_or_else(|| "crate".to_string());
+    let opcodes = estimate_opcodes(module, &module_path);
+    function_keys(module, &module_path)
+        .into_iter()
+        .filter_map(|(key, func)| {
+            let span = func.def.name.span();
+            Some(FunctionCost {
+                name: key.to_string(),
+                uri: uri.clone(),
+                range: span_range(span.start(), span.end(), document, encoding)?,
+                estimated_opcodes: *opcodes.get(&key)?,
+            })
+        })
+        .collect()
+}
+
+struct Estimator<'a> {
+    module_path: &'a str,
+    functions: &'a HashMap<FunctionKey, &'a NoirFunction>,
+    constants: HashMap<String, u128>,
+    memo: HashMap<FunctionKey, usize>,
+    /// Guards against recursion, which the compiler rejects anyway.
+    in_progress: HashSet<FunctionKey>,
+}
+
+impl<'a> Estimator<'a> {
+    fn function(&mut self, key: &FunctionKey) -> usize {
+        let func = match self.functions.get(key) {
+            Some(func) => *func,
+            None => return 0,
+        };
+        if let Some(cost) = self.memo.get(key) {
+            return *cost;
+        }
+        if !self.in_progress.insert(key.clone()) {
+            return 0;
+        }
+        let cost = self.statements(&func.def.body.0);
+        self.in_progress.remove(key);
+        self.memo.insert(key.clone(), cost);
+        cost
+    }
+
+    /// The function of this module `path` calls, as `name`, `Type::name` or with the module's
+    /// own path in front. Functions of other modules aren't known here.
+    fn callee(&self, path: &Path) -> Option<FunctionKey> {
+        let written = import_path(path);
+        let full = match path.kind {
+            PathKind::Plain => format!("{}::{}", self.module_path, written),
+            _ => written,
+        };
+        let local = full.strip_prefix(self.module_path)?.strip_prefix("::")?;
+        let (impl_type, name) = match local.split_once("::") {
+            None => (None, local),
+            Some((typ, name)) if !name.contains("::") => (Some(typ.to_string()), name),
+            Some(_) => return None,
+        };
+        Some(FunctionKey {
+            module: self.module_path.to_string(),
+            impl_type,
+            name: name.to_string(),
+        })
+    }
+
+    /// The method `name` calls go to, when only one `impl` of this module has one by that
+    /// name: the receiver's type isn't resolved here.
+    fn method(&self, name: &str) -> Option<FunctionKey> {
+        let mut methods = self
+            .functions
+            .keys()
+            .filter(|key| key.impl_type.is_some() && key.name == name);
+        match (methods.next(), methods.next()) {
+            (Some(key), None) => Some(key.clone()),
+            _ => None,
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> usize {
+        statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Let(val) => self.expression(&val.expression),
+                Statement::Constrain(constrain) => {
+                    1 + self.expression(&constrain.0.lhs) + self.expression(&constrain.0.rhs)
+                }
+                Statement::Assign(assign) => self.expression(&assign.expression),
+                Statement::Expression(expr) | Statement::Semi(expr) => self.expression(expr),
+                Statement::Error => 0,
+            })
+            .sum()
+    }
+
+    fn expression(&mut self, expr: &Expression) -> usize {
+        match &expr.kind {
+            ExpressionKind::Literal(Literal::Array(ArrayLiteral::Standard(elements))) => {
+                elements.iter().map(|e| self.expression(e)).sum()
+            }
+            ExpressionKind::Literal(Literal::Array(ArrayLiteral::Repeated {
+                repeated_element,
+                ..
+            })) => self.expression(repeated_element),
+            ExpressionKind::Block(block) => self.statements(&block.0),
+            ExpressionKind::Prefix(prefix) => self.expression(&prefix.rhs),
+            ExpressionKind::Index(index) => {
+                1 + self.expression(&index.collection) + self.expression(&index.index)
+            }
+            ExpressionKind::Call(call) => {
+                let arguments: usize = call.arguments.iter().map(|e| self.expression(e)).sum();
+                let callee = self
+                    .callee(&call.func_name)
+                    .map_or(0, |key| self.function(&key));
+                arguments + callee
+            }
+            ExpressionKind::MethodCall(call) => {
+                let arguments: usize = call.arguments.iter().map(|e| self.expression(e)).sum();
+                let callee = self
+                    .method(&call.method_name.0.contents)
+                    .map_or(0, |key| self.function(&key));
+                self.expression(&call.object) + arguments + callee
+            }
+            ExpressionKind::Constructor(constructor) => constructor
+                .fields
+                .iter()
+                .map(|(_, e)| self.expression(e))
+                .sum(),
+            ExpressionKind::MemberAccess(access) => self.expression(&access.lhs),
+            ExpressionKind::Cast(cast) => 1 + self.expression(&cast.lhs),
+            ExpressionKind::Infix(infix) => {
+                let operator = match infix.operator.contents {
+                    BinaryOpKind::Add | BinaryOpKind::Subtract => 0,
+                    BinaryOpKind::Less
+                    | BinaryOpKind::LessEqual
+                    | BinaryOpKind::Greater
+                    | BinaryOpKind::GreaterEqual => COMPARISON_BITS,
+                    _ => 1,
+                };
+                operator + self.expression(&infix.lhs) + self.expression(&infix.rhs)
+            }
+            ExpressionKind::For(for_loop) => {
+                let trips = match (
+                    eval_constant(&for_loop.start_range, &self.constants),
+                    eval_constant(&for_loop.end_range, &self.constants),
+                ) {
+                    (Some(start), Some(end)) => end.saturating_sub(start) as usize,
+                    _ => 1,
+                };
+                trips.saturating_mul(self.expression(&for_loop.block))
+            }
+            ExpressionKind::If(if_expr) => {
+                // Both branches are always executed in a circuit.
+                self.expression(&if_expr.condition)
+                    + self.expression(&if_expr.consequence)
+                    + if_expr
+                        .alternative
+                        .as_ref()
+                        .map_or(0, |alternative| self.expression(alternative))
+            }
+            ExpressionKind::Tuple(elements) => elements.iter().map(|e| self.expression(e)).sum(),
+            _ => 0,
+        }
+    }
+}
+
+/// Orders `functions` by estimated cost, attributing to each its estimated share of
+/// `backend_gates`.
+pub fn hotspots(mut functions: Vec<FunctionCost>, backend_gates: Option<usize>) -> Vec<Hotspot> {
+    functions.sort_by(|a, b| b.estimated_opcodes.cmp(&a.estimated_opcodes));
+    let main = functions
+        .iter()
+        .find(|func| func.name == "main")
+        .map(|main| main.estimated_opcodes)
+        .filter(|opcodes| *opcodes > 0);
+    functions
+        .into_iter()
+        .map(|function| {
+            let estimated_gates = match (backend_gates, main) {
+                (Some(gates), Some(main)) => {
+                    Some(gates * function.estimated_opcodes.min(main) / main)
+                }
+                _ => None,
+            };
+            Hotspot {
+                function,
+                estimated_gates,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use noirc_frontend::parse_program;
+
+    use super::*;
+
+    fn key(impl_type: Option<&str>, name: &str) -> FunctionKey {
+        FunctionKey {
+            module: "crate".to_string(),
+            impl_type: impl_type.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn methods_are_told_apart_from_functions_of_the_same_name() {
+        let (module, _) = parse_program(
+            "struct A { x: Field }
+struct B { x: Field }
+impl A { fn new(x: Field) -> A { A { x: x * x } } }
+impl B { fn new(x: Field) -> B { B { x: x * x * x } } }
+fn new(x: Field) -> Field { x }
+fn main(x: Field) -> pub Field { let b = B::new(x); new(x) }",
+        );
+        let opcodes = estimate_opcodes(&module, "crate");
+        assert_eq!(opcodes[&key(Some("A"), "new")], 1);
+        assert_eq!(opcodes[&key(Some("B"), "new")], 2);
+        assert_eq!(opcodes[&key(None, "new")], 0);
+        assert_eq!(opcodes[&key(None, "main")], 2);
+    }
+
+    #[test]
+    fn reported_costs_are_labelled_as_estimates() {
+        let text = "struct A { x: Field }
+impl A { fn double(self) -> Field { self.x * 2 } }";
+        let (module, _) = parse_program(text);
+        let uri = Url::parse("file:///package/src/main.nr").unwrap();
+        let costs = function_costs(
+            &uri,
+            &module,
+            &Rope::from_str(text),
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(costs.len(), 1);
+        assert_eq!(costs[0].name, "A::double");
+        let json = serde_json::to_value(&costs[0]).unwrap();
+        assert_eq!(json["estimatedOpcodes"], 1);
+    }
+
+    #[test]
+    fn hotspots_share_the_measured_gates_out_by_estimate() {
+        let cost = |name: &str, estimated_opcodes| FunctionCost {
+            name: name.to_string(),
+            uri: Url::parse("file:///package/src/main.nr").unwrap(),
+            range: Range::default(),
+            estimated_opcodes,
+        };
+        let hotspots = hotspots(vec![cost("helper", 5), cost("main", 20)], Some(1000));
+        let gates = hotspots
+            .iter()
+            .map(|hotspot| (hotspot.function.name.as_str(), hotspot.estimated_gates))
+            .collect::<Vec<_>>();
+        assert_eq!(gates, vec![("main", Some(1000)), ("helper", Some(250))]);
+        assert!(hotspots(vec![cost("main", 20)], None)[0]
+            .estimated_gates
+            .is_none());
+    }
+
+    #[test]
+    fn calls_into_other_modules_are_not_inlined_from_this_one() {
+        let (module, _) = parse_program(
+            "mod hash;
+fn square(x: Field) -> Field { x * x }
+fn main(x: Field) -> pub Field { hash::square(x) + square(x) }",
+        );
+        let opcodes = estimate_opcodes(&module, "crate");
+        assert_eq!(opcodes[&key(None, "main")], 1);
+    }
+}
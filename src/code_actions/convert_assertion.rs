@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{BinaryOpKind, Expression, ExpressionKind, InfixExpression, Statement};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::ActionContext;
+use crate::utils::{
+    enclosing_function, innermost_statement, span_offset, span_text, walk_statements,
+};
+
+/// Rewrites between the equivalent spellings of an assertion at the cursor:
+/// `constrain a == b;`, `assert(a == b)` and `assert_eq(a, b)`, and offers to add a failure
+/// message to `assert`s that lack one.
+pub fn convert_assertion(ctx: &ActionContext) -> Vec<CodeAction> {
+    let cursor = match ctx.cursor() {
+        Some(cursor) => cursor,
+        None => return Vec::new(),
+    };
+    let func = match enclosing_function(ctx.module, ctx.document, cursor) {
+        Some(func) => func,
+        None => return Vec::new(),
+    };
+
+    if let Some(Statement::Constrain(constrain)) =
+        innermost_statement(&func.def.body.0, cursor, cursor)
+    {
+        return constrain_rewrites(ctx, &constrain.0).unwrap_or_default();
+    }
+
+    let mut call = None;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Call(c) = &expr.kind {
+            let is_assert = matches!(call_name(expr), Some("assert" | "assert_eq"));
+            if is_assert && expr.span.start() <= cursor && cursor <= expr.span.end() {
+                call = Some((expr, c));
+            }
+        }
+    });
+    let (expr, call) = match call {
+        Some(call) => call,
+        None => return Vec::new(),
+    };
+    let text = |e: &Expression| source(ctx, e);
+    let mut rewrites = Vec::new();
+    match (call_name(expr), call.arguments.as_slice()) {
+        (Some("assert"), [condition, rest @ ..]) => {
+            if let ExpressionKind::Infix(infix) = &condition.kind {
+                if infix.operator.contents == BinaryOpKind::Equal {
+                    let message = rest.first().map(|m| format!(", {}", text(m)));
+                    rewrites.push((
+                        "Convert to `assert_eq`",
+                        format!(
+                            "assert_eq({}, {}{})",
+                            text(&infix.lhs),
+                            text(&infix.rhs),
+                            message.unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+            if rest.is_empty() {
+                rewrites.push((
+                    "Add failure message",
+                    format!("assert({}, \"\")", text(condition)),
+                ));
+            }
+        }
+        (Some("assert_eq"), [lhs, rhs, rest @ ..]) => {
+            let message = rest.first().map(|m| format!(", {}", text(m)));
+            rewrites.push((
+                "Convert to `assert`",
+                format!(
+                    "assert({} == {}{})",
+                    text(lhs),
+                    text(rhs),
+                    message.unwrap_or_default()
+                ),
+            ));
+            if rest.is_empty() {
+                rewrites.push((
+                    "Add failure message",
+                    format!("assert_eq({}, {}, \"\")", text(lhs), text(rhs)),
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    let range = match span_range(ctx, expr.span.start(), expr.span.end()) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+    rewrites
+        .into_iter()
+        .map(|(title, new_text)| rewrite(ctx, title, range, new_text))
+        .collect()
+}
+
+/// `constrain lhs == rhs` as `assert(lhs == rhs)` and, for equalities, `assert_eq(lhs, rhs)`.
+fn constrain_rewrites(ctx: &ActionContext, infix: &InfixExpression) -> Option<Vec<CodeAction>> {
+    // The statement's span starts at the condition, so find the keyword in front of it.
+    let condition_start = infix.lhs.span.start();
+    let before = ctx
+        .document
+        .get_slice(..span_offset(condition_start, ctx.document)?)?
+        .to_string();
+    let keyword_start = before.trim_end().strip_suffix("constrain")?.len();
+    let range = span_range(ctx, keyword_start as u32, infix.rhs.span.end())?;
+
+    let condition = span_text(condition_start, infix.rhs.span.end(), ctx.document)?;
+    let mut actions = vec![rewrite(
+        ctx,
+        "Convert to `assert`",
+        range,
+        format!("assert({})", condition),
+    )];
+    if infix.operator.contents == BinaryOpKind::Equal {
+        actions.push(rewrite(
+            ctx,
+            "Convert to `assert_eq`",
+            range,
+            format!(
+                "assert_eq({}, {})",
+                source(ctx, &infix.lhs),
+                source(ctx, &infix.rhs)
+            ),
+        ));
+    }
+    Some(actions)
+}
+
+fn call_name(expr: &Expression) -> Option<&str> {
+    match &expr.kind {
+        ExpressionKind::Call(call) => match call.func_name.segments.as_slice() {
+            [name] => Some(name.0.contents.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn source(ctx: &ActionContext, expr: &Expression) -> String {
+    span_text(expr.span.start(), expr.span.end(), ctx.document).unwrap_or_default()
+}
+
+fn span_range(ctx: &ActionContext, start: u32, end: u32) -> Option<Range> {
+    crate::utils::span_range(start, end, ctx.document, ctx.encoding)
+}
+
+fn rewrite(ctx: &ActionContext, title: &str, range: Range, new_text: String) -> CodeAction {
+    CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit { range, new_text }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{ExpressionKind, Literal};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, TextEdit, WorkspaceEdit};
+
+use super::ActionContext;
+use crate::utils::{enclosing_function, span_range, span_text, walk_statements};
+
+/// On an integer literal, rewrites it from decimal to hexadecimal or back. Only the digits
+/// change, so a cast such as `255 as u8` keeps its type. Literals too wide for a `u128` are
+/// left alone.
+pub fn convert_literal(ctx: &ActionContext) -> Option<CodeAction> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+
+    let mut literal = None;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Literal(Literal::Integer(_)) = &expr.kind {
+            if expr.span.start() <= cursor && cursor <= expr.span.end() {
+                literal = Some(expr.span);
+            }
+        }
+    });
+    let span = literal?;
+    let text = span_text(span.start(), span.end(), ctx.document)?;
+    let (title, new_text) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => (
+            "Convert to decimal",
+            u128::from_str_radix(hex, 16).ok()?.to_string(),
+        ),
+        None => (
+            "Convert to hexadecimal",
+            format!("0x{:x}", text.parse::<u128>().ok()?),
+        ),
+    };
+
+    Some(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit {
+                    range: span_range(span.start(), span.end(), ctx.document, ctx.encoding)?,
+                    new_text,
+                }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{
+    Expression, ExpressionKind, LValue, Literal, NoirFunction, Pattern, Statement, UnresolvedType,
+    UnresolvedTypeExpression,
+};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, TextEdit, WorkspaceEdit};
+
+use super::ActionContext;
+use crate::inlay_hints::{array_length, eval_constant, global_constants};
+use crate::utils::{
+    enclosing_function, fresh_name, names_in_function, span_range, span_text, walk_expression,
+    walk_statements,
+};
+
+/// Rewrites an indexed loop over an array into the equivalent `map` or `fold` call when its
+/// body is a single assignment of one of these shapes:
+///
+/// - `for i in 0..n { out[i] = f(arr[i]); }` becomes `out = arr.map(|x| f(x));`
+/// - `for i in 0..n { acc = g(acc, arr[i]); }` becomes `acc = arr.fold(acc, |acc, x| g(acc, x));`
+///
+/// The index may only be used to read `arr[i]`, anything else depends on the position and
+/// has no functional equivalent, and the loop must run over the whole array, up to
+/// `arr.len()` or the length `arr` is declared with.
+pub fn convert_loop(ctx: &ActionContext) -> Option<CodeAction> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+
+    let mut found = None;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::For(for_loop) = &expr.kind {
+            if expr.span.start() <= cursor && cursor <= expr.span.end() {
+                found = Some((expr, for_loop));
+            }
+        }
+    });
+    let (expr, for_loop) = found?;
+
+    let starts_at_zero = matches!(&for_loop.start_range.kind,
+        ExpressionKind::Literal(Literal::Integer(value)) if value.try_into_u128() == Some(0));
+    let assign = match single_statement(&for_loop.block)? {
+        Statement::Assign(assign) => assign,
+        _ => return None,
+    };
+    if !starts_at_zero {
+        return None;
+    }
+
+    let index = for_loop.identifier.0.contents.as_str();
+    let element = fresh_name("x", &names_in_function(func));
+    let (array, body) = substitute_element(ctx, &assign.expression, index, &element)?;
+    if !covers_array(ctx, func, &for_loop.end_range, &array, expr.span.start()) {
+        return None;
+    }
+
+    let (title, new_text) = match &assign.lvalue {
+        LValue::Index {
+            array: out,
+            index: i,
+        } if is_path(i, index) => match out.as_ref() {
+            LValue::Ident(out) => (
+                "Convert to `map`",
+                format!("{} = {}.map(|{}| {});", out, array, element, body),
+            ),
+            _ => return None,
+        },
+        LValue::Ident(acc) => (
+            "Convert to `fold`",
+            format!(
+                "{acc} = {}.fold({acc}, |{acc}, {}| {});",
+                array,
+                element,
+                body,
+                acc = acc
+            ),
+        ),
+        _ => return None,
+    };
+
+    // The loop may be followed by a `;` of its own, which is kept.
+    let range = span_range(
+        expr.span.start(),
+        expr.span.end(),
+        ctx.document,
+        ctx.encoding,
+    )?;
+    Some(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit { range, new_text }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+/// Whether `end`, where the loop stops, is the length of `array`: `array.len()`, or a
+/// constant equal to the length `array` is last declared with before `before`.
+fn covers_array(
+    ctx: &ActionContext,
+    func: &NoirFunction,
+    end: &Expression,
+    array: &str,
+    before: u32,
+) -> bool {
+    if let ExpressionKind::MethodCall(call) = &end.kind {
+        return call.method_name.0.contents == "len"
+            && call.arguments.is_empty()
+            && is_path(&call.object, array);
+    }
+    let globals = global_constants(ctx.module);
+    let type_length = |typ: &UnresolvedType| match typ {
+        UnresolvedType::Array(Some(UnresolvedTypeExpression::Constant(length, _)), _) => {
+            Some(*length as u128)
+        }
+        UnresolvedType::Array(Some(UnresolvedTypeExpression::Variable(path)), _) => {
+            globals.get(&path.segments.last()?.0.contents).copied()
+        }
+        _ => None,
+    };
+    let mut length = func
+        .def
+        .parameters
+        .iter()
+        .find(|(pattern, _, _)| binds(pattern, array))
+        .and_then(|(_, typ, _)| type_length(typ));
+    let mut declare = |statements: &[Statement]| {
+        statements.iter().for_each(|statement| match statement {
+            Statement::Let(val)
+                if binds(&val.pattern, array) && val.expression.span.end() < before =>
+            {
+                length = type_length(&val.r#type).or(match &val.expression.kind {
+                    ExpressionKind::Literal(Literal::Array(literal)) => array_length(literal),
+                    _ => None,
+                });
+            }
+            _ => {}
+        })
+    };
+    declare(&func.def.body.0);
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Block(block) = &expr.kind {
+            if expr.span.start() <= before && before <= expr.span.end() {
+                declare(&block.0);
+            }
+        }
+    });
+    length.is_some() && length == eval_constant(end, &globals)
+}
+
+fn binds(pattern: &Pattern, name: &str) -> bool {
+    match pattern {
+        Pattern::Identifier(ident) => ident.0.contents == name,
+        Pattern::Mutable(pattern, _) => binds(pattern, name),
+        _ => false,
+    }
+}
+
+fn single_statement(block: &Expression) -> Option<&Statement> {
+    match &block.kind {
+        ExpressionKind::Block(block) => match block.0.as_slice() {
+            [statement] => Some(statement),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_path(expr: &Expression, name: &str) -> bool {
+    matches!(&expr.kind, ExpressionKind::Path(path)
+        if path.segments.len() == 1 && path.segments[0].0.contents == name)
+}
+
+/// The source of `expr` with every `arr[index]` replaced by `element`, and the name of
+/// `arr`. `None` if `index` is used any other way or indexes more than one array.
+fn substitute_element(
+    ctx: &ActionContext,
+    expr: &Expression,
+    index: &str,
+    element: &str,
+) -> Option<(String, String)> {
+    let mut array: Option<String> = None;
+    let mut replaced = Vec::new();
+    let mut other_uses = 0;
+    let mut index_uses = 0;
+    walk_expression(expr, &mut |e| match &e.kind {
+        ExpressionKind::Index(indexing) if is_path(&indexing.index, index) => {
+            match &indexing.collection.kind {
+                ExpressionKind::Path(path) if path.segments.len() == 1 => {
+                    let name = path.segments[0].0.contents.clone();
+                    if array.get_or_insert_with(|| name.clone()) != &name {
+                        other_uses += 1;
+                    }
+                    replaced.push((e.span.start(), e.span.end()));
+                }
+                _ => other_uses += 1,
+            }
+        }
+        _ if is_path(e, index) => index_uses += 1,
+        _ => {}
+    });
+    // Every `arr[i]` also contains a use of `i` itself.
+    if other_uses > 0 || index_uses != replaced.len() {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut cursor = expr.span.start();
+    for (start, end) in replaced {
+        text.push_str(&span_text(cursor, start, ctx.document)?);
+        text.push_str(element);
+        cursor = end;
+    }
+    text.push_str(&span_text(cursor, expr.span.end(), ctx.document)?);
+    Some((array?, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run_action;
+    use super::*;
+
+    fn converted(text: &str) -> Option<String> {
+        run_action(text, "for", convert_loop)
+    }
+
+    #[test]
+    fn loops_up_to_the_length_become_map() {
+        let text = "fn main(arr: [Field; 3]) {
+    let mut out = [0; 3];
+    for i in 0..3 { out[i] = arr[i] * 2; }
+}";
+        let edited = converted(text).unwrap();
+        assert!(edited.contains("out = arr.map(|x| x * 2);"));
+    }
+
+    #[test]
+    fn loops_up_to_len_become_fold() {
+        let text = "fn main(arr: [Field; 3]) {
+    let mut sum = 0;
+    for i in 0..arr.len() { sum = sum + arr[i]; }
+}";
+        let edited = converted(text).unwrap();
+        assert!(edited.contains("sum = arr.fold(sum, |sum, x| sum + x);"));
+    }
+
+    #[test]
+    fn loops_over_part_of_the_array_are_kept() {
+        let text = "fn main(arr: [Field; 3]) {
+    let mut sum = 0;
+    for i in 0..2 { sum = sum + arr[i]; }
+}";
+        assert_eq!(converted(text), None);
+    }
+
+    #[test]
+    fn loops_over_arrays_of_unknown_length_are_kept() {
+        let text = "fn main(n: Field) {
+    let mut sum = 0;
+    let arr = get();
+    for i in 0..3 { sum = sum + arr[i]; }
+}";
+        assert_eq!(converted(text), None);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{
+    CallExpression, Expression, ExpressionKind, NoirStruct, ParsedModule, Statement, UnresolvedType,
+};
+use ropey::Rope;
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, Url, WorkspaceEdit};
+
+use super::{full_path, placeholder, ActionContext};
+use crate::inlay_hints::{binding_types, literal_type};
+use crate::utils::{
+    all_functions, enclosing_function, function_end, module_path, offset_to_position,
+    position_to_byte, top_level_names, walk_statements,
+};
+
+/// For calls in the selection to a function that is neither declared nor imported here,
+/// offers to generate a stub below the calling function, or at the end of the module a
+/// qualified call such as `hash::pedersen(x)` leads to. Parameter names and types come from
+/// the arguments, the return type from the `let` the call initializes, if annotated, and
+/// then the stub returns a placeholder of that type.
+pub fn create_function(ctx: &ActionContext) -> Vec<CodeAction> {
+    let (start, end) = match (
+        position_to_byte(ctx.params.range.start, ctx.document, ctx.encoding),
+        position_to_byte(ctx.params.range.end, ctx.document, ctx.encoding),
+    ) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Vec::new(),
+    };
+    let func = match enclosing_function(ctx.module, ctx.document, start) {
+        Some(func) => func,
+        None => return Vec::new(),
+    };
+    let below_caller = match function_end(func, ctx.document)
+        .and_then(|end| offset_to_position(end, ctx.document, ctx.encoding))
+    {
+        Some(position) => position,
+        None => return Vec::new(),
+    };
+
+    let known = all_functions(ctx.module)
+        .map(|func| func.def.name.0.contents.as_str())
+        .chain(ctx.module.imports.iter().filter_map(|import| {
+            import
+                .alias
+                .as_ref()
+                .or_else(|| import.path.segments.last())
+                .map(|name| name.0.contents.as_str())
+        }))
+        .collect::<HashSet<_>>();
+    let here = module_path(ctx.uri);
+    let types = binding_types(func);
+    let return_types = annotated_initializers(&func.def.body.0);
+
+    let mut seen = HashSet::new();
+    let mut actions = Vec::new();
+    walk_statements(&func.def.body.0, &mut |expr| {
+        let call = match &expr.kind {
+            ExpressionKind::Call(call) => call,
+            _ => return,
+        };
+        if expr.span.end() < start || end < expr.span.start() {
+            return;
+        }
+        let (name, uri, module, insert_at, separator) = match &call.func_name.segments[..] {
+            [name] if !known.contains(name.0.contents.as_str()) => {
+                (name, ctx.uri, ctx.module, below_caller, "\n\n")
+            }
+            [.., name] if call.func_name.segments.len() > 1 => {
+                let (uri, document, module) = match here
+                    .as_deref()
+                    .and_then(|here| target_module(ctx, here, call))
+                {
+                    Some(target) => target,
+                    None => return,
+                };
+                let end = match offset_to_position(document.len_chars(), document, ctx.encoding) {
+                    Some(end) => end,
+                    None => return,
+                };
+                let separator = if document.to_string().ends_with('\n') {
+                    "\n"
+                } else {
+                    "\n\n"
+                };
+                if top_level_names(module).contains(&name.0.contents) {
+                    return;
+                }
+                (name, uri, module, end, separator)
+            }
+            _ => return,
+        };
+        let name = name.0.contents.as_str();
+        if !seen.insert((uri, name)) {
+            return;
+        }
+
+        let parameters = parameters(&call.arguments, &types);
+        let return_type = return_types.get(&(expr.span.start(), expr.span.end()));
+        let structs = module.types.iter().collect::<Vec<&NoirStruct>>();
+        let (return_type, body) = match return_type {
+            Some(typ) => (
+                format!(" -> {}", typ),
+                format!("    {}\n", placeholder(typ, &structs)),
+            ),
+            None => (String::new(), String::new()),
+        };
+        let new_text = format!(
+            "{}fn {}({}){} {{\n{}}}",
+            separator, name, parameters, return_type, body
+        );
+        let edit = TextEdit {
+            range: Range::new(insert_at, insert_at),
+            new_text,
+        };
+        actions.push(CodeAction {
+            title: format!("Create function `{}`", name),
+            kind: Some(CodeActionKind::QUICKFIX),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                ..WorkspaceEdit::default()
+            }),
+            ..CodeAction::default()
+        });
+    });
+    actions
+}
+
+/// The file of the crate declaring the module a qualified `call` made in the module `here`
+/// leads to.
+fn target_module<'a>(
+    ctx: &ActionContext<'a>,
+    here: &str,
+    call: &CallExpression,
+) -> Option<(&'a Url, &'a Rope, &'a ParsedModule)> {
+    let full = full_path(ctx.module, here, &call.func_name);
+    let (target, _) = full.rsplit_once("::")?;
+    ctx.crate_files.iter().find_map(|(uri, document, module)| {
+        (module_path(uri).as_deref() == Some(target)).then_some((uri, document, &**module))
+    })
+}
+
+/// Names each parameter after its argument when that is a plain variable, and types it from
+/// what is known about the argument, falling back to `Field`.
+fn parameters(arguments: &[Expression], types: &HashMap<String, String>) -> String {
+    let mut names = HashSet::new();
+    arguments
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let (name, typ) = match &arg.kind {
+                ExpressionKind::Path(path) if path.segments.len() == 1 => {
+                    let name = path.segments[0].0.contents.clone();
+                    let typ = types.get(&name).cloned();
+                    (Some(name), typ)
+                }
+                ExpressionKind::Literal(literal) => (None, literal_type(literal)),
+                ExpressionKind::Cast(cast) => (None, Some(cast.r#type.to_string())),
+                _ => (None, None),
+            };
+            let name = name
+                .filter(|name| names.insert(name.clone()))
+                .unwrap_or_else(|| format!("arg{}", i));
+            format!("{}: {}", name, typ.unwrap_or_else(|| "Field".to_string()))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Written-out types of top-level `let`s, keyed by the span of their initializer.
+fn annotated_initializers(statements: &[Statement]) -> HashMap<(u32, u32), &UnresolvedType> {
+    statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Let(val) if !matches!(val.r#type, UnresolvedType::Unspecified) => Some((
+                (val.expression.span.start(), val.expression.span.end()),
+                &val.r#type,
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use noirc_frontend::parse_program;
+
+    use super::super::{apply_edits, run_action, run_crate_action};
+    use super::*;
+
+    fn created(text: &str, selection: &str) -> Option<String> {
+        run_action(text, selection, |ctx| {
+            create_function(ctx).into_iter().next()
+        })
+    }
+
+    #[test]
+    fn stubs_return_a_placeholder_of_the_annotated_type() {
+        let text = "struct Point { x: Field, y: u8 }
+fn main(a: Field) {
+    let p: Point = make(a);
+}";
+        let edited = created(text, "make(a)").unwrap();
+        assert!(edited.ends_with("}\n\nfn make(a: Field) -> Point {\n    Point { x: 0, y: 0 }\n}"));
+    }
+
+    #[test]
+    fn stubs_without_a_known_type_return_nothing() {
+        let text = "fn main(a: Field) {\n    check(a);\n}";
+        let edited = created(text, "check(a)").unwrap();
+        assert!(edited.ends_with("}\n\nfn check(a: Field) {\n}"));
+    }
+
+    #[test]
+    fn qualified_calls_create_the_stub_in_the_module_they_lead_to() {
+        let hash = "fn other() {}\n";
+        let hash_uri = Url::parse("file:///src/hash.nr").unwrap();
+        let files = [(
+            hash_uri.clone(),
+            Rope::from_str(hash),
+            Arc::new(parse_program(hash).0),
+        )];
+        let text = "mod hash;
+fn main(a: Field) {
+    let h: bool = hash::pedersen(a);
+}";
+        let action = run_crate_action(text, "pedersen", &files, |ctx| {
+            create_function(ctx).into_iter().next()
+        })
+        .unwrap();
+        let mut changes = action.edit.unwrap().changes.unwrap();
+        assert_eq!(changes.len(), 1);
+        let edited = apply_edits(hash, changes.remove(&hash_uri).unwrap()).unwrap();
+        assert_eq!(
+            edited,
+            "fn other() {}\n\nfn pedersen(a: Field) -> bool {\n    false\n}"
+        );
+    }
+
+    #[test]
+    fn qualified_calls_to_existing_functions_are_left_alone() {
+        let hash = "fn pedersen(a: Field) -> Field { a }";
+        let files = [(
+            Url::parse("file:///src/hash.nr").unwrap(),
+            Rope::from_str(hash),
+            Arc::new(parse_program(hash).0),
+        )];
+        let text = "mod hash;\nfn main(a: Field) {\n    hash::pedersen(a);\n}";
+        assert!(run_crate_action(text, "pedersen", &files, |ctx| {
+            create_function(ctx).into_iter().next()
+        })
+        .is_none());
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{Expression, ExpressionKind, Ident, Statement};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::{bound_names, selected_statements, selection_offsets, ActionContext};
+use crate::inlay_hints::binding_types;
+use crate::utils::{
+    all_functions, enclosing_function, fresh_name, function_end, line_indent, lvalue_root,
+    names_in_function, offset_to_position, span_offset, span_range, span_text, statement_span,
+    walk_statements,
+};
+
+/// Moves the selected statements into a new function below the current one and calls it in
+/// their place.
+///
+/// Variables declared before the selection that the statements read or assign become
+/// parameters. Variables they declare, and outer variables they assign to, are returned
+/// when they are read after the selection, as a tuple when there are several.
+pub fn extract_function(ctx: &ActionContext) -> Option<CodeAction> {
+    extract(ctx, false)
+}
+
+/// [`extract_function`], generating an `unconstrained fn` so the statements run outside the
+/// circuit.
+pub(super) fn extract(ctx: &ActionContext, unconstrained: bool) -> Option<CodeAction> {
+    let (start, end) = selection_offsets(ctx)?;
+    let func = enclosing_function(ctx.module, ctx.document, start)?;
+    let selected = selected_statements(&func.def.body.0, start, end)?;
+
+    let mut declared = Vec::new();
+    let mut assigned = Vec::new();
+    let mut used = Vec::new();
+    selected.iter().for_each(|statement| {
+        match statement {
+            Statement::Let(val) => bound_names(&val.pattern, &mut declared),
+            Statement::Assign(assign) => assigned.push(lvalue_root(&assign.lvalue)),
+            _ => {}
+        }
+        walk_statements(
+            std::slice::from_ref(statement),
+            &mut |expr| match &expr.kind {
+                ExpressionKind::Path(path) if path.segments.len() == 1 => {
+                    used.push(&path.segments[0])
+                }
+                ExpressionKind::Block(block) => block.0.iter().for_each(|statement| {
+                    if let Statement::Assign(assign) = statement {
+                        assigned.push(lvalue_root(&assign.lvalue));
+                    }
+                }),
+                _ => {}
+            },
+        );
+    });
+    let declared_names = declared
+        .iter()
+        .map(|ident| ident.0.contents.as_str())
+        .collect::<HashSet<_>>();
+
+    // Only what is declared before the selection is passed in: other names are functions,
+    // globals or types, which the new function reaches as well.
+    let mut blocks = Vec::new();
+    let mut in_scope = Vec::new();
+    enclosing_blocks(
+        &func.def.body.0,
+        start,
+        end,
+        false,
+        &mut blocks,
+        &mut in_scope,
+    );
+    func.def
+        .parameters
+        .iter()
+        .for_each(|(pattern, _, _)| bound_names(pattern, &mut in_scope));
+    blocks.iter().for_each(|(statements, _)| {
+        statements.iter().for_each(|statement| match statement {
+            Statement::Let(val) if statement_span(statement).is_some_and(|(_, e)| e <= start) => {
+                bound_names(&val.pattern, &mut in_scope)
+            }
+            _ => {}
+        })
+    });
+    let in_scope = in_scope
+        .iter()
+        .map(|ident| ident.0.contents.as_str())
+        .collect::<HashSet<_>>();
+
+    let types = binding_types(func);
+    let type_of = |name: &str| {
+        types
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| "Field".to_string())
+    };
+    let locals = names_in_function(func);
+
+    // Reads and assignments of variables declared before the selection, in order of first
+    // use.
+    let mut parameters: Vec<&str> = Vec::new();
+    used.iter()
+        .chain(assigned.iter())
+        .map(|ident| ident.0.contents.as_str())
+        .filter(|name| in_scope.contains(name) && !declared_names.contains(name))
+        .for_each(|name| {
+            if !parameters.contains(&name) {
+                parameters.push(name)
+            }
+        });
+    let mut mutated: Vec<&str> = Vec::new();
+    assigned
+        .iter()
+        .map(|ident| ident.0.contents.as_str())
+        .filter(|name| parameters.contains(name))
+        .for_each(|name| {
+            if !mutated.contains(&name) {
+                mutated.push(name)
+            }
+        });
+    let innermost = blocks.last()?.0;
+    // What the selection declares is out of scope once its block ends, while assignments
+    // to outer variables are seen until they are shadowed, and by the next iteration of a
+    // loop around the selection.
+    let mut returned = declared
+        .iter()
+        .map(|ident| ident.0.contents.as_str())
+        .filter(|name| read_after(&[(innermost, false)], start, end, name))
+        .collect::<Vec<_>>();
+    returned.extend(
+        mutated
+            .iter()
+            .filter(|name| read_after(&blocks, start, end, name)),
+    );
+
+    let taken = all_functions(ctx.module)
+        .map(|func| func.def.name.0.contents.clone())
+        .collect::<HashSet<_>>();
+    let name = fresh_name("extracted", &taken);
+
+    // Body: the selected text re-indented one level deep, followed by the returned values.
+    let first_line = ctx.document.char_to_line(span_offset(start, ctx.document)?);
+    let indent = line_indent(ctx.document, first_line);
+    let text = span_text(start, end, ctx.document)?;
+    let mut body = text
+        .lines()
+        .map(|line| format!("    {}", line.strip_prefix(indent.as_str()).unwrap_or(line)))
+        .collect::<Vec<_>>();
+    let (return_type, return_value) = match returned.as_slice() {
+        [] => (String::new(), None),
+        [single] => (format!(" -> {}", type_of(single)), Some(single.to_string())),
+        many => (
+            format!(
+                " -> ({})",
+                many.iter()
+                    .map(|name| type_of(name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Some(format!("({})", many.join(", "))),
+        ),
+    };
+    body.extend(return_value.map(|value| format!("    {}", value)));
+
+    let signature = parameters
+        .iter()
+        .map(|name| {
+            let binding = if mutated.contains(name) { "mut " } else { "" };
+            format!("{}{}: {}", binding, name, type_of(name))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let definition = format!(
+        "\n\n{}fn {}({}){} {{\n{}\n}}",
+        if unconstrained { "unconstrained " } else { "" },
+        name,
+        signature,
+        return_type,
+        body.join("\n")
+    );
+
+    // Call site: mutated variables come back under fresh names and are assigned afterwards.
+    let mut fresh = locals.clone();
+    let bindings = returned
+        .iter()
+        .map(|returned| {
+            if mutated.contains(returned) {
+                let temporary = fresh_name(returned, &fresh);
+                fresh.insert(temporary.clone());
+                temporary
+            } else {
+                returned.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    let call = format!("{}({})", name, parameters.join(", "));
+    let mut call_site = match bindings.as_slice() {
+        [] => format!("{};", call),
+        [single] => format!("let {} = {};", single, call),
+        many => format!("let ({}) = {};", many.join(", "), call),
+    };
+    returned
+        .iter()
+        .zip(&bindings)
+        .filter(|(returned, _)| mutated.contains(returned))
+        .for_each(|(returned, temporary)| {
+            call_site.push_str(&format!("\n{}{} = {};", indent, returned, temporary))
+        });
+
+    let insert_at = offset_to_position(
+        function_end(func, ctx.document)?,
+        ctx.document,
+        ctx.encoding,
+    )?;
+    let edits = vec![
+        TextEdit {
+            range: span_range(start, end, ctx.document, ctx.encoding)?,
+            new_text: call_site,
+        },
+        TextEdit {
+            range: Range::new(insert_at, insert_at),
+            new_text: definition,
+        },
+    ];
+    Some(CodeAction {
+        title: if unconstrained {
+            "Extract into `unconstrained` function".to_string()
+        } else {
+            "Extract into function".to_string()
+        },
+        kind: Some(CodeActionKind::new("refactor.extract.function")),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(ctx.uri.clone(), edits)])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+/// The blocks around `start..end`, from the function's body in to the one holding the
+/// selected statements, each with whether it is a loop's body. The variables of the loops
+/// around the selection go to `loop_variables`.
+fn enclosing_blocks<'a>(
+    statements: &'a [Statement],
+    start: u32,
+    end: u32,
+    in_loop: bool,
+    blocks: &mut Vec<(&'a [Statement], bool)>,
+    loop_variables: &mut Vec<&'a Ident>,
+) {
+    blocks.push((statements, in_loop));
+    let containing = statements.iter().find(|statement| {
+        statement_span(statement)
+            .is_some_and(|(s, e)| s <= start && end <= e && (s, e) != (start, end))
+    });
+    let statement = match containing {
+        Some(statement) => statement,
+        None => return,
+    };
+    let contains = |expr: &Expression| expr.span.start() <= start && end <= expr.span.end();
+    let mut loop_body = None;
+    let mut inner = None;
+    walk_statements(std::slice::from_ref(statement), &mut |expr| {
+        if inner.is_some() || !contains(expr) {
+            return;
+        }
+        match &expr.kind {
+            ExpressionKind::For(for_loop) if contains(&for_loop.block) => {
+                loop_variables.push(&for_loop.identifier);
+                loop_body = Some(for_loop.block.span);
+            }
+            ExpressionKind::Block(block) => inner = Some((&block.0, loop_body == Some(expr.span))),
+            _ => {}
+        }
+    });
+    if let Some((statements, in_loop)) = inner {
+        enclosing_blocks(statements, start, end, in_loop, blocks, loop_variables);
+    }
+}
+
+/// Whether `name` is read after `start..end` before being shadowed, in `blocks` as
+/// [`enclosing_blocks`] lists them. Statements before the selection in a loop's body run
+/// after it too.
+fn read_after(blocks: &[(&[Statement], bool)], start: u32, end: u32, name: &str) -> bool {
+    blocks.iter().rev().any(|(statements, in_loop)| {
+        let mut before = statements
+            .iter()
+            .filter(|statement| statement_span(statement).is_some_and(|(_, e)| e <= start));
+        if *in_loop && before.any(|statement| reads(statement, name)) {
+            return true;
+        }
+        for statement in statements {
+            if !statement_span(statement).is_some_and(|(s, _)| end <= s) {
+                continue;
+            }
+            if reads(statement, name) {
+                return true;
+            }
+            if let Statement::Let(val) = statement {
+                let mut bound = Vec::new();
+                bound_names(&val.pattern, &mut bound);
+                if bound.iter().any(|ident| ident.0.contents == name) {
+                    return false;
+                }
+            }
+        }
+        false
+    })
+}
+
+fn reads(statement: &Statement, name: &str) -> bool {
+    let mut read = false;
+    walk_statements(std::slice::from_ref(statement), &mut |expr| {
+        if let ExpressionKind::Path(path) = &expr.kind {
+            read |= path.segments.len() == 1 && path.segments[0].0.contents == name;
+        }
+    });
+    read
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run_action;
+    use super::*;
+
+    fn extracted(text: &str, selection: &str) -> String {
+        run_action(text, selection, extract_function).unwrap()
+    }
+
+    #[test]
+    fn only_locals_declared_before_become_parameters() {
+        let text = "global LIMIT = 3;
+fn double(x: Field) -> Field { x * 2 }
+fn main(a: Field) {
+    let b = a + 1;
+    assert(double(b) != LIMIT);
+}";
+        let edited = extracted(text, "assert(double(b) != LIMIT);");
+        assert!(edited.contains("    extracted(b);\n"));
+        assert!(edited.contains("fn extracted(b: Field) {"));
+    }
+
+    #[test]
+    fn shadowed_declarations_are_not_returned() {
+        let text = "fn main(a: Field) {
+    let b = a + 1;
+    let b = 2;
+    assert(b == 2);
+}";
+        let edited = extracted(text, "let b = a + 1;");
+        assert!(edited.contains("    extracted(a);\n"));
+        assert!(edited.contains("fn extracted(a: Field) {"));
+    }
+
+    #[test]
+    fn assignments_read_afterwards_are_returned() {
+        let text = "fn main(a: Field) {
+    let mut sum = 0;
+    for i in 0..3 {
+        sum = sum + a;
+    }
+    assert(sum == a * 3);
+}";
+        let edited = extracted(
+            text,
+            "for i in 0..3 {
+        sum = sum + a;
+    }",
+        );
+        assert!(edited.contains("let sum1 = extracted(sum, a);\n    sum = sum1;"));
+        assert!(edited.contains("fn extracted(mut sum: Field, a: Field) -> Field {"));
+    }
+
+    #[test]
+    fn assignments_not_read_afterwards_are_not_returned() {
+        let text = "fn main(a: Field) {
+    let mut b = a;
+    b = b + 1;
+    assert(a != 0);
+}";
+        let edited = extracted(text, "b = b + 1;");
+        assert!(edited.contains("    extracted(b);\n"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CreateFile, CreateFileOptions, DocumentChangeOperation, Position,
+    Range, ResourceOp, TextEdit,
+};
+
+use super::{
+    import_position, item_first_line, multi_file_edit, names_used, qualified_references,
+    selection_offsets, text_document_edit, ActionContext,
+};
+use crate::utils::{
+    block_end, child_module_file, fresh_name, function_end, import_path, module_path, span_offset,
+    span_range, to_snake_case, top_level_names,
+};
+
+/// Moves the top-level functions and structs in the selection into a new file declared as a
+/// child module of this one.
+///
+/// This file gets the `mod` declaration and imports for the moved items it still uses, the
+/// new file imports what it uses from here, and `use`s of the moved items and paths to them
+/// in the rest of the crate are pointed at the new module.
+pub fn extract_module(ctx: &ActionContext) -> Option<CodeAction> {
+    let (start, end) = selection_offsets(ctx)?;
+    let selected = |offset: u32| start <= offset && offset <= end;
+
+    // (name, first line, last line) of every selected item.
+    let mut items = Vec::new();
+    let mut moved_functions = Vec::new();
+    for func in &ctx.module.functions {
+        if selected(func.def.name.span().start()) {
+            let last = ctx.document.char_to_line(function_end(func, ctx.document)?) as u32;
+            items.push((&func.def.name, item_first_line(ctx, func)?, last));
+            moved_functions.push(func);
+        }
+    }
+    for typ in &ctx.module.types {
+        if selected(typ.name.span().start()) {
+            let first = span_offset(typ.name.span().start(), ctx.document)?;
+            let end = block_end(
+                span_offset(typ.name.span().end(), ctx.document)?,
+                ctx.document,
+            )?;
+            let (first, last) = (
+                ctx.document.char_to_line(first),
+                ctx.document.char_to_line(end),
+            );
+            items.push((&typ.name, first as u32, last as u32));
+        }
+    }
+    items.sort_by_key(|(_, first, _)| *first);
+    let moved = items
+        .iter()
+        .map(|(name, _, _)| name.0.contents.as_str())
+        .collect::<HashSet<_>>();
+
+    let declared = ctx
+        .module
+        .module_decls
+        .iter()
+        .map(|decl| decl.0.contents.clone())
+        .collect::<HashSet<_>>();
+    let module_name = fresh_name(&to_snake_case(&items.first()?.0 .0.contents), &declared);
+    let current_path = module_path(ctx.uri)?;
+    let new_path = format!("{}::{}", current_path, module_name);
+    let new_uri = child_module_file(ctx.uri, &module_name)?;
+
+    // The new file: imports of what the moved functions use from here, then the items.
+    let mut used_here = names_used(&moved_functions);
+    let remaining = top_level_names(ctx.module)
+        .into_iter()
+        .filter(|name| !moved.contains(name.as_str()) && used_here.contains(name))
+        .collect::<Vec<_>>();
+    let mut new_text = remaining
+        .iter()
+        .map(|name| format!("use {}::{};\n", current_path, name))
+        .collect::<String>();
+    if !new_text.is_empty() {
+        new_text.push('\n');
+    }
+    let bodies = items
+        .iter()
+        .map(|(_, first, last)| {
+            let start = ctx.document.line_to_char(*first as usize);
+            let end = ctx
+                .document
+                .line_to_char((*last as usize + 1).min(ctx.document.len_lines()));
+            ctx.document.slice(start..end).to_string()
+        })
+        .collect::<Vec<_>>();
+    new_text.push_str(bodies.join("\n").trim_end());
+    new_text.push('\n');
+
+    // This file: drop the items, declare the module and import what is still used here.
+    let staying = ctx
+        .module
+        .functions
+        .iter()
+        .filter(|func| !moved.contains(func.def.name.0.contents.as_str()))
+        .collect::<Vec<_>>();
+    used_here = names_used(&staying);
+    let mut header = format!("mod {};\n", module_name);
+    items
+        .iter()
+        .map(|(name, _, _)| &name.0.contents)
+        .filter(|name| used_here.contains(*name))
+        .for_each(|name| header.push_str(&format!("use {}::{};\n", new_path, name)));
+    let insert_at = import_position(ctx);
+    let mut edits = vec![TextEdit {
+        range: Range::new(insert_at, insert_at),
+        new_text: header,
+    }];
+    edits.extend(items.iter().map(|(_, first, last)| TextEdit {
+        range: Range::new(Position::new(*first, 0), Position::new(last + 1, 0)),
+        new_text: String::new(),
+    }));
+
+    let mut operations = vec![
+        DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: new_uri.clone(),
+            options: Some(CreateFileOptions {
+                overwrite: Some(false),
+                ignore_if_exists: Some(false),
+            }),
+            annotation_id: None,
+        })),
+        text_document_edit(
+            &new_uri,
+            vec![TextEdit {
+                range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                new_text,
+            }],
+        ),
+        text_document_edit(ctx.uri, edits),
+    ];
+
+    // Other files: repoint `use current::item` and `current::item` at the new module.
+    for (uri, document, module) in ctx.crate_files {
+        let mut edits = module
+            .imports
+            .iter()
+            .filter_map(|import| {
+                let name = import.path.segments.last()?;
+                if !moved.contains(name.0.contents.as_str())
+                    || import_path(&import.path) != format!("{}::{}", current_path, name)
+                {
+                    return None;
+                }
+                let first = import.path.segments.first()?;
+                let replacement = format!("{}::{}", new_path, name);
+                // The `crate::` prefix is not part of the segments, so it stays as written.
+                let replacement = replacement.strip_prefix("crate::").unwrap_or(&replacement);
+                Some(TextEdit {
+                    range: span_range(
+                        first.span().start(),
+                        name.span().end(),
+                        document,
+                        ctx.encoding,
+                    )?,
+                    new_text: replacement.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        if let Some(here) = module_path(uri) {
+            for name in &moved {
+                let target = format!("{}::{}", current_path, name);
+                edits.extend(
+                    qualified_references(module, &here, &target)
+                        .into_iter()
+                        .filter_map(|path| {
+                            // The new module sits right under the item's old one.
+                            let at = path.segments.last()?.span().start();
+                            Some(TextEdit {
+                                range: span_range(at, at, document, ctx.encoding)?,
+                                new_text: format!("{}::", module_name),
+                            })
+                        }),
+                );
+            }
+        }
+        if !edits.is_empty() {
+            operations.push(text_document_edit(uri, edits));
+        }
+    }
+
+    Some(CodeAction {
+        title: format!("Move to new module `{}`", module_name),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(multi_file_edit(ctx.annotate_changes, operations)),
+        ..CodeAction::default()
+    })
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use noirc_frontend::Expression;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, Command, Position, Range, TextEdit, WorkspaceEdit,
+};
+
+use super::{selection_offsets, ActionContext};
+use crate::utils::{
+    enclosing_function, fresh_name, innermost_statement, line_indent, names_in_function,
+    span_offset, span_range, span_text, statement_span, walk_statements,
+};
+
+/// Lifts the selected expression into a `let` on the line above the statement containing
+/// it, then asks the editor to rename the new binding.
+pub fn extract_variable(ctx: &ActionContext) -> Option<CodeAction> {
+    let (start, end) = selection_offsets(ctx)?;
+    let func = enclosing_function(ctx.module, ctx.document, start)?;
+
+    let mut selected: Option<&Expression> = None;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if expr.span.start() == start && expr.span.end() == end {
+            selected = Some(expr);
+        }
+    });
+    selected?;
+
+    let statement = innermost_statement(&func.def.body.0, start, end)?;
+    let (statement_start, _) = statement_span(statement)?;
+    let line = ctx
+        .document
+        .char_to_line(span_offset(statement_start, ctx.document)?);
+    let indent = line_indent(ctx.document, line);
+
+    let name = fresh_name("value", &names_in_function(func));
+    let text = span_text(start, end, ctx.document)?;
+    let replaced = span_range(start, end, ctx.document, ctx.encoding)?;
+    let insert_at = Position::new(line as u32, 0);
+    let edits = vec![
+        TextEdit {
+            range: Range::new(insert_at, insert_at),
+            new_text: format!("{}let {} = {};\n", indent, name, text),
+        },
+        TextEdit {
+            range: replaced,
+            new_text: name,
+        },
+    ];
+
+    // The replacement lands on the line below the inserted `let`, at the same column.
+    let renamed_at = Position::new(replaced.start.line + 1, replaced.start.character);
+    Some(CodeAction {
+        title: "Extract into variable".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(ctx.uri.clone(), edits)])),
+            ..WorkspaceEdit::default()
+        }),
+        command: Some(Command {
+            title: "Rename".to_string(),
+            command: "editor.action.rename".to_string(),
+            arguments: Some(vec![
+                serde_json::json!(ctx.uri),
+                serde_json::json!(renamed_at),
+            ]),
+        }),
+        ..CodeAction::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tower_lsp::lsp_types::Url;
+
+    use super::super::{apply_edits, run_action, run_crate_action};
+    use super::*;
+    use crate::utils::{position_to_offset, PositionEncoding};
+
+    fn extracted(text: &str, selection: &str) -> Option<String> {
+        run_action(text, selection, extract_variable)
+    }
+
+    #[test]
+    fn the_let_goes_above_the_statement_and_the_selection_becomes_its_name() {
+        let text = "fn main(a: Field) {
+    assert(a * 2 == 4);
+}";
+        let edited = extracted(text, "a * 2").unwrap();
+        assert_eq!(
+            edited,
+            "fn main(a: Field) {\n    let value = a * 2;\n    assert(value == 4);\n}"
+        );
+    }
+
+    #[test]
+    fn nested_statements_get_the_let_at_their_own_indent() {
+        let text = "fn main(a: Field) {
+    if a == 1 {
+        assert(a + 1 == 2);
+    }
+}";
+        let edited = extracted(text, "a + 1").unwrap();
+        assert!(edited
+            .contains("    if a == 1 {\n        let value = a + 1;\n        assert(value == 2);"));
+    }
+
+    #[test]
+    fn names_in_use_are_not_reused() {
+        let text = "fn main(value: Field) {
+    assert(value * 2 == 4);
+}";
+        let edited = extracted(text, "value * 2").unwrap();
+        assert!(edited.contains("let value1 = value * 2;"));
+        assert!(edited.contains("assert(value1 == 4);"));
+    }
+
+    #[test]
+    fn partial_expressions_are_not_extracted() {
+        let text = "fn main(a: Field) {
+    assert(a * 2 == 4);
+}";
+        assert_eq!(extracted(text, "a * "), None);
+    }
+
+    #[test]
+    fn the_rename_starts_on_the_replaced_selection() {
+        let text = "fn main(a: Field) {
+    assert(a * 2 == 4);
+}";
+        let action = run_crate_action(text, "a * 2", &[], extract_variable).unwrap();
+        let arguments = action.command.unwrap().arguments.unwrap();
+        let position: Position = serde_json::from_value(arguments[1].clone()).unwrap();
+        assert_eq!(position, Position::new(2, 11));
+
+        let uri = Url::parse("file:///src/main.nr").unwrap();
+        let edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+        let edited = Rope::from_str(&apply_edits(text, edits).unwrap());
+        let offset = position_to_offset(position, &edited, PositionEncoding::Utf16).unwrap();
+        assert_eq!(edited.slice(offset..offset + "value".len()), "value");
+    }
+}
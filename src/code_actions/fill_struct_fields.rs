@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{ExpressionKind, NoirStruct};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::{placeholder, ActionContext};
+use crate::utils::{
+    enclosing_function, line_indent, offset_to_position, span_offset, span_text, walk_statements,
+};
+
+/// On a struct literal that leaves fields out, inserts the missing ones with placeholder
+/// values. The struct is looked up in this document first and then in the rest of the crate.
+pub fn fill_struct_fields(ctx: &ActionContext) -> Option<CodeAction> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+
+    // The innermost literal around the cursor, since walking visits parents first.
+    let mut literal = None;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Constructor(constructor) = &expr.kind {
+            if expr.span.start() <= cursor && cursor <= expr.span.end() {
+                literal = Some((expr, constructor));
+            }
+        }
+    });
+    let (expr, constructor) = literal?;
+    let name = &constructor.type_name.segments.last()?.0.contents;
+
+    let structs = ctx
+        .module
+        .types
+        .iter()
+        .chain(
+            ctx.crate_files
+                .iter()
+                .flat_map(|(_, _, module)| module.types.iter()),
+        )
+        .collect::<Vec<&NoirStruct>>();
+    let definition = structs.iter().find(|typ| &typ.name.0.contents == name)?;
+
+    let present = constructor
+        .fields
+        .iter()
+        .map(|(field, _)| field.0.contents.as_str())
+        .collect::<HashSet<_>>();
+    let missing = definition
+        .fields
+        .iter()
+        .filter(|(field, _)| !present.contains(field.0.contents.as_str()))
+        .map(|(field, typ)| format!("{}: {}", field, placeholder(typ, &structs)))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return None;
+    }
+
+    // Insert in front of the closing brace, one field per line if the literal spans lines.
+    let text = span_text(expr.span.start(), expr.span.end(), ctx.document)?;
+    let before_brace = text[..text.rfind('}')?].trim_end();
+    let insert_offset =
+        span_offset(expr.span.start(), ctx.document)? + before_brace.chars().count();
+    let new_text = if text.contains('\n') {
+        let last_field_line = ctx.document.char_to_line(insert_offset);
+        let indent = line_indent(ctx.document, last_field_line);
+        let (separator, indent) = match before_brace.chars().last() {
+            Some('{') => ("", format!("{}    ", indent)),
+            Some(',') => ("", indent),
+            _ => (",", indent),
+        };
+        let fields = missing
+            .iter()
+            .map(|field| format!("\n{}{},", indent, field))
+            .collect::<String>();
+        format!("{}{}", separator, fields)
+    } else {
+        let fields = missing.join(", ");
+        match before_brace.chars().last() {
+            // `Foo {}` needs a space on both sides, `Foo { }` already has the closing one.
+            Some('{') if text[before_brace.len()..].starts_with('}') => format!(" {} ", fields),
+            Some('{') | Some(',') => format!(" {}", fields),
+            _ => format!(", {}", fields),
+        }
+    };
+
+    let position = offset_to_position(insert_offset, ctx.document, ctx.encoding)?;
+    Some(CodeAction {
+        title: format!("Fill missing fields of `{}`", name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit {
+                    range: Range::new(position, position),
+                    new_text,
+                }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
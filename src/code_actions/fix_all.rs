@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{ExpressionKind, Statement, UnresolvedType};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Position, Range, TextEdit, WorkspaceEdit};
+
+use super::type_annotation::annotation_removal;
+use super::ActionContext;
+use crate::diagnostics::unused_variables;
+use crate::inlay_hints::inferred_let_type;
+use crate::utils::{all_functions, position_to_offset, span_offset, walk_statements};
+
+/// `source.fixAll`: the fixes that can't change what the program means, applied across the
+/// whole file at once. Unused imports are removed, unused variables get a `_` prefix and
+/// type annotations that only repeat the inferred type are dropped.
+pub fn fix_all(ctx: &ActionContext) -> Option<CodeAction> {
+    let mut edits = unused_imports(ctx);
+    edits.extend(
+        unused_variables(ctx.module, ctx.document, ctx.encoding)
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let start = position_to_offset(diagnostic.range.start, ctx.document, ctx.encoding)?;
+                let end = position_to_offset(diagnostic.range.end, ctx.document, ctx.encoding)?;
+                Some(TextEdit {
+                    range: diagnostic.range,
+                    new_text: format!("_{}", ctx.document.get_slice(start..end)?),
+                })
+            }),
+    );
+    all_functions(ctx.module).for_each(|func| {
+        let mut blocks = vec![&func.def.body.0];
+        walk_statements(&func.def.body.0, &mut |expr| {
+            if let ExpressionKind::Block(block) = &expr.kind {
+                blocks.push(&block.0);
+            }
+        });
+        blocks
+            .iter()
+            .flat_map(|block| block.iter())
+            .for_each(|statement| {
+                let val = match statement {
+                    Statement::Let(val) if !matches!(val.r#type, UnresolvedType::Unspecified) => {
+                        val
+                    }
+                    _ => return,
+                };
+                if inferred_let_type(ctx.module, func, val) == Some(val.r#type.to_string()) {
+                    edits.extend(annotation_removal(ctx, val));
+                }
+            });
+    });
+    if edits.is_empty() {
+        return None;
+    }
+
+    Some(CodeAction {
+        title: "Fix all auto-fixable problems".to_string(),
+        kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(ctx.uri.clone(), edits)])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+/// Removes the lines of imports whose name appears nowhere else in the file. Matching on
+/// words rather than resolved paths means a mention in a comment keeps an import alive,
+/// which errs on the safe side.
+fn unused_imports(ctx: &ActionContext) -> Vec<TextEdit> {
+    let import_lines = ctx
+        .module
+        .imports
+        .iter()
+        .filter_map(|import| {
+            let first = import.path.segments.first()?.span().start();
+            let last = import
+                .alias
+                .as_ref()
+                .or_else(|| import.path.segments.last())?
+                .span()
+                .end();
+            let line = |byte: u32| {
+                Some(ctx.document.char_to_line(span_offset(byte, ctx.document)?) as u32)
+            };
+            Some((import, line(first)?, line(last)?))
+        })
+        .collect::<Vec<_>>();
+    let in_import = |line: u32| {
+        import_lines
+            .iter()
+            .any(|(_, first, last)| *first <= line && line <= *last)
+    };
+
+    let mut words = HashSet::new();
+    ctx.document
+        .lines()
+        .enumerate()
+        .filter(|(line, _)| !in_import(*line as u32))
+        .for_each(|(_, line)| {
+            line.to_string()
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|word| !word.is_empty())
+                .for_each(|word| {
+                    words.insert(word.to_string());
+                })
+        });
+
+    import_lines
+        .iter()
+        .filter_map(|(import, first, last)| {
+            let name = import
+                .alias
+                .as_ref()
+                .or_else(|| import.path.segments.last())?;
+            (!words.contains(&name.0.contents)).then(|| TextEdit {
+                range: Range::new(Position::new(*first, 0), Position::new(last + 1, 0)),
+                new_text: String::new(),
+            })
+        })
+        .collect()
+}
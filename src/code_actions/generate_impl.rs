@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{NoirStruct, UnresolvedType};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::{placeholder, struct_at, struct_type, ActionContext};
+use crate::utils::offset_to_position;
+
+/// On a struct definition, generates an `impl` below it with a `new` constructor taking
+/// every field, unless the struct already has a `new`.
+pub fn generate_impl(ctx: &ActionContext) -> Option<CodeAction> {
+    let (typ, end) = struct_at(ctx)?;
+    let (self_type, _) = struct_type(typ);
+    let parameters = typ
+        .fields
+        .iter()
+        .map(|(field, field_type)| format!("{}: {}", field, field_type))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let initializers = typ
+        .fields
+        .iter()
+        .map(|(field, _)| format!("{}: {}", field, field))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let method = format!(
+        "fn new({}) -> {} {{\n        {} {{ {} }}\n    }}",
+        parameters, self_type, typ.name, initializers
+    );
+    let title = format!("Generate `impl {}` with `new`", typ.name);
+    impl_action(ctx, typ, end, "new", title, method)
+}
+
+/// On a struct definition, generates a field-wise `eq`. Fields that are structs declared
+/// here are compared with their own `eq`, everything else with `==`.
+pub fn generate_eq(ctx: &ActionContext) -> Option<CodeAction> {
+    let (typ, end) = struct_at(ctx)?;
+    let (self_type, _) = struct_type(typ);
+    let comparisons = typ
+        .fields
+        .iter()
+        .map(|(field, field_type)| {
+            if is_local_struct(field_type, ctx) {
+                format!("self.{}.eq(other.{})", field, field)
+            } else if typ.fields.len() == 1 {
+                format!("self.{} == other.{}", field, field)
+            } else {
+                // Parenthesized since `&` binds tighter than `==`.
+                format!("(self.{} == other.{})", field, field)
+            }
+        })
+        .collect::<Vec<_>>();
+    let body = if comparisons.is_empty() {
+        "true".to_string()
+    } else {
+        comparisons.join(" & ")
+    };
+    let method = format!(
+        "fn eq(self, other: {}) -> bool {{\n        {}\n    }}",
+        self_type, body
+    );
+    let title = format!("Generate `eq` for `{}`", typ.name);
+    impl_action(ctx, typ, end, "eq", title, method)
+}
+
+/// On a struct definition, generates a `default` constructor filling every field with a
+/// placeholder value.
+pub fn generate_default(ctx: &ActionContext) -> Option<CodeAction> {
+    let (typ, end) = struct_at(ctx)?;
+    let (self_type, _) = struct_type(typ);
+    let structs = ctx.module.types.iter().collect::<Vec<&NoirStruct>>();
+    let initializers = typ
+        .fields
+        .iter()
+        .map(|(field, field_type)| format!("{}: {}", field, placeholder(field_type, &structs)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let method = format!(
+        "fn default() -> {} {{\n        {} {{ {} }}\n    }}",
+        self_type, typ.name, initializers
+    );
+    let title = format!("Generate `default` for `{}`", typ.name);
+    impl_action(ctx, typ, end, "default", title, method)
+}
+
+fn is_local_struct(typ: &UnresolvedType, ctx: &ActionContext) -> bool {
+    match typ {
+        UnresolvedType::Named(path, _) => path.segments.last().is_some_and(|name| {
+            ctx.module
+                .types
+                .iter()
+                .any(|typ| typ.name.0.contents == name.0.contents)
+        }),
+        _ => false,
+    }
+}
+
+/// Inserts an `impl` holding `method` below the struct ending at `end`, unless the struct
+/// already has a method called `name`.
+fn impl_action(
+    ctx: &ActionContext,
+    typ: &NoirStruct,
+    end: usize,
+    name: &str,
+    title: String,
+    method: String,
+) -> Option<CodeAction> {
+    let type_name = &typ.name.0.contents;
+    let has_method = ctx.module.impls.iter().any(|imp| {
+        imp.type_path
+            .segments
+            .last()
+            .map(|segment| &segment.0.contents)
+            == Some(type_name)
+            && imp
+                .methods
+                .iter()
+                .any(|method| method.def.name.0.contents == name)
+    });
+    if has_method {
+        return None;
+    }
+
+    let (self_type, impl_generics) = struct_type(typ);
+    let new_text = format!(
+        "\n\nimpl{} {} {{\n    {}\n}}",
+        impl_generics, self_type, method
+    );
+    let insert_at = offset_to_position(end, ctx.document, ctx.encoding)?;
+    Some(CodeAction {
+        title,
+        kind: Some(CodeActionKind::new("refactor.rewrite.generate")),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit {
+                    range: Range::new(insert_at, insert_at),
+                    new_text,
+                }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
@@ -0,0 +1,56 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::NoirStruct;
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::{placeholder, ActionContext};
+use crate::utils::{
+    enclosing_function, fresh_name, function_end, offset_to_position, top_level_names,
+};
+
+/// On a free function, appends a `#[test]` below it that calls the function with a
+/// placeholder for every argument, for the user to replace with real inputs.
+pub fn generate_test(ctx: &ActionContext) -> Option<CodeAction> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+    // Methods would need their `impl` type spelled out in the call; keep to free functions.
+    if !ctx.module.functions.iter().any(|f| std::ptr::eq(f, func)) {
+        return None;
+    }
+
+    let name = &func.def.name.0.contents;
+    let taken = top_level_names(ctx.module)
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let test_name = fresh_name(&format!("test_{}", name), &taken);
+    let structs = ctx.module.types.iter().collect::<Vec<&NoirStruct>>();
+    let arguments = func
+        .def
+        .parameters
+        .iter()
+        .map(|(_, typ, _)| placeholder(typ, &structs))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let new_text = format!(
+        "\n\n#[test]\nfn {}() {{\n    {}({});\n}}",
+        test_name, name, arguments
+    );
+
+    let end = function_end(func, ctx.document)?;
+    let insert_at = offset_to_position(end, ctx.document, ctx.encoding)?;
+    Some(CodeAction {
+        title: format!("Generate test for `{}`", name),
+        kind: Some(CodeActionKind::new("refactor.rewrite.generate")),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit {
+                    range: Range::new(insert_at, insert_at),
+                    new_text,
+                }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
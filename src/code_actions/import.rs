@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::{import_position, ActionContext};
+
+/// Offers `use path::to::name;` for each diagnostic in range that reports an unresolved
+/// name, for every module in `definitions` that declares it.
+///
+/// `definitions` maps a module path such as `crate::merkle` to the top-level names it
+/// declares.
+pub fn import_symbol(
+    ctx: &ActionContext,
+    definitions: &HashMap<String, Vec<Arc<str>>>,
+) -> Vec<CodeAction> {
+    let insert_at = import_position(ctx);
+    ctx.params
+        .context
+        .diagnostics
+        .iter()
+        .filter_map(|diagnostic| Some((diagnostic, unresolved_name(&diagnostic.message)?)))
+        .flat_map(|(diagnostic, name)| {
+            definitions
+                .iter()
+                .filter(move |(_, names)| names.iter().any(|n| n.as_ref() == name))
+                .map(move |(module_path, _)| {
+                    let import = format!("{}::{}", module_path, name);
+                    let edit = TextEdit {
+                        range: Range::new(insert_at, insert_at),
+                        new_text: format!("use {};\n", import),
+                    };
+                    CodeAction {
+                        title: format!("Import `{}`", import),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(HashMap::from([(ctx.uri.clone(), vec![edit])])),
+                            ..WorkspaceEdit::default()
+                        }),
+                        ..CodeAction::default()
+                    }
+                })
+        })
+        .collect()
+}
+
+/// The identifier an unresolved-name diagnostic complains about. The frontend quotes it
+/// with backticks (`cannot find `x` in this scope`) or single quotes (`Could not resolve
+/// 'x' in path`).
+fn unresolved_name(message: &str) -> Option<&str> {
+    let lowercase = message.to_lowercase();
+    if !lowercase.contains("cannot find") && !lowercase.contains("could not resolve") {
+        return None;
+    }
+    let quote = message.find(['`', '\''])?;
+    let rest = &message[quote + 1..];
+    let name = &rest[..rest.find(['`', '\''])?];
+    (!name.is_empty()).then_some(name)
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{Expression, ExpressionKind, Pattern, Statement};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, TextEdit, WorkspaceEdit};
+
+use super::{binding_lines, bound_names, ActionContext};
+use crate::utils::{
+    enclosing_function, lvalue_root, span_range, span_text, walk_expression, walk_statements,
+};
+
+/// On the name of an immutable `let`, replaces every later use of it in its scope, nested
+/// blocks included, with the initializer and removes the binding. The initializer is parenthesized wherever it
+/// becomes the operand of another operator.
+pub fn inline_variable(ctx: &ActionContext) -> Option<CodeAction> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+
+    let mut blocks = vec![func.def.body.0.as_slice()];
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Block(block) = &expr.kind {
+            blocks.push(&block.0);
+        }
+    });
+    let (val, rest) = blocks.into_iter().find_map(|statements| {
+        statements
+            .iter()
+            .enumerate()
+            .find_map(|(i, statement)| match statement {
+                Statement::Let(val) => match &val.pattern {
+                    Pattern::Identifier(ident)
+                        if ident.span().start() <= cursor && cursor <= ident.span().end() =>
+                    {
+                        Some((val, &statements[i + 1..]))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+    })?;
+    let name = match &val.pattern {
+        Pattern::Identifier(ident) => ident.0.contents.as_str(),
+        _ => return None,
+    };
+
+    let (usages, operands) = usages(name, rest)?;
+    let initializer = span_text(
+        val.expression.span.start(),
+        val.expression.span.end(),
+        ctx.document,
+    )?;
+    let compound = matches!(
+        val.expression.kind,
+        ExpressionKind::Infix(_) | ExpressionKind::Cast(_) | ExpressionKind::Prefix(_)
+    );
+
+    let mut edits = vec![TextEdit {
+        range: binding_lines(ctx, val)?,
+        new_text: String::new(),
+    }];
+    for usage in usages {
+        let text = if compound && operands.contains(&usage) {
+            format!("({})", initializer)
+        } else {
+            initializer.clone()
+        };
+        edits.push(TextEdit {
+            range: span_range(usage.0, usage.1, ctx.document, ctx.encoding)?,
+            new_text: text,
+        });
+    }
+
+    Some(CodeAction {
+        title: format!("Inline `{}`", name),
+        kind: Some(CodeActionKind::REFACTOR_INLINE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(ctx.uri.clone(), edits)])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+type Span = (u32, u32);
+
+/// Spans of the uses of `name` in `statements`, up to where a new `let` shadows it, and the
+/// spans that sit in operand position. `None` when the variable is assigned to, or bound
+/// again in a nested block or loop, since some of the uses found would then be of another
+/// variable and inlining would change what the program computes.
+fn usages(name: &str, statements: &[Statement]) -> Option<(Vec<Span>, HashSet<Span>)> {
+    let mut usages = Vec::new();
+    let mut operands = HashSet::new();
+    let mut rebound = false;
+    let mut visit = |expr: &Expression| {
+        let span = |e: &Expression| (e.span.start(), e.span.end());
+        match &expr.kind {
+            ExpressionKind::Block(block) => rebound |= binds(&block.0, name),
+            ExpressionKind::For(for_loop) => rebound |= for_loop.identifier.0.contents == name,
+            ExpressionKind::Path(path)
+                if path.segments.len() == 1 && path.segments[0].0.contents == name =>
+            {
+                usages.push(span(expr))
+            }
+            ExpressionKind::Infix(infix) => {
+                operands.insert(span(&infix.lhs));
+                operands.insert(span(&infix.rhs));
+            }
+            ExpressionKind::Prefix(prefix) => {
+                operands.insert(span(&prefix.rhs));
+            }
+            ExpressionKind::Cast(cast) => {
+                operands.insert(span(&cast.lhs));
+            }
+            ExpressionKind::MemberAccess(access) => {
+                operands.insert(span(&access.lhs));
+            }
+            ExpressionKind::MethodCall(call) => {
+                operands.insert(span(&call.object));
+            }
+            ExpressionKind::Index(index) => {
+                operands.insert(span(&index.collection));
+            }
+            _ => {}
+        }
+    };
+
+    for statement in statements {
+        match statement {
+            Statement::Let(val) => {
+                walk_expression(&val.expression, &mut visit);
+                if binds(std::slice::from_ref(statement), name) {
+                    break;
+                }
+            }
+            Statement::Assign(assign) => {
+                if lvalue_root(&assign.lvalue).0.contents == name {
+                    return None;
+                }
+                walk_expression(&assign.expression, &mut visit);
+            }
+            _ => walk_statements(std::slice::from_ref(statement), &mut visit),
+        }
+    }
+    (!rebound).then_some((usages, operands))
+}
+
+/// Whether one of the `let`s of `statements` binds `name`.
+fn binds(statements: &[Statement], name: &str) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Let(val) => {
+            let mut names = Vec::new();
+            bound_names(&val.pattern, &mut names);
+            names.iter().any(|bound| bound.0.contents == name)
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run_action;
+    use super::*;
+
+    fn inlined(text: &str, selection: &str) -> Option<String> {
+        run_action(text, selection, inline_variable)
+    }
+
+    #[test]
+    fn uses_are_replaced_and_the_binding_removed() {
+        let text = "fn main(a: Field) {
+    let b = a;
+    assert(b == foo(b));
+}";
+        let edited = inlined(text, "b = a").unwrap();
+        assert_eq!(edited, "fn main(a: Field) {\n    assert(a == foo(a));\n}");
+    }
+
+    #[test]
+    fn compound_initializers_are_parenthesized_as_operands() {
+        let text = "fn main(a: Field) {
+    let b = a + 1;
+    let c = b * 2;
+    let d = foo(b);
+}";
+        let edited = inlined(text, "b = a").unwrap();
+        assert!(edited.contains("let c = (a + 1) * 2;"));
+        assert!(edited.contains("let d = foo(a + 1);"));
+    }
+
+    #[test]
+    fn uses_stop_at_a_shadowing_let() {
+        let text = "fn main(a: Field) {
+    let b = a;
+    let b = b + 1;
+    assert(b == 2);
+}";
+        let edited = inlined(text, "b = a").unwrap();
+        assert!(edited.contains("let b = a + 1;"));
+        assert!(edited.contains("assert(b == 2);"));
+    }
+
+    #[test]
+    fn names_bound_again_in_nested_blocks_are_not_inlined() {
+        let text = "fn main(a: Field) {
+    let b = a;
+    let c = { let b = 2; b };
+    assert(c == b);
+}";
+        assert_eq!(inlined(text, "b = a"), None);
+        let text = "fn main(a: Field) {
+    let b = a;
+    for b in 0..3 { assert(b != 4); }
+}";
+        assert_eq!(inlined(text, "b = a"), None);
+    }
+
+    #[test]
+    fn assigned_variables_are_not_inlined() {
+        let text = "fn main(a: Field) {
+    let b = a;
+    b = 2;
+    assert(b == a);
+}";
+        assert_eq!(inlined(text, "b = a"), None);
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, TextEdit, WorkspaceEdit};
+
+use super::ActionContext;
+use crate::utils::position_to_offset;
+
+/// For each type-mismatch diagnostic in range between two integer or `Field` types, offers
+/// to cast the offending expression to the expected type. Casts that can lose bits say so
+/// in their title.
+pub fn insert_cast(ctx: &ActionContext) -> Vec<CodeAction> {
+    ctx.params
+        .context
+        .diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let (expected, found) = mismatched_types(&diagnostic.message)?;
+            let (expected_width, found_width) = (width(expected)?, width(found)?);
+            let start = position_to_offset(diagnostic.range.start, ctx.document, ctx.encoding)?;
+            let end = position_to_offset(diagnostic.range.end, ctx.document, ctx.encoding)?;
+            let text = ctx.document.get_slice(start..end)?.to_string();
+            if text.trim().is_empty() {
+                return None;
+            }
+            let simple = text
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+            let new_text = if simple {
+                format!("{} as {}", text, expected)
+            } else {
+                format!("({}) as {}", text, expected)
+            };
+
+            let truncates = match (expected_width, found_width) {
+                (Width::Field, _) => false,
+                (_, Width::Field) => true,
+                (Width::Integer(to_signed, to_bits), Width::Integer(from_signed, from_bits)) => {
+                    to_bits < from_bits || to_signed != from_signed
+                }
+            };
+            let title = if truncates {
+                format!("Cast to `{}` (may truncate `{}` values)", expected, found)
+            } else {
+                format!("Cast to `{}`", expected)
+            };
+            Some(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        ctx.uri.clone(),
+                        vec![TextEdit {
+                            range: diagnostic.range,
+                            new_text,
+                        }],
+                    )])),
+                    ..WorkspaceEdit::default()
+                }),
+                ..CodeAction::default()
+            })
+        })
+        .collect()
+}
+
+enum Width {
+    Field,
+    /// Signedness and bit size.
+    Integer(bool, u32),
+}
+
+fn width(typ: &str) -> Option<Width> {
+    if typ == "Field" {
+        return Some(Width::Field);
+    }
+    let signed = match typ.chars().next()? {
+        'u' => false,
+        'i' => true,
+        _ => return None,
+    };
+    Some(Width::Integer(signed, typ[1..].parse().ok()?))
+}
+
+/// The expected and found types of a mismatch diagnostic. The frontend words these as
+/// `Expected type u64, found type u32`, other tools as `expected u64, found u32`.
+fn mismatched_types(message: &str) -> Option<(&str, &str)> {
+    // ASCII lowercasing keeps byte offsets valid in `message`.
+    let lowercase = message.to_ascii_lowercase();
+    let expected = lowercase.find("expected ")? + "expected ".len();
+    let found = lowercase.find("found ")? + "found ".len();
+    let type_at = |offset: usize| {
+        let rest = &message[offset..];
+        let rest = rest.strip_prefix("type ").unwrap_or(rest);
+        let rest = rest.trim_start_matches(['`', '\'']);
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        Some(&rest[..end]).filter(|typ| !typ.is_empty())
+    };
+    Some((type_at(expected)?, type_at(found)?))
+}
@@ -0,0 +1,616 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use noirc_frontend::{
+    Expression, ExpressionKind, Ident, LetStatement, NoirFunction, NoirStruct, ParsedModule, Path,
+    PathKind, Pattern, Statement, UnresolvedType,
+};
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    AnnotatedTextEdit, ChangeAnnotation, CodeAction, CodeActionParams, DocumentChangeOperation,
+    DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp,
+    TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::utils::{
+    all_functions, block_end, byte_offset, import_path, position_to_byte, position_to_offset,
+    span_offset, span_position, statement_span, walk_expression, walk_statements, PositionEncoding,
+};
+
+mod convert_assertion;
+mod convert_literal;
+mod convert_loop;
+mod create_function;
+mod extract_function;
+mod extract_module;
+mod extract_variable;
+mod fill_struct_fields;
+mod fix_all;
+mod generate_impl;
+mod generate_test;
+mod import;
+mod inline_variable;
+mod insert_cast;
+mod move_item;
+mod prover_toml;
+mod split_import;
+mod surround;
+mod type_annotation;
+mod unused_variable;
+
+pub use convert_assertion::convert_assertion;
+pub use convert_literal::convert_literal;
+pub use convert_loop::convert_loop;
+pub use create_function::create_function;
+pub use extract_function::extract_function;
+pub use extract_module::extract_module;
+pub use extract_variable::extract_variable;
+pub use fill_struct_fields::fill_struct_fields;
+pub use fix_all::fix_all;
+pub use generate_impl::{generate_default, generate_eq, generate_impl};
+pub use generate_test::generate_test;
+pub use import::import_symbol;
+pub use inline_variable::inline_variable;
+pub use insert_cast::insert_cast;
+pub use move_item::move_item;
+pub use prover_toml::sync_prover_toml;
+pub use split_import::split_import;
+pub use surround::surround;
+pub use type_annotation::type_annotation;
+pub use unused_variable::unused_variable;
+
+/// Every provider of code actions, by the id `codeAction/resolve` runs it again with.
+pub const PROVIDERS: [&str; 22] = [
+    "import_symbol",
+    "split_import",
+    "create_function",
+    "extract_variable",
+    "extract_function",
+    "surround",
+    "inline_variable",
+    "extract_module",
+    "move_item",
+    "generate_impl",
+    "generate_eq",
+    "generate_default",
+    "convert_assertion",
+    "convert_literal",
+    "type_annotation",
+    "fill_struct_fields",
+    "convert_loop",
+    "unused_variable",
+    "insert_cast",
+    "fix_all",
+    "generate_test",
+    "sync_prover_toml",
+];
+
+/// The actions the provider `id`, one of [`PROVIDERS`], offers in `ctx`. `definitions`
+/// gives the top-level names of the other files, keyed by the path to import them by, and
+/// is only called by the providers that import.
+pub fn provide(
+    id: &str,
+    ctx: &ActionContext,
+    definitions: impl FnOnce() -> HashMap<String, Vec<Arc<str>>>,
+) -> Vec<CodeAction> {
+    match id {
+        "import_symbol" => import_symbol(ctx, &definitions()),
+        "split_import" => split_import(ctx).into_iter().collect(),
+        "create_function" => create_function(ctx),
+        "extract_variable" => extract_variable(ctx).into_iter().collect(),
+        "extract_function" => extract_function(ctx).into_iter().collect(),
+        "surround" => surround(ctx),
+        "inline_variable" => inline_variable(ctx).into_iter().collect(),
+        "extract_module" => extract_module(ctx).into_iter().collect(),
+        "move_item" => move_item(ctx),
+        "generate_impl" => generate_impl(ctx).into_iter().collect(),
+        "generate_eq" => generate_eq(ctx).into_iter().collect(),
+        "generate_default" => generate_default(ctx).into_iter().collect(),
+        "convert_assertion" => convert_assertion(ctx),
+        "convert_literal" => convert_literal(ctx).into_iter().collect(),
+        "type_annotation" => type_annotation(ctx).into_iter().collect(),
+        "fill_struct_fields" => fill_struct_fields(ctx).into_iter().collect(),
+        "convert_loop" => convert_loop(ctx).into_iter().collect(),
+        "unused_variable" => unused_variable(ctx),
+        "insert_cast" => insert_cast(ctx),
+        "fix_all" => fix_all(ctx).into_iter().collect(),
+        "generate_test" => generate_test(ctx).into_iter().collect(),
+        "sync_prover_toml" => sync_prover_toml(ctx),
+        _ => Vec::new(),
+    }
+}
+
+/// What every code action gets to look at: the request and the current state of the
+/// document it was made in.
+pub struct ActionContext<'a> {
+    pub params: &'a CodeActionParams,
+    pub uri: &'a Url,
+    pub document: &'a Rope,
+    pub module: &'a ParsedModule,
+    /// Every other file of the document's crate, opened or not, with its tree, for actions
+    /// whose edits reach beyond this one.
+    pub crate_files: &'a [(Url, Rope, Arc<ParsedModule>)],
+    /// Whether the client can show change annotations, see [`multi_file_edit`].
+    pub annotate_changes: bool,
+    /// How the client counts the columns of the positions in the request and in the edits.
+    pub encoding: PositionEncoding,
+}
+
+impl ActionContext<'_> {
+    /// Where the selection starts, as the frontend's spans count offsets.
+    fn cursor(&self) -> Option<u32> {
+        position_to_byte(self.params.range.start, self.document, self.encoding)
+    }
+}
+
+/// The selection as offsets the frontend's spans can be compared with, with surrounding
+/// whitespace trimmed off so that selecting a little too much still matches the expression.
+fn selection_offsets(ctx: &ActionContext) -> Option<(u32, u32)> {
+    let mut start = position_to_offset(ctx.params.range.start, ctx.document, ctx.encoding)?;
+    let mut end = position_to_offset(ctx.params.range.end, ctx.document, ctx.encoding)?;
+    while start < end && ctx.document.get_char(start)?.is_whitespace() {
+        start += 1;
+    }
+    while start < end && ctx.document.get_char(end - 1)?.is_whitespace() {
+        end -= 1;
+    }
+    (start < end).then_some((
+        byte_offset(start, ctx.document)?,
+        byte_offset(end, ctx.document)?,
+    ))
+}
+
+/// The statements lying entirely inside `start..end`, from whichever block holds them. `None`
+/// when the selection cuts through a statement rather than covering whole ones.
+fn selected_statements<'a>(
+    statements: &'a [Statement],
+    start: u32,
+    end: u32,
+) -> Option<&'a [Statement]> {
+    let spans = statements.iter().map(statement_span).collect::<Vec<_>>();
+    let inside = |span: &Option<(u32, u32)>| span.is_some_and(|(s, e)| start <= s && e <= end);
+    let first = spans.iter().position(inside);
+    let last = spans.iter().rposition(inside);
+    if let (Some(first), Some(last)) = (first, last) {
+        let cut = spans
+            .iter()
+            .any(|span| span.is_some_and(|(s, e)| s < end && start < e && !inside(&Some((s, e)))));
+        return (!cut).then(|| &statements[first..=last]);
+    }
+
+    // Nothing selected at this level, so look inside the statement that contains the selection.
+    let index = spans
+        .iter()
+        .position(|span| span.is_some_and(|(s, e)| s <= start && end <= e))?;
+    let mut nested = None;
+    let mut visit = |expr: &'a Expression| {
+        if let ExpressionKind::Block(block) = &expr.kind {
+            if nested.is_none() && expr.span.start() <= start && end <= expr.span.end() {
+                nested = selected_statements(&block.0, start, end);
+            }
+        }
+    };
+    match &statements[index] {
+        Statement::Let(val) => walk_expression(&val.expression, &mut visit),
+        Statement::Assign(assign) => walk_expression(&assign.expression, &mut visit),
+        Statement::Expression(expr) | Statement::Semi(expr) => walk_expression(expr, &mut visit),
+        Statement::Constrain(_) | Statement::Error => {}
+    }
+    nested
+}
+
+/// The start of the line after the last `use`, or the top of the file when there is none.
+fn import_position(ctx: &ActionContext) -> Position {
+    imports_end(ctx.module, ctx.document, ctx.encoding)
+}
+
+/// [`import_position`] for any parsed document, not just the one the action was made in.
+fn imports_end(module: &ParsedModule, document: &Rope, encoding: PositionEncoding) -> Position {
+    let last_import_end = module
+        .imports
+        .iter()
+        .filter_map(|import| import.path.segments.last())
+        .map(|segment| segment.span().end())
+        .max();
+    last_import_end
+        .and_then(|end| span_position(end, document, encoding))
+        .map_or(Position::new(0, 0), |end| Position::new(end.line + 1, 0))
+}
+
+/// The struct definition the cursor is in, with the char offset just past its closing
+/// brace.
+fn struct_at<'a>(ctx: &ActionContext<'a>) -> Option<(&'a NoirStruct, usize)> {
+    let cursor = position_to_offset(ctx.params.range.start, ctx.document, ctx.encoding)?;
+    ctx.module.types.iter().find_map(|typ| {
+        let end = block_end(
+            span_offset(typ.name.span().end(), ctx.document)?,
+            ctx.document,
+        )?;
+        let start = span_offset(typ.name.span().start(), ctx.document)?;
+        let line_start = ctx.document.line_to_char(ctx.document.char_to_line(start));
+        (line_start <= cursor && cursor <= end).then_some((typ, end))
+    })
+}
+
+/// `Name` or `Name<T, U>`, and the matching `<T, U>` to put after `impl`.
+fn struct_type(typ: &NoirStruct) -> (String, String) {
+    if typ.generics.is_empty() {
+        return (typ.name.0.contents.clone(), String::new());
+    }
+    let generics = typ
+        .generics
+        .iter()
+        .map(|generic| generic.0.contents.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    (
+        format!("{}<{}>", typ.name.0.contents, generics),
+        format!("<{}>", generics),
+    )
+}
+
+/// A value of type `typ` to fill in where the user still has to write the real one. Structs
+/// declared in `structs` are built field by field.
+fn placeholder(typ: &UnresolvedType, structs: &[&NoirStruct]) -> String {
+    match typ {
+        UnresolvedType::Bool(_) => "false".to_string(),
+        UnresolvedType::Array(Some(length), element) => {
+            format!("[{}; {}]", placeholder(element, structs), length)
+        }
+        UnresolvedType::String(_) => "\"\"".to_string(),
+        UnresolvedType::Unit => "()".to_string(),
+        UnresolvedType::Tuple(elements) => format!(
+            "({})",
+            elements
+                .iter()
+                .map(|element| placeholder(element, structs))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        UnresolvedType::Named(path, _) => {
+            let name = path.segments.last().map(|segment| &segment.0.contents);
+            match structs
+                .iter()
+                .find(|typ| Some(&typ.name.0.contents) == name)
+            {
+                Some(typ) => format!(
+                    "{} {{ {} }}",
+                    typ.name,
+                    typ.fields
+                        .iter()
+                        .map(|(field, typ)| format!("{}: {}", field, placeholder(typ, structs)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                None => "0".to_string(),
+            }
+        }
+        _ => "0".to_string(),
+    }
+}
+
+/// The names `pattern` binds, through `mut`, tuples and struct fields.
+fn bound_names<'a>(pattern: &'a Pattern, names: &mut Vec<&'a Ident>) {
+    match pattern {
+        Pattern::Identifier(ident) => names.push(ident),
+        Pattern::Mutable(pattern, _) => bound_names(pattern, names),
+        Pattern::Tuple(patterns, _) => patterns.iter().for_each(|p| bound_names(p, names)),
+        Pattern::Struct(_, fields, _) => fields.iter().for_each(|(_, p)| bound_names(p, names)),
+    }
+}
+
+/// The whole lines the `let` occupies, through the end of the line holding its `;`.
+fn binding_lines(ctx: &ActionContext, val: &LetStatement) -> Option<Range> {
+    let pattern_start = match &val.pattern {
+        Pattern::Identifier(ident) => ident.span().start(),
+        Pattern::Mutable(_, span) | Pattern::Tuple(_, span) | Pattern::Struct(_, _, span) => {
+            span.start()
+        }
+    };
+    statement_lines(ctx, pattern_start, val.expression.span.end())
+}
+
+/// The whole lines of a statement starting at `start` whose last expression ends at `end`,
+/// through the end of the line holding its `;`.
+fn statement_lines(ctx: &ActionContext, start: u32, end: u32) -> Option<Range> {
+    let first_line = ctx.document.char_to_line(span_offset(start, ctx.document)?);
+    let end = span_offset(end, ctx.document)?;
+    let semicolon = ctx.document.get_chars_at(end)?.position(|c| c == ';')?;
+    let last_line = ctx.document.char_to_line(end + semicolon);
+    Some(Range::new(
+        Position::new(first_line as u32, 0),
+        Position::new(last_line as u32 + 1, 0),
+    ))
+}
+
+/// The edit for a refactoring that touches several files. With `annotate_changes`, when the
+/// client supports them, each file's changes are annotated as needing confirmation, so the
+/// editor previews them and lets the user approve or drop them file by file.
+pub fn multi_file_edit(
+    annotate_changes: bool,
+    mut operations: Vec<DocumentChangeOperation>,
+) -> WorkspaceEdit {
+    let mut annotations = HashMap::new();
+    if annotate_changes {
+        for operation in &mut operations {
+            let uri = match operation {
+                DocumentChangeOperation::Edit(document_edit) => {
+                    let id = document_edit.text_document.uri.to_string();
+                    document_edit.edits = std::mem::take(&mut document_edit.edits)
+                        .into_iter()
+                        .map(|edit| match edit {
+                            OneOf::Left(text_edit) => OneOf::Right(AnnotatedTextEdit {
+                                text_edit,
+                                annotation_id: id.clone(),
+                            }),
+                            annotated => annotated,
+                        })
+                        .collect();
+                    document_edit.text_document.uri.clone()
+                }
+                DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                    create.annotation_id = Some(create.uri.to_string());
+                    create.uri.clone()
+                }
+                DocumentChangeOperation::Op(_) => continue,
+            };
+            annotations
+                .entry(uri.to_string())
+                .or_insert_with(|| ChangeAnnotation {
+                    label: uri
+                        .path_segments()
+                        .and_then(|mut segments| segments.next_back())
+                        .unwrap_or(uri.as_str())
+                        .to_string(),
+                    needs_confirmation: Some(true),
+                    description: Some(uri.path().to_string()),
+                });
+        }
+    }
+    WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        change_annotations: (!annotations.is_empty()).then_some(annotations),
+        ..WorkspaceEdit::default()
+    }
+}
+
+pub fn text_document_edit(uri: &Url, edits: Vec<TextEdit>) -> DocumentChangeOperation {
+    DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: uri.clone(),
+            version: None,
+        },
+        edits: edits.into_iter().map(OneOf::Left).collect(),
+    })
+}
+
+/// The line a function starts on, moved up over any attribute lines directly above it.
+fn item_first_line(ctx: &ActionContext, func: &NoirFunction) -> Option<u32> {
+    let start = span_offset(func.def.name.span().start(), ctx.document)?;
+    let mut line = ctx.document.char_to_line(start);
+    while line > 0 {
+        let above = ctx.document.get_line(line - 1)?.to_string();
+        if !above.trim_start().starts_with("#[") {
+            break;
+        }
+        line -= 1;
+    }
+    Some(line as u32)
+}
+
+/// Single-segment names the functions call, construct or otherwise refer to.
+fn names_used(functions: &[&NoirFunction]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    functions.iter().for_each(|func| {
+        walk_statements(&func.def.body.0, &mut |expr| {
+            let path = match &expr.kind {
+                ExpressionKind::Path(path) => path,
+                ExpressionKind::Call(call) => &call.func_name,
+                ExpressionKind::Constructor(constructor) => &constructor.type_name,
+                _ => return,
+            };
+            if let [name] = path.segments.as_slice() {
+                names.insert(name.0.contents.clone());
+            }
+        })
+    });
+    names
+}
+
+/// The paths of `module`, the file of the module `here`, written with more than one segment
+/// that lead to `target`, a `crate::` path: from `crate::`, through one of the file's own
+/// submodules or through a module it imports. Single names come from `use`s, which the
+/// actions moving items repoint on their own.
+fn qualified_references<'m>(module: &'m ParsedModule, here: &str, target: &str) -> Vec<&'m Path> {
+    paths_in(module)
+        .into_iter()
+        .filter(|path| path.segments.len() > 1 && full_path(module, here, path) == target)
+        .collect()
+}
+
+/// `path` as written in `module`, the file of the module `here`, spelled out from the root
+/// it starts at: `crate::` for the file's own submodules, or whatever a `use` brought its
+/// first segment in from.
+fn full_path(module: &ParsedModule, here: &str, path: &Path) -> String {
+    let written = import_path(path);
+    let submodule = path.segments.first().is_some_and(|first| {
+        module
+            .module_decls
+            .iter()
+            .any(|decl| decl.0.contents == first.0.contents)
+    });
+    match path.kind {
+        PathKind::Plain if submodule => format!("{}::{}", here, written),
+        PathKind::Plain => crate::stdlib::expand(module, &written),
+        _ => written,
+    }
+}
+
+/// Every path in the signatures, bodies and struct fields of `module`.
+fn paths_in(module: &ParsedModule) -> Vec<&Path> {
+    fn type_paths<'a>(typ: &'a UnresolvedType, paths: &mut Vec<&'a Path>) {
+        match typ {
+            UnresolvedType::Named(path, generics) => {
+                paths.push(path);
+                generics.iter().for_each(|typ| type_paths(typ, paths));
+            }
+            UnresolvedType::Array(_, element) => type_paths(element, paths),
+            UnresolvedType::Tuple(elements) => {
+                elements.iter().for_each(|typ| type_paths(typ, paths))
+            }
+            _ => {}
+        }
+    }
+    fn let_types<'a>(statements: &'a [Statement], paths: &mut Vec<&'a Path>) {
+        statements.iter().for_each(|statement| {
+            if let Statement::Let(val) = statement {
+                type_paths(&val.r#type, paths);
+            }
+        })
+    }
+
+    let mut paths = Vec::new();
+    module
+        .types
+        .iter()
+        .flat_map(|typ| &typ.fields)
+        .for_each(|(_, typ)| type_paths(typ, &mut paths));
+    all_functions(module).for_each(|func| {
+        func.def
+            .parameters
+            .iter()
+            .for_each(|(_, typ, _)| type_paths(typ, &mut paths));
+        type_paths(&func.def.return_type, &mut paths);
+        let_types(&func.def.body.0, &mut paths);
+        walk_statements(&func.def.body.0, &mut |expr| match &expr.kind {
+            ExpressionKind::Path(path) => paths.push(path),
+            ExpressionKind::Call(call) => paths.push(&call.func_name),
+            ExpressionKind::Constructor(constructor) => paths.push(&constructor.type_name),
+            ExpressionKind::Block(block) => let_types(&block.0, &mut paths),
+            _ => {}
+        });
+    });
+    paths
+}
+
+/// Runs `action` on `text` with the first occurrence of `selection` selected, and the
+/// warnings the server reports for `text` in the request, and returns the text the edit
+/// leaves in this document.
+#[cfg(test)]
+fn run_action(
+    text: &str,
+    selection: &str,
+    action: impl FnOnce(&ActionContext) -> Option<CodeAction>,
+) -> Option<String> {
+    let uri = Url::parse("file:///src/main.nr").unwrap();
+    let edits = run_crate_action(text, selection, &[], action)?
+        .edit?
+        .changes?
+        .remove(&uri)?;
+    apply_edits(text, edits)
+}
+
+/// [`run_action`] in `src/main.nr` of a crate made of it and `crate_files`, giving the action
+/// back to look at the edits to every file.
+#[cfg(test)]
+fn run_crate_action(
+    text: &str,
+    selection: &str,
+    crate_files: &[(Url, Rope, Arc<ParsedModule>)],
+    action: impl FnOnce(&ActionContext) -> Option<CodeAction>,
+) -> Option<CodeAction> {
+    use tower_lsp::lsp_types::{
+        CodeActionContext, PartialResultParams, TextDocumentIdentifier, WorkDoneProgressParams,
+    };
+
+    let uri = Url::parse("file:///src/main.nr").unwrap();
+    let document = Rope::from_str(text);
+    let (module, _) = noirc_frontend::parse_program(text);
+    let encoding = PositionEncoding::Utf16;
+    let start = document.byte_to_char(text.find(selection).unwrap());
+    let end = start + selection.chars().count();
+    let position = |offset| crate::utils::offset_to_position(offset, &document, encoding).unwrap();
+    let params = CodeActionParams {
+        text_document: TextDocumentIdentifier::new(uri.clone()),
+        range: Range::new(position(start), position(end)),
+        context: CodeActionContext {
+            diagnostics: crate::diagnostics::unused_variables(&module, &document, encoding),
+            ..CodeActionContext::default()
+        },
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    };
+    let ctx = ActionContext {
+        params: &params,
+        uri: &uri,
+        document: &document,
+        module: &module,
+        crate_files,
+        annotate_changes: false,
+        encoding,
+    };
+    action(&ctx)
+}
+
+/// `text` with `edits`, made against it, applied.
+#[cfg(test)]
+fn apply_edits(text: &str, mut edits: Vec<TextEdit>) -> Option<String> {
+    let document = Rope::from_str(text);
+    let encoding = PositionEncoding::Utf16;
+    edits.sort_by_key(|edit| edit.range.start);
+    let mut edited = document.clone();
+    for edit in edits.iter().rev() {
+        let start = position_to_offset(edit.range.start, &document, encoding)?;
+        let end = position_to_offset(edit.range.end, &document, encoding)?;
+        edited.remove(start..end);
+        edited.insert(start, &edit.new_text);
+    }
+    Some(edited.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edits(annotate_changes: bool) -> WorkspaceEdit {
+        let operations = ["file:///src/main.nr", "file:///src/hash.nr"]
+            .into_iter()
+            .map(|uri| {
+                let edit = TextEdit {
+                    range: Range::default(),
+                    new_text: "renamed".to_string(),
+                };
+                text_document_edit(&Url::parse(uri).unwrap(), vec![edit])
+            })
+            .collect();
+        multi_file_edit(annotate_changes, operations)
+    }
+
+    #[test]
+    fn each_file_needs_confirmation_when_annotated() {
+        let edit = edits(true);
+        let annotations = edit.change_annotations.unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert!(annotations
+            .values()
+            .all(|annotation| annotation.needs_confirmation == Some(true)));
+        let operations = match edit.document_changes {
+            Some(DocumentChanges::Operations(operations)) => operations,
+            changes => panic!("expected operations, got {:?}", changes),
+        };
+        for operation in operations {
+            let document_edit = match operation {
+                DocumentChangeOperation::Edit(document_edit) => document_edit,
+                operation => panic!("expected an edit, got {:?}", operation),
+            };
+            let id = document_edit.text_document.uri.to_string();
+            assert!(matches!(&document_edit.edits[..],
+                [OneOf::Right(annotated)] if annotated.annotation_id == id));
+        }
+    }
+
+    #[test]
+    fn edits_are_plain_without_annotations() {
+        assert!(edits(false).change_annotations.is_none());
+    }
+}
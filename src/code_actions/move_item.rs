@@ -0,0 +1,282 @@
+use noirc_frontend::{NoirFunction, ParsedModule, PathKind};
+use ropey::Rope;
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Position, Range, TextEdit};
+
+use super::{
+    import_position, imports_end, item_first_line, multi_file_edit, names_used,
+    qualified_references, text_document_edit, ActionContext,
+};
+use crate::utils::{
+    enclosing_function, function_end, import_path, module_path, offset_to_position, span_offset,
+    span_range, span_text, top_level_names, PositionEncoding,
+};
+
+/// Offers to move the function under the cursor into each other module of the crate that
+/// doesn't already declare its name.
+///
+/// The target gets the function and imports for what it uses from here, this file imports
+/// it back if it is still called here, and `use`s of it and paths to it in the rest of the
+/// crate are pointed at the target.
+pub fn move_item(ctx: &ActionContext) -> Vec<CodeAction> {
+    let (func, current_path, (first, last), body) = match moved_function(ctx) {
+        Some(moved) => moved,
+        None => return Vec::new(),
+    };
+    let name = &func.def.name.0.contents;
+    let targets = ctx
+        .crate_files
+        .iter()
+        .filter_map(|(uri, document, module)| {
+            let path = module_path(uri).filter(|path| *path != current_path)?;
+            Some((uri, document, path, module))
+        })
+        .collect::<Vec<_>>();
+    targets
+        .iter()
+        .filter(|(_, _, _, module)| !top_level_names(module).contains(name))
+        .filter_map(|(uri, document, path, module)| {
+            // This file: drop the function, importing it back if the rest still calls it.
+            let mut edits = Vec::new();
+            let staying = ctx
+                .module
+                .functions
+                .iter()
+                .filter(|f| !std::ptr::eq(*f, func))
+                .collect::<Vec<_>>();
+            if names_used(&staying).contains(name) {
+                let at = import_position(ctx);
+                edits.push(TextEdit {
+                    range: Range::new(at, at),
+                    new_text: format!("use {}::{};\n", path, name),
+                });
+            }
+            edits.push(TextEdit {
+                range: Range::new(Position::new(first, 0), Position::new(last + 1, 0)),
+                new_text: String::new(),
+            });
+            let mut operations = vec![text_document_edit(ctx.uri, edits)];
+
+            // The target: imports of what the function uses from here, then the function.
+            let used = names_used(&[func]);
+            let imports = top_level_names(ctx.module)
+                .into_iter()
+                .filter(|declared| declared != name && used.contains(declared))
+                .map(|declared| format!("use {}::{};\n", current_path, declared))
+                .collect::<String>();
+            let mut target_edits = repointed_references(
+                document,
+                module,
+                path,
+                &current_path,
+                path,
+                name,
+                ctx.encoding,
+            );
+            if !imports.is_empty() {
+                let at = imports_end(module, document, ctx.encoding);
+                target_edits.push(TextEdit {
+                    range: Range::new(at, at),
+                    new_text: imports,
+                });
+            }
+            let end = offset_to_position(document.len_chars(), document, ctx.encoding)?;
+            let separator = if document.to_string().ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            };
+            target_edits.push(TextEdit {
+                range: Range::new(end, end),
+                new_text: format!("{}{}", separator, body.trim_end()),
+            });
+            operations.push(text_document_edit(uri, target_edits));
+
+            // Other files: repoint `use current::name` and `current::name` at the target.
+            for (other_uri, other_document, other_path, other_module) in &targets {
+                if other_uri == uri {
+                    continue;
+                }
+                let edits = repointed_references(
+                    other_document,
+                    other_module,
+                    other_path,
+                    &current_path,
+                    path,
+                    name,
+                    ctx.encoding,
+                );
+                if !edits.is_empty() {
+                    operations.push(text_document_edit(other_uri, edits));
+                }
+            }
+
+            Some(CodeAction {
+                title: format!("Move `{}` to `{}`", name, path),
+                kind: Some(CodeActionKind::new("refactor.move")),
+                edit: Some(multi_file_edit(ctx.annotate_changes, operations)),
+                ..CodeAction::default()
+            })
+        })
+        .collect()
+}
+
+/// The free function under the cursor, the path of this module, the lines the function
+/// spans and its text, attributes included.
+fn moved_function<'a>(
+    ctx: &ActionContext<'a>,
+) -> Option<(&'a NoirFunction, String, (u32, u32), String)> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+    if !ctx.module.functions.iter().any(|f| std::ptr::eq(f, func)) {
+        return None;
+    }
+    let current_path = module_path(ctx.uri)?;
+    let first = item_first_line(ctx, func)?;
+    let last = ctx.document.char_to_line(function_end(func, ctx.document)?) as u32;
+    let start = ctx.document.try_line_to_char(first as usize).ok()?;
+    let end = ctx
+        .document
+        .try_line_to_char((last as usize + 1).min(ctx.document.len_lines()))
+        .ok()?;
+    let body = ctx.document.get_slice(start..end)?.to_string();
+    Some((func, current_path, (first, last), body))
+}
+
+const CRATE_PREFIX: &str = "crate::";
+
+/// Edits pointing `use from::name` and paths such as `from::name` at `to::name` in
+/// `module`, the file of the module `here`. In the module `to` itself, the line with the
+/// import is removed instead, and the paths are left as just `name`.
+fn repointed_references(
+    document: &Rope,
+    module: &ParsedModule,
+    here: &str,
+    from: &str,
+    to: &str,
+    name: &str,
+    encoding: PositionEncoding,
+) -> Vec<TextEdit> {
+    let in_target = here == to;
+    let original = format!("{}::{}", from, name);
+    let moved = format!("{}::{}", to, name);
+    let imports = module.imports.iter().filter_map(|import| {
+        let last = import.path.segments.last()?;
+        if last.0.contents != name || import_path(&import.path) != original {
+            return None;
+        }
+        let first = import.path.segments.first()?;
+        if in_target {
+            let line = document.char_to_line(span_offset(first.span().start(), document)?) as u32;
+            return Some(TextEdit {
+                range: Range::new(Position::new(line, 0), Position::new(line + 1, 0)),
+                new_text: String::new(),
+            });
+        }
+        // The `crate::` prefix is not part of the segments, so it stays as written.
+        let replacement = moved.strip_prefix(CRATE_PREFIX).unwrap_or(&moved);
+        Some(TextEdit {
+            range: span_range(first.span().start(), last.span().end(), document, encoding)?,
+            new_text: replacement.to_string(),
+        })
+    });
+    let paths = qualified_references(module, here, &original)
+        .into_iter()
+        .filter_map(|path| {
+            let (first, last) = (path.segments.first()?, path.segments.last()?);
+            let mut start = first.span().start();
+            let replacement = match path.kind {
+                PathKind::Crate if in_target => {
+                    // Down to the bare name, so the `crate::` in front goes too.
+                    start = start.checked_sub(CRATE_PREFIX.len() as u32)?;
+                    (span_text(start, first.span().start(), document)? == CRATE_PREFIX)
+                        .then_some(name)?
+                }
+                _ if in_target => name,
+                PathKind::Crate => moved.strip_prefix(CRATE_PREFIX).unwrap_or(&moved),
+                _ => moved.as_str(),
+            };
+            Some(TextEdit {
+                range: span_range(start, last.span().end(), document, encoding)?,
+                new_text: replacement.to_string(),
+            })
+        });
+    imports.chain(paths).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use noirc_frontend::parse_program;
+
+    use super::*;
+
+    fn repointed(text: &str, here: &str) -> String {
+        let document = Rope::from_str(text);
+        let (module, _) = parse_program(text);
+        let mut edits = repointed_references(
+            &document,
+            &module,
+            here,
+            "crate::hash",
+            "crate::utils",
+            "pedersen",
+            PositionEncoding::Utf16,
+        );
+        edits.sort_by_key(|edit| edit.range.start);
+        let mut edited = document.clone();
+        for edit in edits.iter().rev() {
+            let start = crate::utils::position_to_offset(
+                edit.range.start,
+                &document,
+                PositionEncoding::Utf16,
+            )
+            .unwrap();
+            let end = crate::utils::position_to_offset(
+                edit.range.end,
+                &document,
+                PositionEncoding::Utf16,
+            )
+            .unwrap();
+            edited.remove(start..end);
+            edited.insert(start, &edit.new_text);
+        }
+        edited.to_string()
+    }
+
+    #[test]
+    fn imports_and_qualified_paths_follow_the_function() {
+        let text = "use crate::hash::pedersen;
+mod hash;
+fn main(x: Field) {
+    let a = pedersen(x);
+    let b = hash::pedersen(x);
+    let c = crate::hash::pedersen(x);
+    let d = hash::other(x);
+}";
+        let edited = repointed(text, "crate");
+        assert!(edited.contains("use crate::utils::pedersen;"));
+        assert!(edited.contains("let b = crate::utils::pedersen(x);"));
+        assert!(edited.contains("let c = crate::utils::pedersen(x);"));
+        assert!(edited.contains("let d = hash::other(x);"));
+    }
+
+    #[test]
+    fn paths_through_imported_modules_are_repointed() {
+        let text = "use crate::hash;
+fn main(x: Field) -> Field {
+    hash::pedersen(x)
+}";
+        let edited = repointed(text, "crate::merkle");
+        assert!(edited.contains("    crate::utils::pedersen(x)"));
+    }
+
+    #[test]
+    fn the_target_refers_to_the_function_by_name() {
+        let text = "use crate::hash::pedersen;
+fn root(x: Field) -> Field {
+    crate::hash::pedersen(x)
+}";
+        let edited = repointed(text, "crate::utils");
+        assert!(!edited.contains("use crate::hash::pedersen;"));
+        assert!(edited.contains("    pedersen(x)"));
+    }
+}
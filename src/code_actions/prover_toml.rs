@@ -0,0 +1,41 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Command};
+
+use super::ActionContext;
+use crate::prover_toml::{ADD_MAIN_PARAMETERS, GENERATE_PROVER_TOML};
+use crate::utils::enclosing_function;
+
+/// In `fn main`, offers to bring `Prover.toml` and the parameter list in step. The edits
+/// depend on a file outside the editor, so they are left to `workspace/executeCommand`.
+pub fn sync_prover_toml(ctx: &ActionContext) -> Vec<CodeAction> {
+    let in_main = ctx
+        .cursor()
+        .and_then(|cursor| enclosing_function(ctx.module, ctx.document, cursor))
+        .is_some_and(|func| func.def.name.0.contents == "main");
+    if !in_main {
+        return Vec::new();
+    }
+
+    [
+        (
+            "Generate `Prover.toml` inputs from `main`",
+            GENERATE_PROVER_TOML,
+        ),
+        (
+            "Add parameters to `main` from `Prover.toml`",
+            ADD_MAIN_PARAMETERS,
+        ),
+    ]
+    .into_iter()
+    .map(|(title, command)| CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::new("refactor.rewrite.generate")),
+        command: Some(Command {
+            title: title.to_string(),
+            command: command.to_string(),
+            arguments: Some(vec![Value::String(ctx.uri.to_string())]),
+        }),
+        ..CodeAction::default()
+    })
+    .collect()
+}
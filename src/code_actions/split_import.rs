@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::ActionContext;
+use crate::utils::{line_indent, offset_to_position};
+
+/// On `use foo::{a, b::{c, d}};`, writes out one `use` per imported name. This works on the
+/// text rather than the AST, since the parser gives up on grouped imports.
+pub fn split_import(ctx: &ActionContext) -> Option<CodeAction> {
+    let line = ctx.params.range.start.line;
+    let line_start = ctx.document.try_line_to_char(line as usize).ok()?;
+    let indent = line_indent(ctx.document, line as usize);
+    let statement_start = line_start + indent.chars().count();
+    let rest = ctx.document.get_slice(statement_start..)?.to_string();
+    let tree = rest.strip_prefix("use ")?;
+    let tree = &tree[..tree.find(';')?];
+    if !tree.contains('{') {
+        return None;
+    }
+
+    let paths = expand(tree.trim())?;
+    let new_text = paths
+        .iter()
+        .map(|path| format!("use {};", path))
+        .collect::<Vec<_>>()
+        .join(&format!("\n{}", indent));
+    // `use ` plus the tree plus `;`, counted in chars to match the rope's offsets.
+    let end = statement_start + "use ".len() + tree.chars().count() + 1;
+
+    Some(CodeAction {
+        title: "Split into separate `use` statements".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit {
+                    range: Range::new(
+                        offset_to_position(statement_start, ctx.document, ctx.encoding)?,
+                        offset_to_position(end, ctx.document, ctx.encoding)?,
+                    ),
+                    new_text,
+                }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+/// The paths a use tree imports, e.g. `a::{b, c::{d, self}}` is `a::b`, `a::c::d` and `a::c`.
+fn expand(tree: &str) -> Option<Vec<String>> {
+    let open = match tree.find('{') {
+        Some(open) => open,
+        None => return Some(vec![tree.split_whitespace().collect::<Vec<_>>().join(" ")]),
+    };
+    let prefix = tree[..open].trim();
+    let inner = tree[open + 1..].trim_end().strip_suffix('}')?;
+
+    // Split on the commas that aren't inside a nested group.
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut item_start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[item_start..i]);
+                item_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[item_start..]);
+
+    let mut paths = Vec::new();
+    for item in items
+        .iter()
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+    {
+        for path in expand(item)? {
+            paths.push(match path.as_str() {
+                "self" => prefix.trim_end_matches("::").to_string(),
+                _ => format!("{}{}", prefix, path),
+            });
+        }
+    }
+    Some(paths)
+}
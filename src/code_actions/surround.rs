@@ -0,0 +1,104 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{ExpressionKind, Pattern, Statement};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, TextEdit, WorkspaceEdit};
+
+use super::extract_function::extract;
+use super::{selected_statements, selection_offsets, ActionContext};
+use crate::utils::{
+    enclosing_function, fresh_name, line_indent, names_in_function, span_offset, span_range,
+    span_text, walk_statements,
+};
+
+/// Wraps the selected statements in `if true { .. }` or `for i in 0..1 { .. }` for the user
+/// to fill in the condition or range, and offers moving them into an `unconstrained` helper.
+///
+/// Wrapping is not offered when the statements declare variables read after the selection,
+/// since the new block would take them out of scope.
+pub fn surround(ctx: &ActionContext) -> Vec<CodeAction> {
+    let mut actions = Vec::new();
+    actions.extend(surround_with(ctx, "if", |_| "if true".to_string()));
+    actions.extend(surround_with(ctx, "for", |taken| {
+        format!("for {} in 0..1", fresh_name("i", taken))
+    }));
+    actions.extend(extract(ctx, true));
+    actions
+}
+
+fn surround_with(
+    ctx: &ActionContext,
+    keyword: &str,
+    header: impl Fn(&HashSet<String>) -> String,
+) -> Option<CodeAction> {
+    let (start, mut end) = selection_offsets(ctx)?;
+    let func = enclosing_function(ctx.module, ctx.document, start)?;
+    let selected = selected_statements(&func.def.body.0, start, end)?;
+    // Take the `;` of the last statement along when the selection stops just short of it.
+    if span_offset(end, ctx.document).and_then(|end| ctx.document.get_char(end)) == Some(';') {
+        end += 1;
+    }
+
+    let mut declared = HashSet::new();
+    for statement in selected {
+        if let Statement::Let(val) = statement {
+            let pattern = match &val.pattern {
+                Pattern::Mutable(pattern, _) => pattern.as_ref(),
+                pattern => pattern,
+            };
+            match pattern {
+                Pattern::Identifier(ident) => declared.insert(ident.0.contents.as_str()),
+                // Destructuring may bind anything; don't guess which names escape.
+                _ => return None,
+            };
+        }
+    }
+    let mut read_after = false;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Path(path) = &expr.kind {
+            read_after |= expr.span.start() > end
+                && path.segments.len() == 1
+                && declared.contains(path.segments[0].0.contents.as_str());
+        }
+    });
+    if read_after {
+        return None;
+    }
+
+    let first_line = ctx.document.char_to_line(span_offset(start, ctx.document)?);
+    let indent = line_indent(ctx.document, first_line);
+    let text = span_text(start, end, ctx.document)?;
+    let body = text
+        .lines()
+        .map(|line| {
+            let line = line.strip_prefix(indent.as_str()).unwrap_or(line);
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}    {}", indent, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let new_text = format!(
+        "{} {{\n{}\n{}}}",
+        header(&names_in_function(func)),
+        body,
+        indent
+    );
+
+    Some(CodeAction {
+        title: format!("Surround with `{}`", keyword),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                ctx.uri.clone(),
+                vec![TextEdit {
+                    range: span_range(start, end, ctx.document, ctx.encoding)?,
+                    new_text,
+                }],
+            )])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
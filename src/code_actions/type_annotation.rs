@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{LetStatement, Statement, UnresolvedType};
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, WorkspaceEdit};
+
+use super::ActionContext;
+use crate::inlay_hints::inferred_let_type;
+use crate::utils::{
+    enclosing_function, innermost_statement, pattern_end, span_position, span_range, span_text,
+};
+
+/// On a `let` whose type can be inferred, offers to write the type out, or to remove an
+/// annotation that only repeats the inferred type.
+pub fn type_annotation(ctx: &ActionContext) -> Option<CodeAction> {
+    let cursor = ctx.cursor()?;
+    let func = enclosing_function(ctx.module, ctx.document, cursor)?;
+    let val = match innermost_statement(&func.def.body.0, cursor, cursor)? {
+        Statement::Let(val) => val,
+        _ => return None,
+    };
+    let inferred = inferred_let_type(ctx.module, func, val)?;
+
+    let (title, edit) = match &val.r#type {
+        UnresolvedType::Unspecified => {
+            let position = span_position(pattern_end(&val.pattern), ctx.document, ctx.encoding)?;
+            (
+                format!("Add type annotation `{}`", inferred),
+                TextEdit {
+                    range: Range::new(position, position),
+                    new_text: format!(": {}", inferred),
+                },
+            )
+        }
+        annotated if annotated.to_string() == inferred => (
+            "Remove redundant type annotation".to_string(),
+            annotation_removal(ctx, val)?,
+        ),
+        _ => return None,
+    };
+
+    Some(CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(ctx.uri.clone(), vec![edit])])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    })
+}
+
+/// Removes `: Type` from the `let`, leaving the whitespace in front of the `=` as it was.
+pub(super) fn annotation_removal(ctx: &ActionContext, val: &LetStatement) -> Option<TextEdit> {
+    let name_end = pattern_end(&val.pattern);
+    let between = span_text(name_end, val.expression.span.start(), ctx.document)?;
+    let annotation = between[..between.find('=')?].trim_end();
+    let end = name_end + annotation.len() as u32;
+    Some(TextEdit {
+        range: span_range(name_end, end, ctx.document, ctx.encoding)?,
+        new_text: String::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run_action;
+    use super::*;
+
+    fn annotated(text: &str, selection: &str) -> Option<String> {
+        run_action(text, selection, type_annotation)
+    }
+
+    #[test]
+    fn integers_take_the_width_they_are_used_with() {
+        let text = "fn main(y: u8) {
+    let x = 1;
+    assert(x + y == 2);
+}";
+        let edited = annotated(text, "x = 1").unwrap();
+        assert!(edited.contains("let x: u8 = 1;"));
+    }
+
+    #[test]
+    fn annotations_go_after_mut_bindings() {
+        let text = "fn main(y: u32) {
+    let mut x = 1;
+    x = x + y;
+}";
+        let edited = annotated(text, "x = 1").unwrap();
+        assert!(edited.contains("let mut x: u32 = 1;"));
+    }
+
+    #[test]
+    fn unconstrained_integers_are_not_annotated() {
+        let text = "fn main() {
+    let x = 1;
+    let y = [1, 2];
+}";
+        assert_eq!(annotated(text, "x = 1"), None);
+        assert_eq!(annotated(text, "y = "), None);
+    }
+}
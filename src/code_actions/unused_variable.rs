@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{
+    AssignStatement, BinaryOpKind, Expression, ExpressionKind, LValue, LetStatement, Pattern,
+    Statement,
+};
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, Diagnostic, NumberOrString, TextEdit, WorkspaceEdit,
+};
+
+use super::{binding_lines, statement_lines, ActionContext};
+use crate::diagnostics::UNUSED_VARIABLE;
+use crate::utils::{
+    enclosing_function, innermost_statement, lvalue_root, position_to_byte, position_to_offset,
+    walk_expression, walk_statements,
+};
+
+/// Fixes for unused-variable warnings: prefixing the name with `_`, and removing the whole
+/// `let` along with the assignments to the variable when evaluating their values cannot fail
+/// or affect anything else.
+pub fn unused_variable(ctx: &ActionContext) -> Vec<CodeAction> {
+    ctx.params
+        .context
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            diagnostic.code == Some(NumberOrString::String(UNUSED_VARIABLE.to_string()))
+        })
+        .flat_map(|diagnostic| {
+            let name = ctx
+                .document
+                .get_slice(
+                    position_to_offset(diagnostic.range.start, ctx.document, ctx.encoding)
+                        .unwrap_or(0)
+                        ..position_to_offset(diagnostic.range.end, ctx.document, ctx.encoding)
+                            .unwrap_or(0),
+                )
+                .map(|name| name.to_string())
+                .unwrap_or_default();
+            let mut actions = vec![quick_fix(
+                ctx,
+                diagnostic,
+                format!("Rename to `_{}`", name),
+                vec![TextEdit {
+                    range: diagnostic.range,
+                    new_text: format!("_{}", name),
+                }],
+            )];
+            if let Some(edits) = removal(ctx, diagnostic, &name) {
+                actions.push(quick_fix(
+                    ctx,
+                    diagnostic,
+                    format!("Remove `{}`", name),
+                    edits,
+                ));
+            }
+            actions
+        })
+        .collect()
+}
+
+fn removal(ctx: &ActionContext, diagnostic: &Diagnostic, name: &str) -> Option<Vec<TextEdit>> {
+    let offset = position_to_byte(diagnostic.range.start, ctx.document, ctx.encoding)?;
+    let func = enclosing_function(ctx.module, ctx.document, offset)?;
+    let val = match innermost_statement(&func.def.body.0, offset, offset)? {
+        Statement::Let(val) if !matches!(val.pattern, Pattern::Tuple(..) | Pattern::Struct(..)) => {
+            val
+        }
+        _ => return None,
+    };
+    is_pure(&val.expression).then_some(())?;
+    let mut edits = vec![TextEdit {
+        range: binding_lines(ctx, val)?,
+        new_text: String::new(),
+    }];
+
+    // The statements after the `let` in its block, up to one shadowing it.
+    let mut block = &func.def.body.0;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Block(statements) = &expr.kind {
+            if statements.0.iter().any(|statement| is_let(statement, val)) {
+                block = &statements.0;
+            }
+        }
+    });
+    let after = block
+        .iter()
+        .skip_while(|statement| !is_let(statement, val))
+        .skip(1)
+        .take_while(
+            |statement| !matches!(statement, Statement::Let(other) if binds(&other.pattern, name)),
+        );
+    let mut assignments = Vec::new();
+    after.for_each(|statement| {
+        let statement = std::slice::from_ref(statement);
+        assignments_in(statement, &mut assignments);
+        walk_statements(statement, &mut |expr| {
+            if let ExpressionKind::Block(block) = &expr.kind {
+                assignments_in(&block.0, &mut assignments);
+            }
+        });
+    });
+    for assign in assignments {
+        let root = lvalue_root(&assign.lvalue);
+        if root.0.contents != name {
+            continue;
+        }
+        // Writing an element or field may fail on its index.
+        let whole = matches!(assign.lvalue, LValue::Ident(_));
+        (whole && is_pure(&assign.expression)).then_some(())?;
+        edits.push(TextEdit {
+            range: statement_lines(ctx, root.span().start(), assign.expression.span.end())?,
+            new_text: String::new(),
+        });
+    }
+    Some(edits)
+}
+
+fn assignments_in<'a>(statements: &'a [Statement], assignments: &mut Vec<&'a AssignStatement>) {
+    assignments.extend(statements.iter().filter_map(|statement| match statement {
+        Statement::Assign(assign) => Some(assign),
+        _ => None,
+    }));
+}
+
+fn is_let(statement: &Statement, val: &LetStatement) -> bool {
+    matches!(statement, Statement::Let(other) if std::ptr::eq(other, val))
+}
+
+fn binds(pattern: &Pattern, name: &str) -> bool {
+    match pattern {
+        Pattern::Identifier(ident) => ident.0.contents == name,
+        Pattern::Mutable(pattern, _) => binds(pattern, name),
+        Pattern::Tuple(patterns, _) => patterns.iter().any(|p| binds(p, name)),
+        Pattern::Struct(_, fields, _) => fields.iter().any(|(_, p)| binds(p, name)),
+    }
+}
+
+/// Whether dropping the expression leaves the circuit's constraints unchanged. Calls may
+/// constrain, and indexing, division and casts may fail, so only what's left is pure.
+fn is_pure(expr: &Expression) -> bool {
+    let mut pure = true;
+    walk_expression(expr, &mut |e| {
+        pure &= match &e.kind {
+            ExpressionKind::Call(_)
+            | ExpressionKind::MethodCall(_)
+            | ExpressionKind::Index(_)
+            | ExpressionKind::Cast(_)
+            | ExpressionKind::Block(_)
+            | ExpressionKind::For(_)
+            | ExpressionKind::If(_) => false,
+            ExpressionKind::Infix(infix) => !matches!(
+                infix.operator.contents,
+                BinaryOpKind::Divide | BinaryOpKind::Modulo
+            ),
+            _ => true,
+        }
+    });
+    pure
+}
+
+fn quick_fix(
+    ctx: &ActionContext,
+    diagnostic: &Diagnostic,
+    title: String,
+    edits: Vec<TextEdit>,
+) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(ctx.uri.clone(), edits)])),
+            ..WorkspaceEdit::default()
+        }),
+        ..CodeAction::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run_action;
+    use super::*;
+
+    fn removed(text: &str, name: &str) -> Option<String> {
+        let title = format!("Remove `{}`", name);
+        run_action(text, name, |ctx| {
+            unused_variable(ctx)
+                .into_iter()
+                .find(|action| action.title == title)
+        })
+    }
+
+    #[test]
+    fn removing_a_variable_removes_its_assignments() {
+        let text = "fn main(a: Field) {
+    let mut b = a;
+    b = b + 1;
+    for i in 0..2 {
+        b = 2;
+    }
+    assert(a != 0);
+}";
+        assert_eq!(
+            removed(text, "b").unwrap(),
+            "fn main(a: Field) {
+    for i in 0..2 {
+    }
+    assert(a != 0);
+}"
+        );
+    }
+
+    #[test]
+    fn variables_assigned_values_that_may_fail_are_only_renamed() {
+        let text = "fn main(a: Field) {
+    let mut b = a;
+    b = a / 2;
+    assert(a != 0);
+}";
+        assert_eq!(removed(text, "b"), None);
+    }
+}
@@ -0,0 +1,243 @@
+use noirc_frontend::ParsedModule;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower_lsp::lsp_types::{CodeLens, Command, Location, Position, Range, Url};
+
+use crate::contracts::{Contract, SHOW_ABI};
+use crate::prover_toml::{EXECUTE, GENERATE_PROVER_TOML, PROVE};
+use crate::test_explorer::TestStatus;
+use crate::utils::{offset_to_position, span_position, test_functions, PositionEncoding};
+
+/// Runs `nargo test --exact` for each test named after the document URI in its arguments.
+pub const RUN_TESTS: &str = "noir.runTests";
+/// Runs `nargo check` on the package of the document URI in its arguments, publishing what
+/// it reports.
+pub const COMPILE: &str = "noir.compile";
+
+/// What a lens does, kept in its `data` until `codeLens/resolve` gives it a title and a
+/// command: lenses are listed as soon as the tree is there, and what their titles count or
+/// recall is only looked up for those the editor shows.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Lens {
+    RunTest { name: String },
+    RunAllTests { names: Vec<String> },
+    Compile,
+    Execute,
+    Prove,
+    GenerateProverToml { has_prover_toml: bool },
+    ShowAbi { contract: String, function: String },
+    References { name: String },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LensData {
+    pub uri: Url,
+    #[serde(flatten)]
+    pub lens: Lens,
+}
+
+/// A "Run test" lens over every `#[test]` function, and one over the first test that runs
+/// all of the file's tests.
+pub fn test_lenses(
+    module: &ParsedModule,
+    document: &Rope,
+    uri: &Url,
+    encoding: PositionEncoding,
+) -> Vec<CodeLens> {
+    let tests = test_functions(module, document)
+        .into_iter()
+        .filter_map(|func| {
+            let name = func.def.name.0.contents.clone();
+            let position = span_position(func.def.name.span().start(), document, encoding)?;
+            Some((name, position))
+        })
+        .collect::<Vec<_>>();
+
+    let mut lenses = tests
+        .iter()
+        .map(|(name, position)| unresolved(*position, uri, Lens::RunTest { name: name.clone() }))
+        .collect::<Vec<_>>();
+    if let Some((_, position)) = tests.first() {
+        let names = tests.iter().map(|(name, _)| name.clone()).collect();
+        lenses.push(unresolved(*position, uri, Lens::RunAllTests { names }));
+    }
+    lenses
+}
+
+/// Lenses over `fn main`: one that compiles the package, one that writes a `Prover.toml`
+/// with a placeholder for each of its parameters, or adds the ones missing from the
+/// `Prover.toml` there is, and, once there is one, one that executes and one that proves
+/// `main` on its inputs.
+pub fn main_lenses(
+    module: &ParsedModule,
+    document: &Rope,
+    uri: &Url,
+    has_prover_toml: bool,
+    encoding: PositionEncoding,
+) -> Vec<CodeLens> {
+    let main = match module
+        .functions
+        .iter()
+        .find(|func| func.def.name.0.contents == "main")
+    {
+        Some(main) => main,
+        None => return Vec::new(),
+    };
+    let position = match span_position(main.def.name.span().start(), document, encoding) {
+        Some(position) => position,
+        None => return Vec::new(),
+    };
+    let mut lenses = vec![Lens::Compile];
+    if has_prover_toml {
+        lenses.extend([Lens::Execute, Lens::Prove]);
+    }
+    lenses.push(Lens::GenerateProverToml { has_prover_toml });
+    lenses
+        .into_iter()
+        .map(|lens| unresolved(position, uri, lens))
+        .collect()
+}
+
+/// A "Show ABI" lens over every function of the document's contracts.
+pub fn abi_lenses(
+    contracts: &[Contract],
+    document: &Rope,
+    uri: &Url,
+    encoding: PositionEncoding,
+) -> Vec<CodeLens> {
+    contracts
+        .iter()
+        .flat_map(|contract| {
+            contract.functions.iter().filter_map(|func| {
+                let position = offset_to_position(func.name_start, document, encoding)?;
+                let lens = Lens::ShowAbi {
+                    contract: contract.name.clone(),
+                    function: func.name.clone(),
+                };
+                Some(unresolved(position, uri, lens))
+            })
+        })
+        .collect()
+}
+
+/// A lens counting the references to each top-level function and struct of the document,
+/// other than `main` and tests, which nothing calls.
+pub fn reference_lenses(
+    module: &ParsedModule,
+    document: &Rope,
+    uri: &Url,
+    encoding: PositionEncoding,
+) -> Vec<CodeLens> {
+    let tests = test_functions(module, document)
+        .into_iter()
+        .map(|func| &func.def.name.0.contents)
+        .collect::<Vec<_>>();
+    let functions = module
+        .functions
+        .iter()
+        .map(|func| &func.def.name)
+        .filter(|name| name.0.contents != "main" && !tests.contains(&&name.0.contents));
+    let structs = module.types.iter().map(|typ| &typ.name);
+    functions
+        .chain(structs)
+        .filter_map(|name| {
+            let position = span_position(name.span().start(), document, encoding)?;
+            let lens = Lens::References {
+                name: name.0.contents.clone(),
+            };
+            Some(unresolved(position, uri, lens))
+        })
+        .collect()
+}
+
+/// The title and command of `data`'s lens, at `position`. Test lenses recall the test's
+/// last outcome, from `status`, and reference lenses count the `references` they list.
+pub fn command(
+    data: &LensData,
+    position: Position,
+    status: impl Fn(&str) -> Option<TestStatus>,
+    references: impl Fn(&str) -> Vec<Location>,
+) -> Command {
+    let uri = Value::from(data.uri.to_string());
+    let lens_command = |title: String, command: &str, arguments: Vec<Value>| Command {
+        title,
+        command: command.to_string(),
+        arguments: Some(arguments),
+    };
+    match &data.lens {
+        Lens::RunTest { name } => {
+            let title = match status(name) {
+                Some(TestStatus::Passed) => "✓ Run test",
+                Some(TestStatus::Failed) => "✗ Run test",
+                Some(TestStatus::Errored) | None => "▶ Run test",
+            };
+            lens_command(
+                title.to_string(),
+                RUN_TESTS,
+                vec![uri, Value::from(name.as_str())],
+            )
+        }
+        Lens::RunAllTests { names } => {
+            let failed = names
+                .iter()
+                .filter(|name| matches!(status(name), Some(TestStatus::Failed)))
+                .count();
+            let title = match failed {
+                0 => "Run all tests in file".to_string(),
+                failed => format!("Run all tests in file ({} failed)", failed),
+            };
+            let arguments = std::iter::once(uri)
+                .chain(names.iter().map(|name| Value::from(name.as_str())))
+                .collect();
+            lens_command(title, RUN_TESTS, arguments)
+        }
+        Lens::Compile => lens_command("Compile".to_string(), COMPILE, vec![uri]),
+        Lens::Execute => lens_command("▶ Execute".to_string(), EXECUTE, vec![uri]),
+        Lens::Prove => lens_command("Prove".to_string(), PROVE, vec![uri]),
+        Lens::GenerateProverToml { has_prover_toml } => {
+            let title = if *has_prover_toml {
+                "Add missing inputs to Prover.toml"
+            } else {
+                "Generate Prover.toml"
+            };
+            lens_command(title.to_string(), GENERATE_PROVER_TOML, vec![uri])
+        }
+        Lens::ShowAbi { contract, function } => lens_command(
+            "Show ABI".to_string(),
+            SHOW_ABI,
+            vec![
+                uri,
+                Value::from(contract.as_str()),
+                Value::from(function.as_str()),
+            ],
+        ),
+        Lens::References { name } => {
+            let locations = references(name);
+            let title = match locations.len() {
+                1 => "1 reference".to_string(),
+                count => format!("{} references", count),
+            };
+            // The editor's own command, which peeks at the locations it is given.
+            let arguments = vec![
+                uri,
+                serde_json::to_value(position).unwrap_or_default(),
+                serde_json::to_value(locations).unwrap_or_default(),
+            ];
+            lens_command(title, "editor.action.showReferences", arguments)
+        }
+    }
+}
+
+fn unresolved(position: Position, uri: &Url, lens: Lens) -> CodeLens {
+    let data = LensData {
+        uri: uri.clone(),
+        lens,
+    };
+    CodeLens {
+        range: Range::new(position, position),
+        command: None,
+        data: serde_json::to_value(data).ok(),
+    }
+}
@@ -0,0 +1,201 @@
+use std::borrow::Cow;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
+use tower_lsp::lsp_types::{MessageType, Url};
+
+use crate::test_explorer::{self, TestStatus};
+use crate::{
+    circuit_size, code_lens, contracts, nargo, prover_toml, workspace, Backend,
+    GateReportNotification, ProfileNotification, TestResult, TestResultNotification,
+};
+
+/// Every command `workspace/executeCommand` runs, as declared in the server's capabilities.
+pub const COMMANDS: &[&str] = &[
+    prover_toml::GENERATE_PROVER_TOML,
+    prover_toml::ADD_MAIN_PARAMETERS,
+    prover_toml::EXECUTE,
+    prover_toml::PROVE,
+    code_lens::RUN_TESTS,
+    code_lens::COMPILE,
+    circuit_size::GATE_REPORT,
+    circuit_size::PROFILE,
+    contracts::SHOW_ABI,
+    workspace::RELOAD_WORKSPACE,
+];
+
+impl Backend {
+    /// Runs `command` with the `arguments` the client sent, returning what the command
+    /// produced for the client, if anything. Unknown commands and arguments that don't fit
+    /// the command are answered with an invalid params error, and commands that couldn't do
+    /// their work, say because nargo failed, with an internal one.
+    #[tracing::instrument(skip(self, arguments))]
+    pub(crate) async fn run_command(
+        &self,
+        command: &str,
+        arguments: &[Value],
+    ) -> Result<Option<Value>> {
+        match command {
+            prover_toml::GENERATE_PROVER_TOML | prover_toml::ADD_MAIN_PARAMETERS => {
+                self.prover_toml_command(command, argument(arguments, 0)?)
+                    .await;
+                Ok(None)
+            }
+            prover_toml::EXECUTE => {
+                let execution = self.execute(&argument(arguments, 0)?).await;
+                respond(execution, "could not execute the package")
+            }
+            prover_toml::PROVE => {
+                let proof = self.prove(&argument(arguments, 0)?).await;
+                respond(proof, "could not prove the package")
+            }
+            code_lens::RUN_TESTS => {
+                let names = (1..arguments.len())
+                    .map(|i| argument(arguments, i))
+                    .collect::<Result<Vec<String>>>()?;
+                self.run_tests_command(argument(arguments, 0)?, &names)
+                    .await;
+                Ok(None)
+            }
+            code_lens::COMPILE => {
+                let uri = argument::<Url>(arguments, 0)?;
+                let project = uri
+                    .to_file_path()
+                    .ok()
+                    .and_then(|path| self.project_for(&path))
+                    .ok_or_else(|| Error::invalid_params(format!("{} is in no package", uri)))?;
+                self.check_project(&project).await;
+                Ok(None)
+            }
+            circuit_size::GATE_REPORT => {
+                let report = self.gate_report(&argument(arguments, 0)?).await;
+                if let Some(report) = &report {
+                    self.client
+                        .send_notification::<GateReportNotification>(report.clone())
+                        .await;
+                }
+                respond(report, "could not count the package's gates")
+            }
+            circuit_size::PROFILE => {
+                let profile = self.profile(&argument(arguments, 0)?).await;
+                if let Some(profile) = &profile {
+                    self.client
+                        .send_notification::<ProfileNotification>(profile.clone())
+                        .await;
+                }
+                respond(profile, "could not profile the package")
+            }
+            contracts::SHOW_ABI => {
+                let uri = argument(arguments, 0)?;
+                let contract = argument::<String>(arguments, 1)?;
+                let function = argument::<String>(arguments, 2)?;
+                let abi = self.show_abi(&uri, &contract, &function).await;
+                respond(abi, "could not compile the contract")
+            }
+            workspace::RELOAD_WORKSPACE => {
+                self.reload_workspace().await;
+                Ok(None)
+            }
+            _ => {
+                self.client
+                    .log_message(MessageType::WARNING, format!("unknown command {}", command))
+                    .await;
+                Err(Error::invalid_params(format!(
+                    "unknown command {}",
+                    command
+                )))
+            }
+        }
+    }
+
+    /// Asks the client to apply the `Prover.toml` edit `command` makes for the package of
+    /// the document at `uri`.
+    async fn prover_toml_command(&self, command: &str, uri: Url) {
+        let edit = match self.prover_toml_edit(command, &uri) {
+            Some(edit) => edit,
+            None => return,
+        };
+        match self.client.apply_edit(edit).await {
+            Ok(res) if res.applied => tracing::debug!("edit applied"),
+            Ok(_) => tracing::debug!("edit rejected"),
+            Err(err) => self.client.log_message(MessageType::ERROR, err).await,
+        }
+    }
+
+    /// Runs the tests named in `names` one by one, in the package of the document at `uri`,
+    /// reporting each result as it comes in.
+    async fn run_tests_command(&self, uri: Url, names: &[String]) {
+        let project = match uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return,
+        };
+
+        let nargo = self.project_nargo(&project).await;
+        let token = self.begin_progress(format!("nargo test {}", project)).await;
+        let (mut run, mut failed) = (0, 0);
+        for name in names {
+            let (passed, output) = match nargo::test(&nargo, &project.root, name).await {
+                Some(result) => result,
+                None => {
+                    self.client
+                        .log_message(MessageType::ERROR, "could not run `nargo test`")
+                        .await;
+                    break;
+                }
+            };
+            run += 1;
+            if !passed {
+                failed += 1;
+            }
+            let status = if passed {
+                TestStatus::Passed
+            } else {
+                TestStatus::Failed
+            };
+            self.test_statuses
+                .insert((uri.to_string(), name.to_string()), status);
+            self.client
+                .send_notification::<TestResultNotification>(TestResult {
+                    uri: uri.clone(),
+                    name: name.to_string(),
+                    passed,
+                    location: (!passed)
+                        .then(|| test_explorer::failure_location(&output, &project.root))
+                        .flatten(),
+                    output,
+                })
+                .await;
+        }
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        tracing::info!("{} tests run, {} failed", run, failed);
+    }
+}
+
+/// The `i`th of `arguments`, such as the document URI most commands take first.
+fn argument<T: DeserializeOwned>(arguments: &[Value], i: usize) -> Result<T> {
+    let value = arguments
+        .get(i)
+        .ok_or_else(|| Error::invalid_params(format!("missing argument {}", i)))?;
+    serde_json::from_value(value.clone())
+        .map_err(|err| Error::invalid_params(format!("argument {}: {}", i, err)))
+}
+
+/// What a command produced, serialized for the client, or `failure` if it produced nothing.
+fn respond<T: Serialize>(result: Option<T>, failure: &'static str) -> Result<Option<Value>> {
+    let result = result.ok_or(Error {
+        code: ErrorCode::InternalError,
+        message: Cow::Borrowed(failure),
+        data: None,
+    })?;
+    serde_json::to_value(result)
+        .map(Some)
+        .map_err(|_| Error::internal_error())
+}
@@ -0,0 +1,107 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Name of the settings section, asked for with `workspace/configuration` or sent with
+/// `did_change_configuration`.
+pub const SECTION: &str = "noir-language-server";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    pub inlay_hints: InlayHintsConfig,
+    /// Offer the lenses running tests and `main`, and showing contract ABIs.
+    pub code_lens: bool,
+    /// Report what the server finds by itself, such as unused variables. What nargo reports
+    /// is shown regardless.
+    pub diagnostics: bool,
+    /// Run `nargo check` on the package whenever one of its files is saved.
+    pub compile_on_save: bool,
+    /// The nargo binary to run, instead of looking for one.
+    pub nargo_path: Option<String>,
+    /// The nargo version to look for, e.g. `0.7.1`. Takes precedence over the version in a
+    /// package's `noir-toolchain.toml`.
+    pub toolchain: Option<String>,
+    /// Threads parsing packages and the standard library, one per core by default. Only read
+    /// from the initialization options, since the pool is started once.
+    pub num_threads: Option<usize>,
+    /// Megabytes the syntax trees and texts of files that aren't open may take before the
+    /// least recently used are dropped. Without it only their number is bounded.
+    pub cache_size_mb: Option<usize>,
+    /// Send the client `telemetry/event`s counting how often each request is made and how
+    /// long it takes, nothing about the code it was made on. Off unless the user opts in.
+    pub telemetry: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            inlay_hints: InlayHintsConfig::default(),
+            code_lens: true,
+            diagnostics: true,
+            compile_on_save: true,
+            nargo_path: None,
+            toolchain: None,
+            num_threads: None,
+            cache_size_mb: None,
+            telemetry: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InlayHintsConfig {
+    pub type_hints: bool,
+    pub parameter_hints: bool,
+    pub generic_hints: bool,
+    pub length_hints: bool,
+    /// Off by default, long chains get noisy.
+    pub chaining_hints: bool,
+    pub gate_count_hints: bool,
+    /// Labels longer than this many characters are cut short and end in `…`.
+    pub max_length: Option<usize>,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        InlayHintsConfig {
+            type_hints: true,
+            parameter_hints: true,
+            generic_hints: true,
+            length_hints: true,
+            chaining_hints: false,
+            gate_count_hints: true,
+            max_length: Some(25),
+        }
+    }
+}
+
+impl Config {
+    /// Clients differ in whether they send our section or the whole settings object,
+    /// so accept both. Missing or malformed fields fall back to their defaults.
+    pub fn from_settings(settings: &Value) -> Config {
+        let settings = settings.get(SECTION).unwrap_or(settings);
+        serde_json::from_value(settings.clone()).unwrap_or_default()
+    }
+}
+
+impl Config {
+    /// `cache_size_mb` in bytes.
+    pub fn cache_budget(&self) -> Option<usize> {
+        self.cache_size_mb
+            .map(|megabytes| megabytes.saturating_mul(1024 * 1024))
+    }
+}
+
+impl InlayHintsConfig {
+    pub fn truncate(&self, label: &str) -> String {
+        match self.max_length {
+            Some(max) if label.chars().count() > max => {
+                let mut truncated = label.chars().take(max).collect::<String>();
+                truncated.push('…');
+                truncated
+            }
+            _ => label.to_string(),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use ropey::Rope;
+
+use crate::utils::block_end;
+
+/// Compiles the package and returns the ABI of one function of one of its contracts.
+/// Arguments are the document URI, the contract's name and the function's name.
+pub const SHOW_ABI: &str = "noir.showAbi";
+
+/// A `contract Name { .. }` block of a contract package.
+#[derive(Debug, Clone)]
+pub struct Contract {
+    pub name: String,
+    /// Offset of the contract's name.
+    pub name_start: usize,
+    /// Offset just past the brace closing the contract.
+    pub end: usize,
+    pub functions: Vec<ContractFunction>,
+}
+
+/// A function declared directly in a contract, one of its entry points.
+#[derive(Debug, Clone)]
+pub struct ContractFunction {
+    pub name: String,
+    pub name_start: usize,
+}
+
+/// What a contract function may be declared with before `fn`.
+const FUNCTION_MODIFIERS: &[&str] = &["pub", "open", "unconstrained", "internal"];
+
+/// Every contract declared in `document`. The frontend doesn't parse `contract` blocks, so
+/// they and their functions are found in the text, by the lines they are declared on.
+pub fn contracts(document: &Rope) -> Vec<Contract> {
+    let mut contracts = Vec::new();
+    let mut line = 0;
+    while line < document.len_lines() {
+        let text = document.line(line).to_string();
+        let (column, name) = match declared_name(&text, "contract", &[]) {
+            Some(declared) => declared,
+            None => {
+                line += 1;
+                continue;
+            }
+        };
+        let name_start = document.line_to_char(line) + column;
+        let end = match block_end(name_start, document) {
+            Some(end) => end,
+            None => break,
+        };
+        let last_line = document.char_to_line(end.min(document.len_chars()));
+        let functions = (line + 1..=last_line)
+            .filter_map(|line| {
+                let text = document.line(line).to_string();
+                let (column, name) = declared_name(&text, "fn", FUNCTION_MODIFIERS)?;
+                Some(ContractFunction {
+                    name,
+                    name_start: document.line_to_char(line) + column,
+                })
+            })
+            .collect();
+        contracts.push(Contract {
+            name,
+            name_start,
+            end,
+            functions,
+        });
+        line = last_line + 1;
+    }
+    contracts
+}
+
+/// The column and name of what `line` declares with `keyword`, when it starts with the
+/// keyword, possibly after some of `modifiers`.
+fn declared_name(line: &str, keyword: &str, modifiers: &[&str]) -> Option<(usize, String)> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut rest = line.trim_start();
+    loop {
+        let word = rest.split(|c: char| !is_name_char(c)).next()?;
+        if word == keyword {
+            break;
+        }
+        if !modifiers.contains(&word) {
+            return None;
+        }
+        rest = rest[word.len()..].trim_start();
+    }
+    let after = &rest[keyword.len()..];
+    if !after.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let after = after.trim_start();
+    let name = after
+        .chars()
+        .take_while(|c| is_name_char(*c))
+        .collect::<String>();
+    if name.is_empty() {
+        return None;
+    }
+    let column = line[..line.len() - after.len()].chars().count();
+    Some((column, name))
+}
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet};
+
+use noirc_frontend::{
+    Expression, ExpressionKind, Ident, LValue, NoirFunction, ParsedModule, Pattern, Statement,
+};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag, NumberOrString, Range};
+
+use crate::utils::{all_functions, span_range, walk_expression, walk_statements, PositionEncoding};
+
+/// Source reported on diagnostics the server computes itself.
+pub const SOURCE: &str = "noir-lsp";
+
+pub const UNUSED_VARIABLE: &str = "unused_variable";
+pub const MISSING_MAIN: &str = "missing_main";
+pub const UNUSED_FUNCTION: &str = "unused_function";
+
+/// Warnings for `let` bindings that are never read before going out of scope or being
+/// shadowed. Names starting with `_` are exempt, as in Rust.
+pub fn unused_variables(
+    module: &ParsedModule,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<Diagnostic> {
+    all_functions(module)
+        .flat_map(|func| unused_variables_in(func, document, encoding))
+        .collect()
+}
+
+/// `unused_variables` of a single function, for files large enough to be diagnosed a few
+/// functions at a time.
+pub fn unused_variables_in(
+    func: &NoirFunction,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<Diagnostic> {
+    let mut unused = Vec::new();
+    unused_in_block(&func.def.body.0, &mut unused);
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if let ExpressionKind::Block(block) = &expr.kind {
+            unused_in_block(&block.0, &mut unused);
+        }
+    });
+
+    unused
+        .into_iter()
+        .filter_map(|ident| {
+            Some(Diagnostic {
+                range: span_range(ident.span().start(), ident.span().end(), document, encoding)?,
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(UNUSED_VARIABLE.to_string())),
+                source: Some(SOURCE.to_string()),
+                message: format!("unused variable `{}`", ident),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Diagnostic::default()
+            })
+        })
+        .collect()
+}
+
+/// An error on the crate root of a binary package that has no `fn main` to start from.
+/// Libraries and contracts have no `main`, so this is only for binaries.
+pub fn missing_main(module: &ParsedModule) -> Option<Diagnostic> {
+    if module
+        .functions
+        .iter()
+        .any(|func| func.def.name.0.contents == "main")
+    {
+        return None;
+    }
+    Some(Diagnostic {
+        range: Range::default(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(MISSING_MAIN.to_string())),
+        source: Some(SOURCE.to_string()),
+        message: "binary packages need a `main` function".to_string(),
+        ..Diagnostic::default()
+    })
+}
+
+/// Warnings for the free functions of a binary crate's module that `main` and the tests
+/// never reach, `reached` being what `reachable_functions` found over the whole crate.
+pub fn unreachable_functions(
+    module: &ParsedModule,
+    document: &Rope,
+    reached: &HashSet<String>,
+    encoding: PositionEncoding,
+) -> Vec<Diagnostic> {
+    module
+        .functions
+        .iter()
+        .filter(|func| !reached.contains(&func.def.name.0.contents))
+        .filter_map(|func| {
+            let name = &func.def.name;
+            Some(Diagnostic {
+                range: span_range(name.span().start(), name.span().end(), document, encoding)?,
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(UNUSED_FUNCTION.to_string())),
+                source: Some(SOURCE.to_string()),
+                message: format!("function `{}` is never used", name),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Diagnostic::default()
+            })
+        })
+        .collect()
+}
+
+/// The names of the functions a binary reaches, from `roots`, its `main` and tests, and the
+/// initializers of its globals, following `references`, what `function_references` found in
+/// each file of the crate. Names are matched by their last segment, so calling a function
+/// reaches every function of the crate with its name.
+pub fn reachable_functions(
+    references: &[&HashMap<String, HashSet<String>>],
+    roots: impl IntoIterator<Item = String>,
+) -> HashSet<String> {
+    let mut reached = HashSet::new();
+    let mut pending = roots.into_iter().chain([String::new()]).collect::<Vec<_>>();
+    while let Some(name) = pending.pop() {
+        if !reached.insert(name.clone()) {
+            continue;
+        }
+        for file in references {
+            if let Some(names) = file.get(&name) {
+                pending.extend(names.iter().filter(|n| !reached.contains(*n)).cloned());
+            }
+        }
+    }
+    reached
+}
+
+/// What each function of `module`, methods included, calls or refers to by path, by their
+/// last segment, keyed by the function's name. What the globals' initializers refer to is
+/// kept under the empty name.
+pub fn function_references(module: &ParsedModule) -> HashMap<String, HashSet<String>> {
+    let mut references: HashMap<String, HashSet<String>> = HashMap::new();
+    for func in all_functions(module) {
+        let names = references
+            .entry(func.def.name.0.contents.clone())
+            .or_default();
+        walk_statements(&func.def.body.0, &mut |expr| referenced_by(expr, names));
+    }
+    let globals = references.entry(String::new()).or_default();
+    for global in &module.globals {
+        walk_expression(&global.expression, &mut |expr| referenced_by(expr, globals));
+    }
+    references
+}
+
+fn referenced_by(expr: &Expression, names: &mut HashSet<String>) {
+    match &expr.kind {
+        ExpressionKind::Path(path) => {
+            names.extend(path.segments.last().map(|s| s.0.contents.clone()))
+        }
+        ExpressionKind::Call(call) => {
+            names.extend(call.func_name.segments.last().map(|s| s.0.contents.clone()))
+        }
+        ExpressionKind::MethodCall(call) => {
+            names.insert(call.method_name.0.contents.clone());
+        }
+        _ => {}
+    }
+}
+
+fn unused_in_block<'a>(statements: &'a [Statement], unused: &mut Vec<&'a Ident>) {
+    statements.iter().enumerate().for_each(|(i, statement)| {
+        let ident = match statement {
+            Statement::Let(val) => match &val.pattern {
+                Pattern::Identifier(ident) => ident,
+                Pattern::Mutable(pattern, _) => match pattern.as_ref() {
+                    Pattern::Identifier(ident) => ident,
+                    _ => return,
+                },
+                _ => return,
+            },
+            _ => return,
+        };
+        if !ident.0.contents.starts_with('_') && !is_read(&ident.0.contents, &statements[i + 1..]) {
+            unused.push(ident);
+        }
+    })
+}
+
+/// Whether `name` is read in `statements` before a `let` shadows it. Assigning to it
+/// doesn't count, and neither does reading it in a value assigned back to it, as in
+/// `x = x + 1`, which only feeds itself.
+fn is_read(name: &str, statements: &[Statement]) -> bool {
+    let mut reads = Vec::new();
+    let mut reassigned = assignments_to(name, statements).collect::<Vec<_>>();
+    let mut visit = |expr: &Expression| match &expr.kind {
+        ExpressionKind::Path(path)
+            if path.segments.len() == 1 && path.segments[0].0.contents == name =>
+        {
+            reads.push((expr.span.start(), expr.span.end()))
+        }
+        ExpressionKind::Block(block) => reassigned.extend(assignments_to(name, &block.0)),
+        _ => {}
+    };
+    for statement in statements {
+        match statement {
+            Statement::Let(val) => {
+                walk_expression(&val.expression, &mut visit);
+                let pattern = match &val.pattern {
+                    Pattern::Mutable(pattern, _) => pattern.as_ref(),
+                    pattern => pattern,
+                };
+                if matches!(pattern, Pattern::Identifier(ident) if ident.0.contents == name) {
+                    break;
+                }
+            }
+            _ => walk_statements(std::slice::from_ref(statement), &mut visit),
+        }
+    }
+    reads.iter().any(|(start, end)| {
+        !reassigned
+            .iter()
+            .any(|(value_start, value_end)| value_start <= start && end <= value_end)
+    })
+}
+
+/// The spans of the values `statements` assign to `name` itself, not to one of its
+/// elements or fields.
+fn assignments_to<'a>(
+    name: &'a str,
+    statements: &'a [Statement],
+) -> impl Iterator<Item = (u32, u32)> + 'a {
+    statements
+        .iter()
+        .filter_map(move |statement| match statement {
+            Statement::Assign(assign) => match &assign.lvalue {
+                LValue::Ident(ident) if ident.0.contents == name => {
+                    Some((assign.expression.span.start(), assign.expression.span.end()))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use noirc_frontend::parse_program;
+
+    use super::*;
+
+    fn reached(files: &[&str]) -> HashSet<String> {
+        let references = files
+            .iter()
+            .map(|text| function_references(&parse_program(text).0))
+            .collect::<Vec<_>>();
+        let references = references.iter().collect::<Vec<_>>();
+        reachable_functions(&references, ["main".to_string()])
+    }
+
+    #[test]
+    fn functions_are_reached_through_other_files() {
+        let reached = reached(&[
+            "mod helpers; fn main() { helpers::double(1); }",
+            "fn double(x: Field) -> Field { halve(x) * 4 } fn halve(x: Field) -> Field { x / 2 }",
+        ]);
+        assert!(reached.contains("double"));
+        assert!(reached.contains("halve"));
+    }
+
+    #[test]
+    fn functions_only_called_by_unreached_ones_are_unreached() {
+        let reached = reached(&["fn main() {} fn a() { b(); } fn b() {}"]);
+        assert!(!reached.contains("a"));
+        assert!(!reached.contains("b"));
+    }
+
+    #[test]
+    fn globals_reach_what_they_refer_to() {
+        let reached = reached(&["global N = size(); fn size() -> Field { 3 } fn main() {}"]);
+        assert!(reached.contains("size"));
+    }
+
+    fn unused(text: &str) -> Vec<String> {
+        let module = parse_program(text).0;
+        unused_variables(&module, &Rope::from_str(text), PositionEncoding::Utf16)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn variables_only_assigned_to_are_unused() {
+        let found = unused("fn main(a: Field) { let mut b = a; b = 1; b = b + a; }");
+        assert_eq!(found.len(), 1);
+    }
+
+    #[test]
+    fn variables_read_elsewhere_are_used() {
+        assert!(
+            unused("fn main(a: Field) { let mut b = a; b = b + 1; assert(b != a); }").is_empty()
+        );
+    }
+
+    #[test]
+    fn functions_diagnosed_one_at_a_time_find_the_same() {
+        let text = "fn main(a: Field) { let b = a; } fn f() { let c = 1; let _d = 2; }
+            impl S { fn m(x: Field) { let e = x; } }";
+        let module = parse_program(text).0;
+        let document = Rope::from_str(text);
+        let chunked = all_functions(&module)
+            .flat_map(|func| unused_variables_in(func, &document, PositionEncoding::Utf16))
+            .map(|diagnostic| diagnostic.message)
+            .collect::<Vec<_>>();
+        assert_eq!(chunked.len(), 3);
+        assert_eq!(chunked, unused(text));
+    }
+
+    #[test]
+    fn unreached_functions_are_warned_about_whether_pub_or_not() {
+        let text = "fn main() {} pub fn helper() {}";
+        let module = parse_program(text).0;
+        let reached = reachable_functions(&[&function_references(&module)], ["main".to_string()]);
+        let found = unreachable_functions(
+            &module,
+            &Rope::from_str(text),
+            &reached,
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message, "function `helper` is never used");
+    }
+}
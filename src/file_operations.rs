@@ -0,0 +1,168 @@
+use noirc_frontend::{ExpressionKind, Ident, ParsedModule, Path, PathKind};
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    FileOperationFilter, FileOperationPattern, FileOperationPatternKind,
+    FileOperationRegistrationOptions, Position, Range, TextEdit,
+};
+
+use crate::utils::{
+    all_functions, span_offset, span_range, walk_expression, walk_statements, PositionEncoding,
+};
+use crate::workspace;
+
+/// The files whose renames the server rewrites paths for.
+pub fn noir_files() -> FileOperationRegistrationOptions {
+    FileOperationRegistrationOptions {
+        filters: vec![filter("**/*.nr", FileOperationPatternKind::File)],
+    }
+}
+
+/// The files and folders whose creation, renaming and deletion change what the server knows
+/// of the workspace: sources, manifests and the folders holding them.
+pub fn workspace_files() -> FileOperationRegistrationOptions {
+    FileOperationRegistrationOptions {
+        filters: vec![
+            filter("**/*.nr", FileOperationPatternKind::File),
+            filter(
+                &format!("**/{}", workspace::MANIFEST),
+                FileOperationPatternKind::File,
+            ),
+            filter("**/*", FileOperationPatternKind::Folder),
+        ],
+    }
+}
+
+fn filter(glob: &str, kind: FileOperationPatternKind) -> FileOperationFilter {
+    FileOperationFilter {
+        scheme: Some("file".to_string()),
+        pattern: FileOperationPattern {
+            glob: glob.to_string(),
+            matches: Some(kind),
+            options: None,
+        },
+    }
+}
+
+/// Rewrites the paths in `module` that go through the module at `from` so they go through
+/// `to` instead, both given as segments after `crate`. Paths written from the crate root,
+/// `use crate::a::b` anywhere or `use a::b` in the crate root itself, are the only ones
+/// found: relative ones would need the module they're written in resolved.
+pub fn path_edits(
+    module: &ParsedModule,
+    document: &Rope,
+    from: &[&str],
+    to: &[&str],
+    crate_root: bool,
+    encoding: PositionEncoding,
+) -> Vec<TextEdit> {
+    let mut paths = module
+        .imports
+        .iter()
+        .map(|import| &import.path)
+        .collect::<Vec<_>>();
+    for func in all_functions(module) {
+        walk_statements(&func.def.body.0, &mut |expr| match &expr.kind {
+            ExpressionKind::Path(path) => paths.push(path),
+            ExpressionKind::Call(call) => paths.push(&call.func_name),
+            ExpressionKind::Constructor(constructor) => paths.push(&constructor.type_name),
+            _ => {}
+        });
+    }
+    for global in &module.globals {
+        walk_expression(&global.expression, &mut |expr| {
+            if let ExpressionKind::Path(path) = &expr.kind {
+                paths.push(path)
+            }
+        });
+    }
+    paths
+        .into_iter()
+        .filter(|path| match path.kind {
+            PathKind::Crate => true,
+            PathKind::Plain => crate_root,
+            PathKind::Dep => false,
+        })
+        .filter_map(|path| rewrite(path, document, from, to, encoding))
+        .collect()
+}
+
+/// Renames the module `name` in its `mod name;` declaration in `module`.
+pub fn rename_declaration(
+    module: &ParsedModule,
+    document: &Rope,
+    name: &str,
+    new_name: &str,
+    encoding: PositionEncoding,
+) -> Option<TextEdit> {
+    let decl = declaration(module, name)?;
+    Some(TextEdit {
+        range: span_range(decl.span().start(), decl.span().end(), document, encoding)?,
+        new_text: new_name.to_string(),
+    })
+}
+
+/// Removes the line declaring the module `name` from `module`, if the declaration has the
+/// line to itself.
+pub fn remove_declaration(module: &ParsedModule, document: &Rope, name: &str) -> Option<TextEdit> {
+    let decl = declaration(module, name)?;
+    let line = document.char_to_line(span_offset(decl.span().start(), document)?);
+    let text = document.line(line).to_string();
+    let statement = text.trim().trim_start_matches("pub ").trim_end_matches(';');
+    if statement.trim_start_matches("mod").trim() != name {
+        return None;
+    }
+    Some(TextEdit {
+        range: Range::new(
+            Position::new(line as u32, 0),
+            Position::new(line as u32 + 1, 0),
+        ),
+        new_text: String::new(),
+    })
+}
+
+/// Declares the module `name` in `module`, after its last module declaration or at the top
+/// of the file if it has none.
+pub fn add_declaration(module: &ParsedModule, document: &Rope, name: &str) -> TextEdit {
+    let line = module
+        .module_decls
+        .iter()
+        .filter_map(|decl| span_offset(decl.span().start(), document))
+        .map(|offset| document.char_to_line(offset) + 1)
+        .max()
+        .unwrap_or(0);
+    let position = Position::new(line as u32, 0);
+    TextEdit {
+        range: Range::new(position, position),
+        new_text: format!("mod {};\n", name),
+    }
+}
+
+fn declaration<'a>(module: &'a ParsedModule, name: &str) -> Option<&'a Ident> {
+    module
+        .module_decls
+        .iter()
+        .find(|decl| decl.0.contents == name)
+}
+
+/// The edit replacing the `from` segments `path` starts with by `to`.
+fn rewrite(
+    path: &Path,
+    document: &Rope,
+    from: &[&str],
+    to: &[&str],
+    encoding: PositionEncoding,
+) -> Option<TextEdit> {
+    let segments = path.segments.get(..from.len())?;
+    let through = segments
+        .iter()
+        .zip(from)
+        .all(|(segment, name)| segment.0.contents == *name);
+    if !through || to.is_empty() {
+        return None;
+    }
+    let (first, last) = (segments.first()?, segments.last()?);
+    Some(TextEdit {
+        range: span_range(first.span().start(), last.span().end(), document, encoding)?,
+        new_text: to.join("::"),
+    })
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use noirc_frontend::{
+    ExpressionKind, Ident, NoirFunction, ParsedModule, Pattern, Statement, UnresolvedType,
+};
+use tower_lsp::lsp_types::{SymbolKind, Url};
+
+use crate::interner::Interner;
+use crate::utils::{all_functions, module_path, walk_expression, walk_statements};
+
+/// The top-level items every analysed file declares and the names it refers to, kept up to
+/// date as trees are stored, for the features that look across files: references, renames,
+/// definitions, completions and workspace symbols.
+///
+/// Names are resolved by name alone, like the rest of the server: a reference to `foo` is a
+/// reference to every item named `foo` the caller considers, usually those of one package.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    files: DashMap<String, Arc<FileSymbols>>,
+    /// The files declaring or referring to each name, to look a name up without going
+    /// through every file.
+    by_name: DashMap<Arc<str>, HashSet<String>>,
+}
+
+/// What a file declares and refers to.
+#[derive(Debug, Default)]
+pub struct FileSymbols {
+    /// The path the file's items are imported by, such as `crate::merkle`.
+    pub module_path: Option<String>,
+    pub definitions: Vec<Symbol>,
+    pub references: Vec<Occurrence>,
+}
+
+/// An item declared at the top level of a file, or a method of one of its `impl`s.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: Arc<str>,
+    pub kind: SymbolKind,
+    /// The type a method is declared for.
+    pub container: Option<Arc<str>>,
+    /// Byte offsets of the name in the file.
+    pub span: (u32, u32),
+}
+
+/// Where a name is used: the last segment of a path, a called function or method, or a
+/// type written out.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub name: Arc<str>,
+    /// Byte offsets of the name in the file.
+    pub span: (u32, u32),
+}
+
+impl FileSymbols {
+    /// The declared or referred to name the byte at `offset` is in.
+    pub fn name_at(&self, offset: u32) -> Option<&Arc<str>> {
+        let covers = |span: (u32, u32)| span.0 <= offset && offset <= span.1;
+        self.definitions
+            .iter()
+            .find(|symbol| covers(symbol.span))
+            .map(|symbol| &symbol.name)
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|occurrence| covers(occurrence.span))
+                    .map(|occurrence| &occurrence.name)
+            })
+    }
+}
+
+impl SymbolIndex {
+    /// Replaces what the file at `uri` declares and refers to with what `module` does.
+    pub fn update(&self, uri: &str, module: &ParsedModule, interner: &Interner) {
+        let symbols = summarize(uri, module, interner);
+        self.unlink(uri);
+        for name in symbols
+            .definitions
+            .iter()
+            .map(|symbol| &symbol.name)
+            .chain(symbols.references.iter().map(|occurrence| &occurrence.name))
+        {
+            self.by_name
+                .entry(Arc::clone(name))
+                .or_default()
+                .insert(uri.to_string());
+        }
+        self.files.insert(uri.to_string(), Arc::new(symbols));
+    }
+
+    pub fn remove(&self, uri: &str) {
+        self.unlink(uri);
+        self.files.remove(uri);
+    }
+
+    pub fn clear(&self) {
+        self.files.clear();
+        self.by_name.clear();
+    }
+
+    pub fn file(&self, uri: &str) -> Option<Arc<FileSymbols>> {
+        self.files.get(uri).map(|file| Arc::clone(&file))
+    }
+
+    /// Every file with what it declares and refers to. The index isn't locked while they
+    /// are read.
+    pub fn files(&self) -> Vec<(String, Arc<FileSymbols>)> {
+        self.files
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())))
+            .collect()
+    }
+
+    /// The files declaring or referring to `name`, with what they do.
+    pub fn files_using(&self, name: &str) -> Vec<(String, Arc<FileSymbols>)> {
+        let uris = match self.by_name.get(name) {
+            Some(uris) => uris.iter().cloned().collect::<Vec<_>>(),
+            None => return Vec::new(),
+        };
+        uris.into_iter()
+            .filter_map(|uri| {
+                let file = self.file(&uri)?;
+                Some((uri, file))
+            })
+            .collect()
+    }
+
+    /// How many files are indexed, and how many items they declare.
+    pub fn counts(&self) -> (usize, usize) {
+        let symbols = self.files.iter().map(|entry| entry.definitions.len()).sum();
+        (self.files.len(), symbols)
+    }
+
+    /// Items whose name contains `query`, ignoring case, for workspace symbols.
+    pub fn search(&self, query: &str) -> Vec<(String, Symbol)> {
+        let query = query.to_lowercase();
+        self.files
+            .iter()
+            .flat_map(|entry| {
+                entry
+                    .definitions
+                    .iter()
+                    .filter(|symbol| symbol.name.to_lowercase().contains(&query))
+                    .map(|symbol| (entry.key().clone(), symbol.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Drops `uri` from the files of the names it used.
+    fn unlink(&self, uri: &str) {
+        let previous = match self.files.get(uri) {
+            Some(previous) => Arc::clone(&previous),
+            None => return,
+        };
+        for name in previous
+            .definitions
+            .iter()
+            .map(|symbol| &symbol.name)
+            .chain(
+                previous
+                    .references
+                    .iter()
+                    .map(|occurrence| &occurrence.name),
+            )
+        {
+            if let Some(mut uris) = self.by_name.get_mut(name) {
+                uris.remove(uri);
+            }
+            self.by_name.remove_if(name, |_, uris| uris.is_empty());
+        }
+    }
+}
+
+fn summarize(uri: &str, module: &ParsedModule, interner: &Interner) -> FileSymbols {
+    let symbol = |name: &Ident, kind: SymbolKind, container: Option<Arc<str>>| Symbol {
+        name: interner.intern(&name.0.contents),
+        kind,
+        container,
+        span: (name.span().start(), name.span().end()),
+    };
+
+    let mut definitions = Vec::new();
+    definitions.extend(
+        module
+            .functions
+            .iter()
+            .map(|func| symbol(&func.def.name, SymbolKind::FUNCTION, None)),
+    );
+    for imp in &module.impls {
+        let container = imp
+            .type_path
+            .segments
+            .last()
+            .map(|name| interner.intern(&name.0.contents));
+        definitions.extend(
+            imp.methods
+                .iter()
+                .map(|func| symbol(&func.def.name, SymbolKind::METHOD, container.clone())),
+        );
+    }
+    definitions.extend(
+        module
+            .types
+            .iter()
+            .map(|typ| symbol(&typ.name, SymbolKind::STRUCT, None)),
+    );
+    definitions.extend(
+        module
+            .globals
+            .iter()
+            .filter_map(|global| match &global.pattern {
+                Pattern::Identifier(name) => Some(symbol(name, SymbolKind::CONSTANT, None)),
+                _ => None,
+            }),
+    );
+
+    let mut names = Vec::new();
+    for func in all_functions(module) {
+        function_references(func, &mut names);
+    }
+    for imp in &module.impls {
+        names.extend(imp.type_path.segments.last());
+    }
+    for typ in &module.types {
+        typ.fields
+            .iter()
+            .for_each(|(_, typ)| type_references(typ, &mut names));
+    }
+    for global in &module.globals {
+        walk_expression(&global.expression, &mut |expr| {
+            expression_references(&expr.kind, &mut names)
+        });
+    }
+    let references = names
+        .into_iter()
+        .map(|name| Occurrence {
+            name: interner.intern(&name.0.contents),
+            span: (name.span().start(), name.span().end()),
+        })
+        .collect();
+
+    FileSymbols {
+        module_path: Url::parse(uri).ok().and_then(|uri| module_path(&uri)),
+        definitions,
+        references,
+    }
+}
+
+fn function_references<'a>(func: &'a NoirFunction, names: &mut Vec<&'a Ident>) {
+    func.def
+        .parameters
+        .iter()
+        .for_each(|(_, typ, _)| type_references(typ, names));
+    type_references(&func.def.return_type, names);
+    let_types(&func.def.body.0, names);
+    walk_statements(&func.def.body.0, &mut |expr| {
+        expression_references(&expr.kind, names)
+    });
+}
+
+fn expression_references<'a>(kind: &'a ExpressionKind, names: &mut Vec<&'a Ident>) {
+    match kind {
+        ExpressionKind::Path(path) => names.extend(path.segments.last()),
+        ExpressionKind::Call(call) => names.extend(call.func_name.segments.last()),
+        ExpressionKind::MethodCall(call) => names.push(&call.method_name),
+        ExpressionKind::Constructor(constructor) => {
+            names.extend(constructor.type_name.segments.last())
+        }
+        ExpressionKind::Block(block) => let_types(&block.0, names),
+        _ => {}
+    }
+}
+
+/// The types `let` statements directly in `statements` are annotated with.
+fn let_types<'a>(statements: &'a [Statement], names: &mut Vec<&'a Ident>) {
+    statements.iter().for_each(|statement| {
+        if let Statement::Let(val) = statement {
+            type_references(&val.r#type, names);
+        }
+    })
+}
+
+fn type_references<'a>(typ: &'a UnresolvedType, names: &mut Vec<&'a Ident>) {
+    match typ {
+        UnresolvedType::Named(path, generics) => {
+            names.extend(path.segments.last());
+            generics.iter().for_each(|typ| type_references(typ, names));
+        }
+        UnresolvedType::Array(_, element) => type_references(element, names),
+        UnresolvedType::Tuple(elements) => {
+            elements.iter().for_each(|typ| type_references(typ, names))
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Bumped whenever what is cached changes shape, so older entries are ignored.
+const FORMAT: u32 = 1;
+
+/// A hash of the sources of the package at `root`: the path and contents of every `.nr`
+/// file under its `src` directory. Indexes cached under it are still valid as long as none
+/// of them changed.
+pub fn source_hash(root: &Path) -> Option<u64> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.join("src")];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).ok()?.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "nr") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    FORMAT.hash(&mut hasher);
+    root.hash(&mut hasher);
+    for path in files {
+        path.hash(&mut hasher);
+        std::fs::read(&path).ok()?.hash(&mut hasher);
+    }
+    Some(hasher.finish())
+}
+
+/// What `index` computes from the sources of the package at `root`, read from the `kind`
+/// entry of the cache when an earlier run stored it for the same sources, and stored there
+/// otherwise.
+pub fn cached<T: Serialize + DeserializeOwned>(
+    kind: &str,
+    root: &Path,
+    index: impl FnOnce() -> T,
+) -> T {
+    let hash = match source_hash(root) {
+        Some(hash) => hash,
+        None => return index(),
+    };
+    if let Some(cached) = load(kind, hash) {
+        return cached;
+    }
+    let computed = index();
+    store(kind, hash, &computed);
+    computed
+}
+
+/// The `kind` of index cached for sources with `hash`, if an earlier run stored one.
+fn load<T: DeserializeOwned>(kind: &str, hash: u64) -> Option<T> {
+    let bytes = std::fs::read(entry(kind, hash)?).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Caches `index` as the `kind` of index of sources with `hash`, for the next run. The cache
+/// only saves time, so failing to write it is ignored.
+fn store<T: Serialize>(kind: &str, hash: u64, index: &T) {
+    let path = match entry(kind, hash) {
+        Some(path) => path,
+        None => return,
+    };
+    let bytes = match serde_json::to_vec(index) {
+        Ok(bytes) => bytes,
+        Err(_) => return,
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    // Written aside and renamed, so a server reading it at the same time never sees half.
+    let partial = path.with_extension("partial");
+    if std::fs::write(&partial, bytes).is_ok() {
+        let _ = std::fs::rename(&partial, &path);
+    }
+}
+
+fn entry(kind: &str, hash: u64) -> Option<PathBuf> {
+    Some(dir()?.join(format!("{}-{:016x}.json", kind, hash)))
+}
+
+/// `NOIR_LSP_CACHE_DIR` when it is set, and otherwise `noir-lsp` in the user's cache
+/// directory.
+fn dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("NOIR_LSP_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".cache")))?;
+    Some(cache.join("noir-lsp"))
+}
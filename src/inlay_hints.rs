@@ -0,0 +1,576 @@
+use std::collections::HashMap;
+
+use noirc_frontend::{
+    ArrayLiteral, BinaryOpKind, Expression, ExpressionKind, Ident, LetStatement, Literal,
+    NoirFunction, ParsedModule, Pattern, Statement, UnresolvedType, UnresolvedTypeExpression,
+};
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{InlayHint, Range};
+
+use crate::circuit_size::{function_keys, FunctionKey};
+use crate::utils::{all_functions, byte_offset, span_offset, walk_statements};
+
+/// The hints that fall inside the range the client asked for.
+pub fn in_range(hints: &[InlayHint], range: Range) -> Vec<InlayHint> {
+    hints
+        .iter()
+        .filter(|hint| range.start <= hint.position && hint.position <= range.end)
+        .cloned()
+        .collect()
+}
+
+/// Generic arguments left for the compiler to infer at call sites, as `(offset, label)`
+/// pairs where `offset` is the end of the callee's name, e.g. `compute_root` + `::<32>`.
+pub fn generic_argument_hints(module: &ParsedModule) -> Vec<(u32, String)> {
+    let mut hints = Vec::new();
+    for_each_local_call(module, |callee, name, arguments| {
+        if callee.def.generics.is_empty() {
+            return;
+        }
+        if let Some(label) = infer_generics(callee, arguments) {
+            hints.push((name.span().end(), label));
+        }
+    });
+    hints
+}
+
+/// Parameter names in front of each argument, as `(offset, label)` pairs where `offset` is
+/// the start of the argument. Arguments that are already a variable of the same name are
+/// skipped, as the hint would only repeat them.
+pub fn parameter_hints(module: &ParsedModule) -> Vec<(u32, String)> {
+    let mut hints = Vec::new();
+    for_each_local_call(module, |callee, _, arguments| {
+        callee
+            .def
+            .parameters
+            .iter()
+            .zip(arguments)
+            .for_each(|((pattern, _, _), arg)| {
+                let param_name = match pattern {
+                    Pattern::Identifier(ident) => &ident.0.contents,
+                    _ => return,
+                };
+                let repeats_name = matches!(&arg.kind, ExpressionKind::Path(path)
+                    if path.segments.len() == 1 && &path.segments[0].0.contents == param_name);
+                if !repeats_name {
+                    hints.push((arg.span.start(), format!("{}:", param_name)));
+                }
+            })
+    });
+    hints
+}
+
+/// Calls `f` with the definition, the called name and the arguments of every call to a
+/// function declared in this module.
+fn for_each_local_call<'a>(
+    module: &'a ParsedModule,
+    mut f: impl FnMut(&'a NoirFunction, &'a Ident, &'a [Expression]),
+) {
+    let functions: HashMap<&str, &NoirFunction> = all_functions(module)
+        .map(|func| (func.def.name.0.contents.as_str(), func))
+        .collect();
+
+    all_functions(module).for_each(|func| {
+        walk_statements(&func.def.body.0, &mut |expr| {
+            if let ExpressionKind::Call(call) = &expr.kind {
+                if let Some(name) = call.func_name.segments.last() {
+                    if let Some(callee) = functions.get(name.0.contents.as_str()) {
+                        f(callee, name, &call.arguments);
+                    }
+                }
+            }
+        })
+    });
+}
+
+/// Renders the callee's generics as `::<..>`, using `_` for any the arguments don't pin down.
+/// Returns `None` when nothing could be inferred, since `::<_, _>` tells the user nothing.
+fn infer_generics(callee: &NoirFunction, arguments: &[Expression]) -> Option<String> {
+    let mut resolved = HashMap::new();
+    callee
+        .def
+        .parameters
+        .iter()
+        .zip(arguments)
+        .for_each(|((_, typ, _), arg)| unify(typ, arg, &mut resolved));
+    if resolved.is_empty() {
+        return None;
+    }
+
+    let generics = callee
+        .def
+        .generics
+        .iter()
+        .map(|generic| {
+            resolved
+                .get(generic.0.contents.as_str())
+                .cloned()
+                .unwrap_or_else(|| "_".to_string())
+        })
+        .collect::<Vec<_>>();
+    Some(format!("::<{}>", generics.join(", ")))
+}
+
+fn unify<'a>(typ: &'a UnresolvedType, arg: &Expression, resolved: &mut HashMap<&'a str, String>) {
+    match (typ, &arg.kind) {
+        (
+            UnresolvedType::Array(length, element),
+            ExpressionKind::Literal(Literal::Array(array)),
+        ) => {
+            if let Some(UnresolvedTypeExpression::Variable(path)) = length {
+                if let (Some(name), Some(len)) = (path.segments.last(), array_length(array)) {
+                    resolved.insert(name.0.contents.as_str(), len.to_string());
+                }
+            }
+            if let ArrayLiteral::Standard(elements) = array {
+                if let Some(first) = elements.first() {
+                    unify(element, first, resolved);
+                }
+            }
+        }
+        (UnresolvedType::Named(path, _), ExpressionKind::Literal(literal))
+            if path.segments.len() == 1 =>
+        {
+            if let Some(name) = literal_type_name(literal) {
+                resolved.insert(path.segments[0].0.contents.as_str(), name.to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn array_length(array: &ArrayLiteral) -> Option<u128> {
+    match array {
+        ArrayLiteral::Standard(elements) => Some(elements.len() as u128),
+        ArrayLiteral::Repeated { length, .. } => match &length.kind {
+            ExpressionKind::Literal(Literal::Integer(value)) => value.try_into_u128(),
+            _ => None,
+        },
+    }
+}
+
+/// The type the compiler gives an unannotated literal, written as it would be in source so
+/// it can be inserted as an annotation. Integers take their type from how they are used, so
+/// they, and arrays of them or of non-literal elements, have no such type here.
+pub fn literal_type(literal: &Literal) -> Option<String> {
+    match literal {
+        Literal::Array(array) => {
+            let element = match array {
+                ArrayLiteral::Standard(elements) => elements.first()?,
+                ArrayLiteral::Repeated {
+                    repeated_element, ..
+                } => repeated_element,
+            };
+            let element_type = match &element.kind {
+                ExpressionKind::Literal(literal) => literal_type(literal)?,
+                _ => return None,
+            };
+            Some(format!("[{}; {}]", element_type, array_length(array)?))
+        }
+        Literal::Str(string) => Some(format!("str<{}>", string.len())),
+        Literal::Integer(_) => None,
+        _ => literal_type_name(literal).map(str::to_string),
+    }
+}
+
+fn literal_type_name(literal: &Literal) -> Option<&'static str> {
+    match literal {
+        Literal::Bool(_) => Some("bool"),
+        Literal::Integer(_) => Some("Field"),
+        Literal::Str(_) => Some("str"),
+        Literal::Array(_) => None,
+    }
+}
+
+/// Trip counts of `for` loops and lengths of array literals, as `(offset, label)` pairs
+/// placed after the range or the literal. These drive unrolling, so they are only shown
+/// when the bounds fold to constants.
+pub fn length_hints(module: &ParsedModule) -> Vec<(u32, String)> {
+    let globals = global_constants(module);
+    let mut hints = Vec::new();
+    all_functions(module).for_each(|func| {
+        walk_statements(&func.def.body.0, &mut |expr| match &expr.kind {
+            ExpressionKind::For(for_loop) => {
+                let start = eval_constant(&for_loop.start_range, &globals);
+                let end = eval_constant(&for_loop.end_range, &globals);
+                if let (Some(start), Some(end)) = (start, end) {
+                    let trips = end.saturating_sub(start);
+                    let plural = if trips == 1 { "" } else { "s" };
+                    hints.push((
+                        for_loop.end_range.span.end(),
+                        format!("{} iteration{}", trips, plural),
+                    ));
+                }
+            }
+            ExpressionKind::Literal(Literal::Array(array)) => {
+                let length = match array {
+                    ArrayLiteral::Repeated { length, .. } => eval_constant(length, &globals),
+                    ArrayLiteral::Standard(_) => array_length(array),
+                };
+                if let Some(length) = length {
+                    hints.push((expr.span.end(), format!("len {}", length)));
+                }
+            }
+            _ => {}
+        })
+    });
+    hints
+}
+
+/// Module-level `global`s whose value folds to an integer.
+pub fn global_constants(module: &ParsedModule) -> HashMap<String, u128> {
+    let mut constants = HashMap::new();
+    module.globals.iter().for_each(|global| {
+        if let Pattern::Identifier(name) = &global.pattern {
+            if let Some(value) = eval_constant(&global.expression, &constants) {
+                constants.insert(name.0.contents.clone(), value);
+            }
+        }
+    });
+    constants
+}
+
+/// Folds integer literals, references to known constants and `+ - * /` between them.
+pub fn eval_constant(expr: &Expression, constants: &HashMap<String, u128>) -> Option<u128> {
+    match &expr.kind {
+        ExpressionKind::Literal(Literal::Integer(value)) => value.try_into_u128(),
+        ExpressionKind::Path(path) if path.segments.len() == 1 => {
+            constants.get(&path.segments[0].0.contents).copied()
+        }
+        ExpressionKind::Infix(infix) => {
+            let lhs = eval_constant(&infix.lhs, constants)?;
+            let rhs = eval_constant(&infix.rhs, constants)?;
+            match infix.operator.contents {
+                BinaryOpKind::Add => lhs.checked_add(rhs),
+                BinaryOpKind::Subtract => lhs.checked_sub(rhs),
+                BinaryOpKind::Multiply => lhs.checked_mul(rhs),
+                BinaryOpKind::Divide => lhs.checked_div(rhs),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Return types of calls whose result feeds straight into another call, either as the
+/// receiver of a method (`a.map(f) /* : [u32; 4] */ .fold(..)`) or as an argument, as
+/// `(offset, label)` pairs placed after the inner call.
+///
+/// Methods are looked up by name alone, so a name declared with different return types in
+/// several `impl`s is left without a hint rather than guessed.
+pub fn chaining_hints(module: &ParsedModule) -> Vec<(u32, String)> {
+    let mut return_types: HashMap<&str, Option<String>> = HashMap::new();
+    all_functions(module).for_each(|func| {
+        let rendered = match &func.def.return_type {
+            UnresolvedType::Unit | UnresolvedType::Unspecified => None,
+            typ => Some(typ.to_string()),
+        };
+        return_types
+            .entry(func.def.name.0.contents.as_str())
+            .and_modify(|existing| {
+                if *existing != rendered {
+                    *existing = None
+                }
+            })
+            .or_insert(rendered);
+    });
+
+    let return_type_of = |expr: &Expression| {
+        let name = match &expr.kind {
+            ExpressionKind::MethodCall(call) => &call.method_name,
+            ExpressionKind::Call(call) => call.func_name.segments.last()?,
+            _ => return None,
+        };
+        let typ = return_types.get(name.0.contents.as_str())?.as_ref()?;
+        Some((expr.span.end(), format!(": {}", typ)))
+    };
+
+    let mut hints = Vec::new();
+    all_functions(module).for_each(|func| {
+        walk_statements(&func.def.body.0, &mut |expr| match &expr.kind {
+            ExpressionKind::MethodCall(call) => hints.extend(return_type_of(&call.object)),
+            ExpressionKind::Call(call) => {
+                hints.extend(call.arguments.iter().filter_map(return_type_of))
+            }
+            _ => {}
+        })
+    });
+    hints
+}
+
+/// Estimated opcode counts of the functions of `module`, the module at `module_path`, as
+/// `(offset, label)` pairs placed just inside the opening brace of each function body.
+pub fn gate_count_hints(
+    module: &ParsedModule,
+    module_path: &str,
+    opcodes: &HashMap<FunctionKey, usize>,
+    document: &Rope,
+) -> Vec<(u32, String)> {
+    function_keys(module, module_path)
+        .into_iter()
+        .filter_map(|(key, func)| {
+            let count = opcodes.get(&key)?;
+            let name_end = span_offset(func.def.name.span().end(), document)?;
+            // In chars from here on: the rope counts chars, the frontend's spans bytes.
+            let brace = document.get_chars_at(name_end)?.position(|c| c == '{')?;
+            let plural = if *count == 1 { "" } else { "s" };
+            // Given back as a byte offset, like the other hints' spans.
+            let inside = byte_offset(name_end + brace + 1, document)?;
+            Some((inside, format!("≈{} opcode{}", count, plural)))
+        })
+        .collect()
+}
+
+/// The type an unannotated integer binding `name` in `func` ends up with: the integer type of
+/// whatever it is combined with or passed to, or `Field` when nothing pins it down. The flag
+/// is true when the type was defaulted, since `Field` arithmetic wraps around the field
+/// modulus instead of failing on overflow.
+pub fn integer_binding_type(
+    module: &ParsedModule,
+    func: &NoirFunction,
+    name: &str,
+) -> (String, bool) {
+    let mut known: HashMap<&str, &UnresolvedType> = HashMap::new();
+    func.def.parameters.iter().for_each(|(pattern, typ, _)| {
+        if let Pattern::Identifier(ident) = pattern {
+            known.insert(ident.0.contents.as_str(), typ);
+        }
+    });
+    func.def.body.0.iter().for_each(|statement| {
+        if let Statement::Let(val) = statement {
+            if let Pattern::Identifier(ident) = &val.pattern {
+                known.insert(ident.0.contents.as_str(), &val.r#type);
+            }
+        }
+    });
+    let callees: HashMap<&str, &NoirFunction> = all_functions(module)
+        .map(|func| (func.def.name.0.contents.as_str(), func))
+        .collect();
+
+    let is_binding = |expr: &Expression| {
+        matches!(&expr.kind, ExpressionKind::Path(path)
+            if path.segments.len() == 1 && path.segments[0].0.contents == name)
+    };
+    let integer_type_of = |expr: &Expression| -> Option<String> {
+        let typ = match &expr.kind {
+            ExpressionKind::Path(path) if path.segments.len() == 1 => {
+                *known.get(path.segments[0].0.contents.as_str())?
+            }
+            ExpressionKind::Cast(cast) => &cast.r#type,
+            _ => return None,
+        };
+        matches!(typ, UnresolvedType::Integer(..)).then(|| typ.to_string())
+    };
+
+    let mut inferred = None;
+    walk_statements(&func.def.body.0, &mut |expr| {
+        if inferred.is_some() {
+            return;
+        }
+        match &expr.kind {
+            ExpressionKind::Infix(infix) if is_binding(&infix.lhs) => {
+                inferred = integer_type_of(&infix.rhs)
+            }
+            ExpressionKind::Infix(infix) if is_binding(&infix.rhs) => {
+                inferred = integer_type_of(&infix.lhs)
+            }
+            ExpressionKind::Call(call) => {
+                let callee = call
+                    .func_name
+                    .segments
+                    .last()
+                    .and_then(|name| callees.get(name.0.contents.as_str()));
+                if let Some(callee) = callee {
+                    inferred = callee
+                        .def
+                        .parameters
+                        .iter()
+                        .zip(&call.arguments)
+                        .find(|(_, arg)| is_binding(arg))
+                        .and_then(|((_, typ, _), _)| {
+                            matches!(typ, UnresolvedType::Integer(..)).then(|| typ.to_string())
+                        });
+                }
+            }
+            _ => {}
+        }
+    });
+
+    match inferred {
+        Some(typ) => (typ, false),
+        None => ("Field".to_string(), true),
+    }
+}
+
+/// Types of the parameters and top-level `let`s of `func` that are either written out or
+/// follow from a literal initializer.
+pub fn binding_types(func: &NoirFunction) -> HashMap<String, String> {
+    let mut types = HashMap::new();
+    func.def.parameters.iter().for_each(|(pattern, typ, _)| {
+        if let Pattern::Identifier(ident) = pattern {
+            types.insert(ident.0.contents.clone(), typ.to_string());
+        }
+    });
+    func.def.body.0.iter().for_each(|statement| {
+        if let Statement::Let(val) = statement {
+            if let Pattern::Identifier(ident) = &val.pattern {
+                let typ = match (&val.r#type, &val.expression.kind) {
+                    (UnresolvedType::Unspecified, ExpressionKind::Literal(literal)) => {
+                        literal_type(literal)
+                    }
+                    (UnresolvedType::Unspecified, _) => None,
+                    (typ, _) => Some(typ.to_string()),
+                };
+                if let Some(typ) = typ {
+                    types.insert(ident.0.contents.clone(), typ);
+                }
+            }
+        }
+    });
+    types
+}
+
+/// The type hint for `val` in `func`, with whether it is only the `Field` the compiler
+/// defaults an integer nothing constrains to. Unlike an annotation, a hint can show the
+/// default, since it matters for overflow.
+pub fn let_type_hint(
+    module: &ParsedModule,
+    func: &NoirFunction,
+    val: &LetStatement,
+) -> Option<(String, bool)> {
+    match (&val.expression.kind, binding_name(&val.pattern)) {
+        (ExpressionKind::Literal(Literal::Integer(_)), Some(name)) => {
+            Some(integer_binding_type(module, func, &name.0.contents))
+        }
+        _ => inferred_let_type(module, func, val).map(|typ| (typ, false)),
+    }
+}
+
+/// The name `pattern` binds, `mut` or not, when it binds a single one.
+fn binding_name(pattern: &Pattern) -> Option<&Ident> {
+    match pattern {
+        Pattern::Identifier(name) => Some(name),
+        Pattern::Mutable(pattern, _) => binding_name(pattern),
+        _ => None,
+    }
+}
+
+/// What the type of `val` in `func` would be without an annotation, when it can be told
+/// from the initializer: a literal, an integer whose uses pin its width, a cast, a variable of
+/// known type or a call to a function of this module.
+pub fn inferred_let_type(
+    module: &ParsedModule,
+    func: &NoirFunction,
+    val: &LetStatement,
+) -> Option<String> {
+    match &val.expression.kind {
+        ExpressionKind::Literal(Literal::Integer(_)) => {
+            let name = binding_name(&val.pattern)?;
+            match integer_binding_type(module, func, &name.0.contents) {
+                (typ, false) => Some(typ),
+                // Only what the binding is used with is looked at, so a default is a guess.
+                (_, true) => None,
+            }
+        }
+        ExpressionKind::Literal(literal) => literal_type(literal),
+        ExpressionKind::Cast(cast) => Some(cast.r#type.to_string()),
+        ExpressionKind::Path(path) if path.segments.len() == 1 => {
+            binding_types(func).remove(&path.segments[0].0.contents)
+        }
+        ExpressionKind::Call(call) => {
+            let name = call.func_name.segments.last()?;
+            all_functions(module)
+                .find(|callee| callee.def.name.0.contents == name.0.contents)
+                .map(|callee| match &callee.def.return_type {
+                    UnresolvedType::Unspecified => UnresolvedType::Unit.to_string(),
+                    typ => typ.to_string(),
+                })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noirc_frontend::parse_program;
+
+    use super::*;
+    use crate::circuit_size::estimate_opcodes;
+
+    fn generic_hints(text: &str) -> Vec<(usize, String)> {
+        let (module, _) = parse_program(text);
+        generic_argument_hints(&module)
+            .into_iter()
+            .map(|(offset, label)| (offset as usize, label))
+            .collect()
+    }
+
+    #[test]
+    fn array_lengths_give_numeric_generics() {
+        let text = "fn compute_root<N>(path: [Field; N]) -> Field { path[0] }
+fn main() { let root = compute_root([1, 2, 3]); }";
+        let call = text.rfind("compute_root").unwrap() + "compute_root".len();
+        assert_eq!(generic_hints(text), vec![(call, "::<3>".to_string())]);
+    }
+
+    #[test]
+    fn generics_the_arguments_leave_open_are_underscores() {
+        let text = "fn pair<T, N>(x: T, y: [Field; N]) {}
+fn main(a: Field) { pair(true, [1, 2]); pair(a, [1, 2]); }";
+        let labels = generic_hints(text)
+            .into_iter()
+            .map(|(_, label)| label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["::<bool, 2>", "::<_, 2>"]);
+    }
+
+    #[test]
+    fn calls_whose_generics_nothing_pins_down_get_no_hint() {
+        let text = "fn id<T>(x: T) -> T { x }
+fn double(x: Field) -> Field { x * 2 }
+fn main(a: Field) { let b = id(a); let c = double(b); }";
+        assert!(generic_hints(text).is_empty());
+    }
+
+    #[test]
+    fn parameter_names_are_shown_unless_the_argument_repeats_them() {
+        let text = "fn add(a: Field, b: Field) -> Field { a + b }
+fn main(a: Field) { let c = add(a, 2); }";
+        let (module, _) = parse_program(text);
+        let two = text.rfind('2').unwrap() as u32;
+        assert_eq!(parameter_hints(&module), vec![(two, "b:".to_string())]);
+    }
+
+    fn let_hint(text: &str) -> Option<(String, bool)> {
+        let (module, _) = parse_program(text);
+        let func = &module.functions[0];
+        match &func.def.body.0[0] {
+            Statement::Let(val) => let_type_hint(&module, func, val),
+            statement => panic!("expected a let, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn integer_hints_take_the_width_they_are_used_with() {
+        let text = "fn main(y: u8) { let mut x = 1; assert(x + y == 2); }";
+        assert_eq!(let_hint(text), Some(("u8".to_string(), false)));
+    }
+
+    #[test]
+    fn unconstrained_integers_are_hinted_as_the_default_field() {
+        let text = "fn main() { let x = 5; }";
+        assert_eq!(let_hint(text), Some(("Field".to_string(), true)));
+    }
+
+    #[test]
+    fn gate_counts_go_inside_the_body_past_multibyte_text() {
+        let text = "// π ≈ 3\nfn main(x: Field) { assert(x * x == 4); }";
+        let (module, _) = parse_program(text);
+        let document = Rope::from_str(text);
+        let opcodes = estimate_opcodes(&module, "crate");
+        let hints = gate_count_hints(&module, "crate", &opcodes, &document);
+        let inside = text.find('{').unwrap() + 1;
+        assert_eq!(hints, vec![(inside as u32, "≈2 opcodes".to_string())]);
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use noirc_frontend::{NoirFunction, Pattern, Statement};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, TextDocumentPositionParams};
+
+use crate::prover_toml::{is_public, parameter_name};
+
+/// Ghost text continuing the line being typed. The request is newer than the `lsp-types` the
+/// server is built with, so its types are declared here.
+pub const INLINE_COMPLETION: &str = "textDocument/inlineCompletion";
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionParams {
+    #[serde(flatten)]
+    pub text_document_position: TextDocumentPositionParams,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineCompletionItem {
+    pub insert_text: InsertText,
+    pub range: Range,
+}
+
+/// Plain text, or a snippet whose placeholders the user tabs through.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum InsertText {
+    Text(String),
+    Snippet { kind: String, value: String },
+}
+
+/// Closes the `assert(` the line ends in: with the parentheses and semicolon it still lacks
+/// once a condition is written, or with a condition as well right after the opening
+/// parenthesis. That condition compares the last variable `func` binds before `offset`, or
+/// its first private parameter, with its first `pub` one, as `main` checks its result.
+pub fn close_assert(line: &str, func: Option<&NoirFunction>, offset: u32) -> Option<String> {
+    let start = line.rfind("assert(")?;
+    let condition = &line[start + "assert(".len()..];
+    let opened = condition.matches('(').count() + 1;
+    let closed = condition.matches(')').count();
+    let missing = ")".repeat(opened.checked_sub(closed).filter(|n| *n > 0)?);
+    if !condition.trim().is_empty() {
+        let last = condition.trim_end().chars().last()?;
+        let complete = last.is_alphanumeric() || matches!(last, '_' | ')' | ']');
+        return complete.then(|| format!("{};", missing));
+    }
+
+    let func = func?;
+    let parameters = &func.def.parameters;
+    let public = parameters
+        .iter()
+        .filter(|(_, _, visibility)| is_public(visibility))
+        .find_map(|(pattern, _, _)| parameter_name(pattern))?;
+    let bound = func
+        .def
+        .body
+        .0
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Let(val) => match &val.pattern {
+                Pattern::Identifier(name) if name.span().end() <= offset => {
+                    Some(name.0.contents.clone())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .last();
+    let compared = bound.or_else(|| {
+        parameters
+            .iter()
+            .filter(|(_, _, visibility)| !is_public(visibility))
+            .find_map(|(pattern, _, _)| parameter_name(pattern))
+    })?;
+    (compared != public).then(|| format!("{} == {}{};", compared, public, missing))
+}
+
+/// Fills in the fields of the struct literal the line ends in, `Point {`, given the struct's
+/// `fields` as `fields_of` finds them. Fields get the variable of the same name when there
+/// is one in `names`, and a placeholder to fill in otherwise.
+pub fn complete_struct(
+    line: &str,
+    fields_of: impl Fn(&str) -> Option<Vec<String>>,
+    names: &HashSet<String>,
+) -> Option<InsertText> {
+    let before = line.trim_end().strip_suffix('{')?.trim_end();
+    let name_start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1);
+    let (context, name) = before.split_at(name_start);
+    // Types after `struct`, `impl` or `->` open a body rather than a literal.
+    let in_expression = context
+        .trim_end()
+        .chars()
+        .last()
+        .map_or(true, |c| matches!(c, '=' | '(' | ',' | '[' | '{'));
+    if name.is_empty() || !in_expression {
+        return None;
+    }
+    let fields = fields_of(name)?;
+    if fields.is_empty() {
+        return Some(InsertText::Text(" }".to_string()));
+    }
+    if fields.iter().all(|field| names.contains(field)) {
+        let fields = fields
+            .iter()
+            .map(|field| format!("{}: {}", field, field))
+            .collect::<Vec<_>>();
+        return Some(InsertText::Text(format!(" {} }}", fields.join(", "))));
+    }
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            if names.contains(field) {
+                format!("{}: {}", field, field)
+            } else {
+                format!("{}: ${{{}:{}}}", field, i + 1, field)
+            }
+        })
+        .collect::<Vec<_>>();
+    Some(InsertText::Snippet {
+        kind: "snippet".to_string(),
+        value: format!(" {} }}$0", fields.join(", ")),
+    })
+}
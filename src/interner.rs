@@ -0,0 +1,74 @@
+use std::mem::size_of;
+use std::sync::Arc;
+
+use dashmap::DashSet;
+
+/// Names the server holds many copies of, such as the ones the indexes and the trees'
+/// derived results keep, stored once and shared behind an `Arc`. The same name from
+/// another file, another dependency or another parse of the same file takes no more memory,
+/// and comparing it costs a pointer check at most.
+///
+/// Interned names stay until `collect` drops those nothing else holds anymore.
+///
+/// Only names are interned. The syntax trees' nodes are the frontend's types, allocated by its
+/// parser, so the server shares each tree behind an `Arc` rather than copying it into an arena.
+#[derive(Debug, Default)]
+pub struct Interner {
+    names: DashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// The shared copy of `name`, made on first use.
+    pub fn intern(&self, name: &str) -> Arc<str> {
+        if let Some(interned) = self.names.get(name) {
+            return Arc::clone(&interned);
+        }
+        let interned = Arc::<str>::from(name);
+        if self.names.insert(Arc::clone(&interned)) {
+            return interned;
+        }
+        // Another thread interned it in the meantime, so share theirs.
+        self.names
+            .get(name)
+            .map_or(interned, |interned| Arc::clone(&interned))
+    }
+
+    /// Drops the names only the interner still holds.
+    pub fn collect(&self) {
+        self.names.retain(|name| Arc::strong_count(name) > 1);
+    }
+
+    /// The bytes the interned names take, their text and their place in the set.
+    pub fn bytes(&self) -> usize {
+        self.names
+            .iter()
+            .map(|name| size_of::<Arc<str>>() + 2 * size_of::<usize>() + name.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_one_copy_of_each_name() {
+        let interner = Interner::default();
+        let first = interner.intern("main");
+        let second = interner.intern(&String::from("main"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &interner.intern("foo")));
+    }
+
+    #[test]
+    fn collects_only_names_nothing_else_holds() {
+        let interner = Interner::default();
+        let kept = interner.intern("kept");
+        interner.intern("dropped");
+        let before = interner.bytes();
+        interner.collect();
+        assert!(interner.bytes() < before);
+        assert!(Arc::ptr_eq(&kept, &interner.intern("kept")));
+        assert_eq!(interner.names.len(), 1);
+    }
+}
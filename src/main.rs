@@ -1,50 +1,351 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
-use dashmap::DashMap;
-use noirc_frontend::{parse_program, ExpressionKind, ParsedModule, Statement};
+use clap::Parser;
+use dashmap::{DashMap, DashSet};
+use noirc_frontend::{parse_program, ExpressionKind, ParsedModule, Statement, UnresolvedType};
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use thread_safe::ThreadSafe;
+use tokio::task::AbortHandle;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::notification::Notification;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+mod acir;
+mod analysis;
+mod ast;
+mod catch_panic;
+mod circuit_size;
+mod code_actions;
+mod code_lens;
+mod commands;
+mod config;
+mod contracts;
+mod diagnostics;
+mod file_operations;
+mod index;
+mod index_cache;
+mod inlay_hints;
+mod inline_completion;
+mod interner;
+mod markup;
+mod memory;
+mod nargo;
+mod outline;
+mod prover_toml;
+mod registration;
+mod status;
+mod stdlib;
+mod telemetry;
+mod test_explorer;
+mod trace;
+mod utils;
+mod vfs;
+mod workspace;
+
+use analysis::AnalysisStore;
+use catch_panic::CatchPanic;
+use config::Config;
+use index::FileSymbols;
+use status::{CompilationState, CompilationStatus, ServerStatus, StatusReport};
+use stdlib::StdItem;
+use test_explorer::{ListTestsParams, RunTestsParams, TestItem, TestOutcome, TestStatus};
+use utils::{
+    apply_changes, offset_to_position, pattern_end, position_to_byte, position_to_offset,
+    span_position, span_range, PositionEncoding,
+};
+use vfs::Vfs;
+use workspace::{Dependency, PackageKind, Project};
+
+/// The server. It only holds its `State` behind an `Arc`, so work that outlives the
+/// notification starting it, such as analysing edits once they stop, can take a clone of it
+/// to a task of its own.
+#[derive(Debug, Clone)]
+struct Backend(Arc<State>);
+
+impl std::ops::Deref for Backend {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        &self.0
+    }
+}
+
 #[derive(Debug)]
-struct Backend {
+struct State {
     client: Client,
-    ast_map: DashMap<String, ThreadSafe<RefCell<ParsedModule>>>,
+    ast_map: AnalysisStore,
     document_map: DashMap<String, Rope>,
+    /// What the editor's buffers hold, layered over the files on disk.
+    vfs: Vfs,
+    /// Estimated opcodes per function, only present while the document parses.
+    gate_count_map: DashMap<String, HashMap<circuit_size::FunctionKey, usize>>,
+    /// Roots of the packages whose last `nargo check` reported no errors, the only ones whose
+    /// functions get gate count hints.
+    checked_packages: DashSet<PathBuf>,
+    version_map: DashMap<String, i32>,
+    /// Documents edited since they were last analysed, with the version waiting for it.
+    unanalysed: DashMap<String, i32>,
+    /// The analysis waiting for each document's edits to stop, aborted by the next edit.
+    debounced: DashMap<String, (i32, AbortHandle)>,
+    /// Open documents an edit couldn't be applied to. Their edits are refused until one
+    /// brings the whole text.
+    out_of_sync: DashSet<String>,
+    /// Roots of the packages whose ACIR the client has viewed, to tell it when to refresh.
+    acir_views: DashSet<PathBuf>,
+    /// What the last `nargo check` of each file's package reported for it.
+    compile_diagnostics: DashMap<String, Vec<Diagnostic>>,
+    /// Every hint in a document, computed for the version it is keyed with.
+    inlay_hint_cache: DashMap<String, (i32, Vec<InlayHint>)>,
+    config: RwLock<Config>,
+    /// The nargo binary commands run, once one has been found.
+    nargo: RwLock<Option<PathBuf>>,
+    /// The nargo of the toolchain version each package's toolchain file pins, keyed by the
+    /// package's root, with the version it was found for.
+    pinned_nargo: DashMap<PathBuf, (String, Option<PathBuf>)>,
+    /// Whether positions are exchanged with the client in UTF-8 bytes rather than UTF-16.
+    utf8_positions: AtomicBool,
+    /// Whether the client resolves code action edits lazily through `codeAction/resolve`.
+    resolve_code_action_edits: AtomicBool,
+    /// Whether the client can show `ChangeAnnotation`s on workspace edits.
+    change_annotation_support: AtomicBool,
+    /// Whether the client renders markdown in hovers, rather than plain text.
+    markdown_hovers: AtomicBool,
+    /// Whether the client answers `workspace/configuration`.
+    configuration_support: AtomicBool,
+    /// Whether the client asks for code lenses again on `workspace/codeLens/refresh`.
+    code_lens_refresh_support: AtomicBool,
+    /// Whether the client asks for inlay hints again on `workspace/inlayHint/refresh`.
+    inlay_hint_refresh_support: AtomicBool,
+    /// Whether the client shows the progress the server creates with
+    /// `window/workDoneProgress/create`.
+    work_done_progress_support: AtomicBool,
+    /// How many progress tokens have been handed out, which numbers the next one.
+    progress_tokens: AtomicU64,
+    /// The providers to register once the client is initialized.
+    dynamic: RwLock<registration::Dynamic>,
+    /// The workspace folders the client has open.
+    workspace_folders: DashSet<PathBuf>,
+    /// Every package discovered so far, keyed by the directory holding its `Nargo.toml`.
+    projects: DashMap<PathBuf, Project>,
+    /// Roots of the packages whose whole crate has been parsed.
+    loaded_crates: DashSet<PathBuf>,
+    /// Top-level items of every module of the loaded packages' dependencies, keyed by the
+    /// dependency's root and the module's path in it, such as `crate::tree`. Each package
+    /// imports them under the name its own manifest gives the dependency, see
+    /// `dependency_modules`.
+    dependency_index: DashMap<(PathBuf, String), Vec<StdItem>>,
+    /// Items of every standard library module, keyed by its path such as `std::hash`. Filled
+    /// by the warm-up, or by the first request needing it otherwise, see `ensure_stdlib`.
+    stdlib_index: DashMap<String, Vec<StdItem>>,
+    /// Whether `stdlib_index` is of the standard library of the toolchain in use.
+    stdlib_indexed: tokio::sync::Mutex<bool>,
+    /// Roots of the dependencies in `dependency_index`, which every package depending on
+    /// them shares.
+    indexed_dependencies: DashSet<PathBuf>,
+    /// The outcome of the last run of each test, keyed by the URI of its file and its name,
+    /// for the test lenses.
+    test_statuses: DashMap<(String, String), TestStatus>,
+    /// The status last sent to the client.
+    status: RwLock<ServerStatus>,
+    /// How the last background compile that finished went.
+    last_compile: RwLock<Option<CompilationStatus>>,
+    /// When the server started, for `noir/status`.
+    started: Instant,
     // semantic_token_map: DashMap<String, Vec<ImCompleteSemanticToken>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(value) = params.trace {
+            trace::set(value);
+        }
+        if let Some(options) = &params.initialization_options {
+            let config = Config::from_settings(options);
+            if let Some(threads) = config.num_threads {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build_global();
+                if let Err(err) = pool {
+                    tracing::warn!("cannot start {} parsing threads: {}", threads, err);
+                }
+            }
+            telemetry::set_enabled(config.telemetry);
+            *self.config.write().unwrap_or_else(PoisonError::into_inner) = config;
+        }
+        // Columns are counted in UTF-8 bytes when the client can, since that is what the
+        // frontend's spans count, and in UTF-16 code units otherwise.
+        let utf8_positions = params
+            .capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_ref())
+            .is_some_and(|encodings| encodings.contains(&PositionEncodingKind::UTF8));
+        self.utf8_positions.store(utf8_positions, Ordering::Relaxed);
+        let resolves_edits = params
+            .capabilities
+            .text_document
+            .and_then(|text_document| text_document.code_action)
+            .and_then(|code_action| code_action.resolve_support)
+            .is_some_and(|support| support.properties.iter().any(|p| p == "edit"));
+        self.resolve_code_action_edits
+            .store(resolves_edits, Ordering::Relaxed);
+        let dynamic = registration::Dynamic::of(&params.capabilities);
+        *self.dynamic.write().unwrap_or_else(PoisonError::into_inner) = dynamic;
+        let hover_formats = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.hover.as_ref())
+            .and_then(|hover| hover.content_format.as_ref());
+        self.markdown_hovers
+            .store(markup::supports_markdown(hover_formats), Ordering::Relaxed);
+        let configuration = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.configuration)
+            .unwrap_or(false);
+        self.configuration_support
+            .store(configuration, Ordering::Relaxed);
+        let code_lens_refresh = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.code_lens.as_ref())
+            .and_then(|code_lens| code_lens.refresh_support)
+            .unwrap_or(false);
+        self.code_lens_refresh_support
+            .store(code_lens_refresh, Ordering::Relaxed);
+        let work_done_progress = params
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        self.work_done_progress_support
+            .store(work_done_progress, Ordering::Relaxed);
+        let inlay_hint_refresh = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.inlay_hint.as_ref())
+            .and_then(|inlay_hint| inlay_hint.refresh_support)
+            .unwrap_or(false);
+        self.inlay_hint_refresh_support
+            .store(inlay_hint_refresh, Ordering::Relaxed);
+        let annotations = params
+            .capabilities
+            .workspace
+            .and_then(|workspace| workspace.workspace_edit)
+            .is_some_and(|workspace_edit| workspace_edit.change_annotation_support.is_some());
+        self.change_annotation_support
+            .store(annotations, Ordering::Relaxed);
+
+        let roots = params
+            .workspace_folders
+            .iter()
+            .flatten()
+            .map(|folder| &folder.uri)
+            .chain(params.root_uri.as_ref());
+        for root in roots {
+            if let Ok(path) = root.to_file_path() {
+                self.add_workspace_folder(&path).await;
+            }
+        }
+
         Ok(InitializeResult {
             server_info: None,
             offset_encoding: None,
             capabilities: ServerCapabilities {
-                inlay_hint_provider: Some(OneOf::Left(true)),
+                position_encoding: Some(self.encoding().kind()),
+                inlay_hint_provider: (!dynamic.inlay_hints).then_some(OneOf::Left(true)),
+                code_lens_provider: (!dynamic.code_lens).then_some(CodeLensOptions {
+                    resolve_provider: Some(true),
+                }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![":".to_string()]),
+                    ..CompletionOptions::default()
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: None,
+                        work_done_progress_options: WorkDoneProgressOptions::default(),
+                        resolve_provider: Some(true),
+                    },
+                )),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: commands::COMMANDS
+                        .iter()
+                        .map(|command| command.to_string())
+                        .collect(),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 workspace: Some(WorkspaceServerCapabilities {
                     workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                         supported: Some(true),
                         change_notifications: Some(OneOf::Left(true)),
                     }),
-                    file_operations: None,
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        did_create: Some(file_operations::workspace_files()),
+                        did_rename: Some(file_operations::workspace_files()),
+                        did_delete: Some(file_operations::workspace_files()),
+                        will_rename: Some(file_operations::noir_files()),
+                        ..WorkspaceFileOperationsServerCapabilities::default()
+                    }),
                 }),
+                // Capabilities newer than `lsp-types`, which clients look for by their name.
+                experimental: Some(serde_json::json!({ "inlineCompletionProvider": true })),
                 ..ServerCapabilities::default()
             },
         })
     }
     async fn initialized(&self, _: InitializedParams) {
+        tracing::info!("initialized");
         self.client
-            .log_message(MessageType::INFO, "initialized!")
+            .send_notification::<ServerStatusNotification>(ServerStatus::Loading)
             .await;
+
+        let dynamic = *self.dynamic.read().unwrap_or_else(PoisonError::into_inner);
+        if !dynamic.watchers {
+            tracing::info!(
+                "the client can't watch files, reload the workspace after editing one outside it"
+            );
+        }
+        let registrations = dynamic.registrations();
+        if !registrations.is_empty() {
+            if let Err(err) = self.client.register_capability(registrations).await {
+                self.client.log_message(MessageType::ERROR, err).await;
+            }
+        }
+        if let Some(config) = self.fetch_config().await {
+            telemetry::set_enabled(config.telemetry);
+            *self.config.write().unwrap_or_else(PoisonError::into_inner) = config;
+        }
+        self.find_nargo().await;
+        self.warm_up().await;
+        self.settle_status().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -52,9 +353,19 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file opened!")
-            .await;
+        tracing::debug!(uri = %params.text_document.uri, "opened");
+        // Kept before anything is awaited, so requests and edits coming in while the crate
+        // loads find the document.
+        let rope = Rope::from_str(&params.text_document.text);
+        if let Ok(path) = params.text_document.uri.to_file_path() {
+            self.vfs.set(&path, rope.clone());
+        }
+        self.set_document(
+            &params.text_document.uri,
+            rope,
+            params.text_document.version,
+        );
+        self.out_of_sync.remove(params.text_document.uri.as_str());
         self.on_change(TextDocumentItem {
             uri: params.text_document.uri,
             text: params.text_document.text,
@@ -63,206 +374,3227 @@ impl LanguageServer for Backend {
         .await
     }
 
-    async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
-        self.on_change(TextDocumentItem {
-            uri: params.text_document.uri,
-            text: std::mem::take(&mut params.content_changes[0].text),
-            version: params.text_document.version,
-        })
-        .await
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let mut rope = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.clone(),
+            // The edits are relative to text the server never got, so there is nothing they
+            // can be applied to.
+            None => {
+                self.client
+                    .log_message(
+                        MessageType::ERROR,
+                        format!("edit to {}, which was never opened", uri),
+                    )
+                    .await;
+                return;
+            }
+        };
+        let mut changes = params.content_changes.as_slice();
+        if self.out_of_sync.contains(uri.as_str()) {
+            // Ranges are relative to text the server doesn't have; only the whole text brings
+            // the document back in sync.
+            match changes.iter().rposition(|change| change.range.is_none()) {
+                Some(full) => changes = &changes[full..],
+                None => return,
+            }
+            self.out_of_sync.remove(uri.as_str());
+        }
+        if let Err(change) = apply_changes(&mut rope, changes, self.encoding()) {
+            // The text stays as it was before the batch, and later edits would only make the
+            // two drift further apart, so they are refused until the editor sends it whole.
+            self.out_of_sync.insert(uri.to_string());
+            self.client
+                .log_message(
+                    MessageType::ERROR,
+                    format!("edit out of range in {}: {:?}", uri, change.range),
+                )
+                .await;
+            self.client
+                .show_message(
+                    MessageType::ERROR,
+                    format!("{} is out of sync with the editor, reopen it", uri),
+                )
+                .await;
+            return;
+        }
+        if let Ok(path) = uri.to_file_path() {
+            self.vfs.set(&path, rope.clone());
+        }
+        // Positions in requests refer to the edited text, so it is kept right away, while
+        // analysing it waits for the edits to stop.
+        let version = params.text_document.version;
+        self.set_document(&uri, rope, version);
+        self.unanalysed.insert(uri.to_string(), version);
+        self.debounce(uri, version);
     }
 
-    async fn did_save(&self, _: DidSaveTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file saved!")
-            .await;
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        tracing::debug!(uri = %params.text_document.uri, "saved");
+        self.analyse_edits(&params.text_document.uri).await;
+        let project = match params
+            .text_document
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return,
+        };
+        if self.acir_views.contains(&project.root) {
+            if let Some(uri) = acir::acir_uri(&project) {
+                self.client
+                    .send_notification::<AcirChangedNotification>(acir::AcirChangedParams { uri })
+                    .await;
+            }
+        }
+        if self
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .compile_on_save
+        {
+            self.check_project(&project).await;
+        }
     }
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file closed!")
-            .await;
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        tracing::debug!(uri = %params.text_document.uri, "closed");
+        let uri = params.text_document.uri;
+        let path = match uri.to_file_path() {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        let lost_edits = self.vfs.close(&path);
+        self.inlay_hint_cache.remove(uri.as_str());
+        self.out_of_sync.remove(uri.as_str());
+
+        // Files of a loaded crate stay, since the rest of the crate is analysed with them.
+        let in_crate = path.extension().is_some_and(|ext| ext == "nr")
+            && self
+                .project_for(&path)
+                .is_some_and(|project| self.loaded_crates.contains(&project.root));
+        if !in_crate {
+            self.forget(uri.as_str());
+            self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        } else if lost_edits {
+            // Unsaved edits are gone with the buffer, so analyse the file as it is on disk.
+            if let Some(text) = self.vfs.read(&path) {
+                let version = self.version_map.get(uri.as_str()).map_or(0, |v| *v);
+                self.set_document(&uri, Rope::from_str(&text), version);
+                self.on_change(TextDocumentItem { uri, text, version })
+                    .await;
+            }
+        } else {
+            self.analyse_edits(&uri).await;
+        }
+        self.evict_closed_files();
     }
 
-    async fn inlay_hint(
-        &self,
-        params: tower_lsp::lsp_types::InlayHintParams,
-    ) -> Result<Option<Vec<InlayHint>>> {
-        let mut inlays: Vec<(u32, u32, Option<String>)> = Vec::new();
-        self.client
-            .log_message(MessageType::INFO, "inlay hint")
-            .await;
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
         let uri = &params.text_document.uri;
-        self.client
-            .log_message(MessageType::INFO, format!("{:?}", uri))
-            .await;
-
-        (*self
-            .ast_map
-            .get(uri.as_str())
-            .unwrap()
-            .value()
-            .try_get_ref()
-            .unwrap()
-            .borrow())
-        .functions
-        .iter()
-        .for_each(|func| match func.kind {
-            noirc_frontend::FunctionKind::Normal => {
-                func.def
-                    .body
-                    .0
-                    .iter()
-                    .for_each(|statement| match statement {
-                        Statement::Let(val) => {
-                            let literal_type = match &val.expression.kind {
-                                ExpressionKind::Literal(lit) => match lit {
-                                    noirc_frontend::Literal::Array(_) => {
-                                        Some(": array".to_string())
-                                    }
-                                    noirc_frontend::Literal::Bool(_) => Some(": bool".to_string()),
-                                    noirc_frontend::Literal::Integer(_) => {
-                                        Some(": integer".to_string())
-                                    }
-                                    noirc_frontend::Literal::Str(_) => Some(": string".to_string()),
-                                },
-                                _ => None,
-                            };
-                            inlays.push((
-                                val.expression.span.start(),
-                                val.expression.span.end(),
-                                literal_type,
-                            ));
-                        }
-                        _ => {}
-                    })
+        tracing::debug!(%uri, "inlay hints");
+        self.analyse_edits(uri).await;
+        let version = self.version_map.get(uri.as_str()).map(|version| *version);
+        if let Some(cached) = self.inlay_hint_cache.get(uri.as_str()) {
+            if Some(cached.0) == version {
+                return Ok(Some(inlay_hints::in_range(&cached.1, params.range)));
             }
-            _ => {}
-        });
-
-        self.client
-            .log_message(MessageType::INFO, format!("{:?}", inlays))
-            .await;
+        }
+        let config = self
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .inlay_hints
+            .clone();
 
         let document = match self.document_map.get(uri.as_str()) {
-            Some(rope) => rope,
+            Some(rope) => rope.clone(),
             None => return Ok(None),
         };
 
+        let (inlays, labelled_hints) = {
+            let mut inlays: Vec<(u32, String, bool)> = Vec::new();
+            let mut labelled_hints = Vec::new();
+            let ast = match self.ast_map.get_version(uri.as_str(), version) {
+                Some(ast) => ast,
+                None => return Ok(None),
+            };
+
+            if config.type_hints {
+                ast.functions.iter().for_each(|func| match func.kind {
+                    noirc_frontend::FunctionKind::Normal => {
+                        func.def
+                            .body
+                            .0
+                            .iter()
+                            .for_each(|statement| match statement {
+                                Statement::Let(val)
+                                    if matches!(val.r#type, UnresolvedType::Unspecified) =>
+                                {
+                                    let literal_type = match &val.expression.kind {
+                                        ExpressionKind::Literal(_) => {
+                                            inlay_hints::let_type_hint(&ast, func, val)
+                                        }
+                                        _ => None,
+                                    };
+                                    if let Some((literal_type, defaulted)) = literal_type {
+                                        inlays.push((
+                                            pattern_end(&val.pattern),
+                                            format!(": {}", literal_type),
+                                            defaulted,
+                                        ));
+                                    }
+                                }
+                                _ => {}
+                            })
+                    }
+                    _ => {}
+                });
+            }
+
+            if config.parameter_hints {
+                inlay_hints::parameter_hints(&ast)
+                    .into_iter()
+                    .for_each(|(offset, label)| {
+                        labelled_hints.push((offset, label, InlayHintKind::PARAMETER, false))
+                    });
+            }
+            if config.generic_hints {
+                inlay_hints::generic_argument_hints(&ast)
+                    .into_iter()
+                    .for_each(|(offset, label)| {
+                        labelled_hints.push((offset, label, InlayHintKind::TYPE, false))
+                    });
+            }
+            if config.length_hints {
+                inlay_hints::length_hints(&ast)
+                    .into_iter()
+                    .for_each(|(offset, label)| {
+                        labelled_hints.push((offset, label, InlayHintKind::TYPE, true))
+                    });
+            }
+            if config.chaining_hints {
+                inlay_hints::chaining_hints(&ast)
+                    .into_iter()
+                    .for_each(|(offset, label)| {
+                        labelled_hints.push((offset, label, InlayHintKind::TYPE, false))
+                    });
+            }
+            // Only for packages nargo compiled on their last check.
+            let checked = || {
+                uri.to_file_path()
+                    .ok()
+                    .and_then(|path| self.project_for(&path))
+                    .is_some_and(|project| self.checked_packages.contains(&project.root))
+            };
+            if config.gate_count_hints && checked() {
+                let opcodes = self.gate_count_map.get(uri.as_str());
+                let module_path = utils::module_path(uri).unwrap_or_else(|| "crate".to_string());
+                if let Some(opcodes) = opcodes {
+                    inlay_hints::gate_count_hints(&ast, &module_path, &opcodes, &document)
+                        .into_iter()
+                        .for_each(|(offset, label)| {
+                            labelled_hints.push((offset, label, InlayHintKind::TYPE, true))
+                        });
+                }
+            }
+            (inlays, labelled_hints)
+        };
+
+        tracing::trace!(?inlays, "type hints");
+
         let inlay_hint_list = inlays
             .iter()
-            .map(|item| {
-                // let start_position = offset_to_position(item.0, document)?;
-                let end_position = offset_to_position((item.0 - 2) as usize, &document).unwrap();
+            .filter_map(|(offset, label, defaulted)| {
+                let end_position = span_position(*offset, &document, self.encoding())?;
+                // Double-clicking the hint writes it into the source as an annotation, unless
+                // it is only the default, which the user should choose to write instead.
+                let text_edits = (!defaulted).then(|| {
+                    vec![TextEdit {
+                        range: Range::new(end_position, end_position),
+                        new_text: label.clone(),
+                    }]
+                });
+                let tooltip = defaulted.then(|| {
+                    InlayHintTooltip::String(
+                        "Nothing constrains this integer, so it defaults to `Field`. \
+                         Field arithmetic wraps around the field modulus \
+                         instead of failing on overflow."
+                            .to_string(),
+                    )
+                });
                 let inlay_hint = InlayHint {
-                    text_edits: None,
-                    tooltip: None,
+                    text_edits,
+                    tooltip,
                     kind: Some(InlayHintKind::TYPE),
                     padding_left: None,
                     padding_right: None,
                     data: None,
                     position: end_position,
                     label: InlayHintLabel::LabelParts(vec![InlayHintLabelPart {
-                        value: item.2.clone().unwrap(),
+                        value: config.truncate(label),
                         tooltip: None,
-                        location: Some(Location {
-                            uri: params.text_document.uri.clone(),
-                            range: Range {
-                                start: Position::new(0, 4),
-                                end: Position::new(0, 5),
-                            },
-                        }),
+                        location: None,
                         command: None,
                     }]),
                 };
-                inlay_hint
+                Some(inlay_hint)
             })
+            .chain(
+                labelled_hints
+                    .iter()
+                    .filter_map(|(offset, label, kind, padded)| {
+                        Some(InlayHint {
+                            text_edits: None,
+                            tooltip: None,
+                            kind: Some(*kind),
+                            padding_left: Some(*padded),
+                            padding_right: Some(*kind == InlayHintKind::PARAMETER),
+                            data: None,
+                            position: span_position(*offset, &document, self.encoding())?,
+                            label: InlayHintLabel::String(config.truncate(label)),
+                        })
+                    }),
+            )
             .collect::<Vec<_>>();
 
-        Ok(Some(inlay_hint_list))
+        if self.outdated(uri.as_str(), version) {
+            return Ok(None);
+        }
+        let hints_in_range = inlay_hints::in_range(&inlay_hint_list, params.range);
+        if let Some(version) = version {
+            self.inlay_hint_cache
+                .insert(uri.to_string(), (version, inlay_hint_list));
+        }
+
+        Ok(Some(hints_in_range))
     }
 
-    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
-        self.client
-            .log_message(MessageType::INFO, "configuration changed!")
-            .await;
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        checkpoint().await;
+        let position = &params.text_document_position_params;
+        self.ensure_stdlib().await;
+        let item = self.library_item_at(&position.text_document.uri, position.position);
+        Ok(item.map(|(item, range)| Hover {
+            contents: HoverContents::Markup(markup::code(
+                &item.signature,
+                self.markdown_hovers.load(Ordering::Relaxed),
+            )),
+            range: Some(range),
+        }))
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
-        self.client
-            .log_message(MessageType::INFO, "workspace folders changed!")
-            .await;
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let position = &params.text_document_position_params;
+        self.ensure_stdlib().await;
+        let uri = &position.text_document.uri;
+        if let Some((item, _)) = self.library_item_at(uri, position.position) {
+            return Ok(Some(GotoDefinitionResponse::Scalar(item.location)));
+        }
+        // Otherwise the items of the package by that name.
+        let name = match self.name_at(uri, position.position) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let locations = self.occurrences(uri, &name, true, false);
+        Ok((!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations)))
     }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
-        self.client
-            .log_message(MessageType::INFO, "watched files have changed!")
-            .await;
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position = &params.text_document_position;
+        let uri = &position.text_document.uri;
+        self.analyse_edits(uri).await;
+        let name = match self.name_at(uri, position.position) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let declarations = params.context.include_declaration;
+        Ok(Some(self.occurrences(uri, &name, declarations, true)))
     }
 
-    async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<Value>> {
-        self.client
-            .log_message(MessageType::INFO, "command executed!")
-            .await;
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let uri = &params.text_document.uri;
+        self.analyse_edits(uri).await;
+        let name = match self.name_at(uri, params.position) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        // Only what the package declares, not the standard library's or local variables.
+        if self.occurrences(uri, &name, true, false).is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(PrepareRenameResponse::DefaultBehavior {
+            default_behavior: true,
+        }))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = &params.text_document_position;
+        let uri = &position.text_document.uri;
+        self.analyse_edits(uri).await;
+        if !utils::is_identifier(&params.new_name) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "`{}` is not a valid name",
+                params.new_name
+            )));
+        }
+        let name = match self.name_at(uri, position.position) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        if self.occurrences(uri, &name, true, false).is_empty() {
+            return Ok(None);
+        }
+        let mut changes: BTreeMap<Url, Vec<TextEdit>> = BTreeMap::new();
+        for location in self.occurrences(uri, &name, true, true) {
+            changes.entry(location.uri).or_default().push(TextEdit {
+                range: location.range,
+                new_text: params.new_name.clone(),
+            });
+        }
+        // Renames reaching into other files are previewed file by file, where the client can.
+        let annotate = changes.len() > 1 && self.change_annotation_support.load(Ordering::Relaxed);
+        let operations = changes
+            .into_iter()
+            .map(|(uri, edits)| code_actions::text_document_edit(&uri, edits))
+            .collect();
+        Ok(Some(code_actions::multi_file_edit(annotate, operations)))
+    }
+
+    /// Moves the paths and the `mod` declaration naming a renamed file's module along with
+    /// it, so the crate still builds once the file has its new name.
+    async fn will_rename_files(&self, params: RenameFilesParams) -> Result<Option<WorkspaceEdit>> {
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for rename in params.files {
+            let (old, new) = match (Url::parse(&rename.old_uri), Url::parse(&rename.new_uri)) {
+                (Ok(old), Ok(new)) => (old, new),
+                _ => continue,
+            };
+            tracing::debug!(%old, %new, "renaming");
+            self.module_rename_edits(&old, &new, &mut changes).await;
+        }
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }))
+    }
+
+    #[allow(deprecated)] // `SymbolInformation::deprecated` is superseded by `tags`.
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let symbols = self
+            .ast_map
+            .symbols()
+            .search(&params.query)
+            .into_iter()
+            .filter_map(|(uri, symbol)| {
+                let document = self.document_map.get(&uri)?.clone();
+                Some(SymbolInformation {
+                    name: symbol.name.to_string(),
+                    kind: symbol.kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: Url::parse(&uri).ok()?,
+                        range: span_range(
+                            symbol.span.0,
+                            symbol.span.1,
+                            &document,
+                            self.encoding(),
+                        )?,
+                    },
+                    container_name: symbol.container.as_deref().map(str::to_string),
+                })
+            })
+            .collect();
+        Ok(Some(symbols))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        checkpoint().await;
+        let position = &params.text_document_position;
+        // Like hovers, completions only take the imports from the tree, so they don't wait
+        // for edits being typed to be analysed.
+        self.ensure_stdlib().await;
+        let uri = position.text_document.uri.as_str();
+        let document = match self.document_map.get(uri) {
+            Some(rope) => rope.clone(),
+            None => return Ok(None),
+        };
+        let path = match position_to_offset(position.position, &document, self.encoding())
+            .and_then(|offset| stdlib::path_at(&document, offset, false))
+        {
+            Some((_, path)) => path,
+            None => return Ok(None),
+        };
+        // Only what follows `module::` is completed from the index.
+        let module_path = match path.rsplit_once("::") {
+            Some((module_path, _)) => module_path,
+            None => return Ok(None),
+        };
+        let module = self.ast_map.get(uri);
+        let resolved = module
+            .as_ref()
+            .and_then(|module| stdlib::resolve(module, module_path));
+        let completions = match resolved {
+            Some(module_path) => {
+                let modules = self.stdlib_index.iter().collect::<Vec<_>>();
+                let modules = modules
+                    .iter()
+                    .map(|entry| (entry.key().as_str(), entry.value().as_slice()));
+                library_completions(modules, &module_path)
+            }
+            None => {
+                let expanded = module.as_ref().map_or_else(
+                    || module_path.to_string(),
+                    |module| stdlib::expand(module, module_path),
+                );
+                if expanded.starts_with("dep::") {
+                    let modules = self.dependency_modules(&position.text_document.uri);
+                    let modules = modules
+                        .iter()
+                        .map(|(path, items)| (path.as_str(), items.as_slice()));
+                    library_completions(modules, &expanded)
+                } else {
+                    self.crate_completions(&position.text_document.uri, module_path)
+                }
+            }
+        };
+        Ok(Some(CompletionResponse::Array(completions)))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = &params.text_document.uri;
+        if !self
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .code_lens
+        {
+            return Ok(None);
+        }
+        self.analyse_edits(uri).await;
+        let document = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.clone(),
+            None => return Ok(None),
+        };
+        let (module, version) = match self.current_tree(uri.as_str()) {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        let encoding = self.encoding();
+        let mut lenses = code_lens::test_lenses(&module, &document, uri, encoding);
+        lenses.extend(code_lens::reference_lenses(
+            &module, &document, uri, encoding,
+        ));
+
+        let project = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Some((self.project_for(&path)?, path)));
+        if let Some((project, path)) = project {
+            if project.kind == PackageKind::Contract {
+                let contracts = contracts::contracts(&document);
+                lenses.extend(code_lens::abi_lenses(&contracts, &document, uri, encoding));
+            } else if path == project.crate_root {
+                // Only the crate root's `main` takes the package's inputs.
+                let has_prover_toml = project.prover_toml().is_file();
+                lenses.extend(code_lens::main_lenses(
+                    &module,
+                    &document,
+                    uri,
+                    has_prover_toml,
+                    encoding,
+                ));
+            }
+        }
+        if self.outdated(uri.as_str(), version) {
+            return Ok(None);
+        }
+        Ok(Some(lenses))
+    }
+
+    async fn code_lens_resolve(&self, lens: CodeLens) -> Result<CodeLens> {
+        let data = match lens
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value::<code_lens::LensData>(data).ok())
+        {
+            Some(data) => data,
+            None => return Ok(lens),
+        };
+        let status = |name: &str| {
+            self.test_statuses
+                .get(&(data.uri.to_string(), name.to_string()))
+                .map(|status| *status)
+        };
+        let references = |name: &str| self.occurrences(&data.uri, name, false, true);
+        let command = code_lens::command(&data, lens.range.start, status, references);
+        Ok(CodeLens {
+            command: Some(command),
+            ..lens
+        })
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = &params.text_document.uri;
+        self.analyse_edits(uri).await;
+        let document = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.clone(),
+            None => return Ok(None),
+        };
+        // Only contract packages may declare contracts.
+        let contracts = match uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) if project.kind == PackageKind::Contract => {
+                contracts::contracts(&document)
+            }
+            _ => Vec::new(),
+        };
+        let (module, version) = match self.current_tree(uri.as_str()) {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        let symbols = outline::document_symbols(&module, &document, &contracts, self.encoding());
+        if self.outdated(uri.as_str(), version) {
+            return Ok(None);
+        }
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        checkpoint().await;
+        self.analyse_edits(&params.text_document.uri).await;
+        self.ensure_stdlib().await;
+        let uri = &params.text_document.uri;
+        let version = self.version_map.get(uri.as_str()).map(|version| *version);
+        let resolve = self.resolve_code_action_edits.load(Ordering::Relaxed);
+        let actions = self
+            .code_actions(&params, None)
+            .into_iter()
+            .map(|(provider, mut action)| {
+                // Leave the edits for `codeAction/resolve`, so only the chosen one is sent
+                // over, and computed again by its provider alone.
+                if resolve && action.edit.take().is_some() {
+                    action.data = serde_json::to_value(CodeActionData {
+                        uri: uri.clone(),
+                        version,
+                        provider: provider.to_string(),
+                        range: params.range,
+                    })
+                    .ok();
+                }
+                CodeActionOrCommand::CodeAction(action)
+            })
+            .collect();
+        Ok(Some(actions))
+    }
+
+    async fn code_action_resolve(&self, mut action: CodeAction) -> Result<CodeAction> {
+        let data = action
+            .data
+            .take()
+            .and_then(|data| serde_json::from_value::<CodeActionData>(data).ok());
+        let data = match data {
+            Some(data) => data,
+            None => return Ok(action),
+        };
+        self.analyse_edits(&data.uri).await;
+        // An edit computed against newer text would land in the wrong places.
+        if self.outdated(data.uri.as_str(), data.version) {
+            return Ok(action);
+        }
+        self.ensure_stdlib().await;
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier::new(data.uri),
+            range: data.range,
+            // Quick fixes find the diagnostic they fix in the request, as when listed.
+            context: CodeActionContext {
+                diagnostics: action.diagnostics.clone().unwrap_or_default(),
+                ..CodeActionContext::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        action.edit = self
+            .code_actions(&params, Some(&data.provider))
+            .into_iter()
+            .find(|(_, resolved)| resolved.title == action.title)
+            .and_then(|(_, resolved)| resolved.edit);
+        Ok(action)
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Clients that answer `workspace/configuration` may send nothing with the change.
+        let config = match self.fetch_config().await {
+            Some(config) => config,
+            None => Config::from_settings(&params.settings),
+        };
+        self.apply_config(config).await;
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        tracing::info!("workspace folders changed");
+        for folder in params.event.removed {
+            if let Ok(path) = folder.uri.to_file_path() {
+                self.workspace_folders.remove(&path);
+                self.projects.retain(|root, _| !root.starts_with(&path));
+                self.loaded_crates.retain(|root| !root.starts_with(&path));
+            }
+        }
+        for folder in params.event.added {
+            if let Ok(path) = folder.uri.to_file_path() {
+                self.add_workspace_folder(&path).await;
+            }
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        tracing::debug!(changes = params.changes.len(), "watched files changed");
+        let (mut deleted, mut created, mut changed) = (Vec::new(), Vec::new(), Vec::new());
+        for change in params.changes {
+            let path = match change.uri.to_file_path() {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            match change.typ {
+                FileChangeType::CREATED => created.push(path),
+                FileChangeType::DELETED => deleted.push(path),
+                _ => changed.push((change.uri, path)),
+            }
+        }
+        let inputs = deleted
+            .iter()
+            .chain(&created)
+            .chain(changed.iter().map(|(_, path)| path))
+            .filter(|path| path.ends_with(prover_toml::PROVER_TOML))
+            .filter_map(|path| self.project_for(path))
+            .map(|project| (project.root.clone(), project))
+            .collect::<HashMap<_, _>>();
+        self.files_moved(&deleted, &created).await;
+
+        let manifests = changed
+            .iter()
+            .filter(|(_, path)| path.ends_with(workspace::MANIFEST))
+            .filter_map(|(_, path)| path.parent().map(Path::to_path_buf))
+            .collect::<HashSet<_>>();
+        for root in manifests {
+            self.reload_project(&root).await;
+        }
+        for (uri, path) in changed {
+            let is_source = path.extension().is_some_and(|ext| ext == "nr");
+            if is_source && !self.vfs.is_open(&path) && self.document_map.contains_key(uri.as_str())
+            {
+                self.reread(uri, &path).await;
+            }
+        }
 
-        match self.client.apply_edit(WorkspaceEdit::default()).await {
-            Ok(res) if res.applied => self.client.log_message(MessageType::INFO, "applied").await,
-            Ok(_) => self.client.log_message(MessageType::INFO, "rejected").await,
-            Err(err) => self.client.log_message(MessageType::ERROR, err).await,
+        // Whether a package has inputs decides which lenses its `main` gets.
+        for project in inputs.values() {
+            self.validate_inputs(project).await;
+        }
+        if !inputs.is_empty() {
+            self.refresh_code_lenses().await;
         }
+    }
+
+    async fn did_create_files(&self, params: CreateFilesParams) {
+        let created = params
+            .files
+            .iter()
+            .filter_map(|file| Url::parse(&file.uri).ok()?.to_file_path().ok())
+            .collect::<Vec<_>>();
+        self.files_moved(&[], &created).await;
+    }
+
+    async fn did_rename_files(&self, params: RenameFilesParams) {
+        let path = |uri: &str| Url::parse(uri).ok()?.to_file_path().ok();
+        let (deleted, created) = params
+            .files
+            .iter()
+            .filter_map(|rename| Some((path(&rename.old_uri)?, path(&rename.new_uri)?)))
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        self.files_moved(&deleted, &created).await;
+    }
+
+    async fn did_delete_files(&self, params: DeleteFilesParams) {
+        let deleted = params
+            .files
+            .iter()
+            .filter_map(|file| Url::parse(&file.uri).ok()?.to_file_path().ok())
+            .collect::<Vec<_>>();
+        self.files_moved(&deleted, &[]).await;
+    }
 
-        Ok(None)
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        self.run_command(&params.command, &params.arguments).await
     }
 }
+
+/// The submodules and items of `module_path` among the library `modules`, keyed by path.
+fn library_completions<'a>(
+    modules: impl Iterator<Item = (&'a str, &'a [StdItem])>,
+    module_path: &str,
+) -> Vec<CompletionItem> {
+    let prefix = format!("{}::", module_path);
+    let mut completions = Vec::new();
+    for (path, items) in modules {
+        if path == module_path {
+            completions.extend(items.iter().map(|item| CompletionItem {
+                label: item.name.to_string(),
+                kind: Some(item.kind),
+                detail: Some(item.signature.clone()),
+                ..CompletionItem::default()
+            }));
+        } else if let Some(name) = path.strip_prefix(&prefix) {
+            if !name.contains("::") {
+                completions.push(CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::MODULE),
+                    ..CompletionItem::default()
+                });
+            }
+        }
+    }
+    completions
+}
+
+/// What `codeAction/resolve` needs to compute an action's edit again: the document and
+/// version it was listed for, the provider that listed it and the range it was asked for.
+/// The action's title tells it from the others of its provider.
+#[derive(Debug, Deserialize, Serialize)]
+struct CodeActionData {
+    uri: Url,
+    version: Option<i32>,
+    provider: String,
+    range: Range,
+}
+
+/// The outcome of one test run through `noir.runTests`, sent as soon as the test finishes.
 #[derive(Debug, Deserialize, Serialize)]
-struct InlayHintParams {
-    path: String,
+struct TestResult {
+    uri: Url,
+    name: String,
+    passed: bool,
+    output: String,
+    /// Where a failed test failed, when nargo says.
+    location: Option<Location>,
+}
+
+enum TestResultNotification {}
+impl Notification for TestResultNotification {
+    type Params = TestResult;
+    const METHOD: &'static str = "noir/testResult";
+}
+
+enum GateReportNotification {}
+impl Notification for GateReportNotification {
+    type Params = circuit_size::GateReport;
+    const METHOD: &'static str = "noir/gateReport";
+}
+
+enum ProfileNotification {}
+impl Notification for ProfileNotification {
+    type Params = circuit_size::Profile;
+    const METHOD: &'static str = "noir/profile";
+}
+
+enum AcirChangedNotification {}
+impl Notification for AcirChangedNotification {
+    type Params = acir::AcirChangedParams;
+    const METHOD: &'static str = acir::ACIR_CHANGED;
+}
+
+enum ServerStatusNotification {}
+impl Notification for ServerStatusNotification {
+    type Params = ServerStatus;
+    const METHOD: &'static str = status::SERVER_STATUS;
 }
 
-enum CustomNotification {}
-impl Notification for CustomNotification {
-    type Params = InlayHintParams;
-    const METHOD: &'static str = "custom/notification";
+enum CompilationStatusNotification {}
+impl Notification for CompilationStatusNotification {
+    type Params = CompilationStatus;
+    const METHOD: &'static str = status::COMPILATION_STATUS;
 }
+
 struct TextDocumentItem {
     uri: Url,
     text: String,
     version: i32,
 }
 impl Backend {
-    async fn on_change(&self, params: TextDocumentItem) {
-        let rope = ropey::Rope::from_str(&params.text);
-        self.document_map
-            .insert(params.uri.to_string(), rope.clone());
+    /// How the columns of the positions exchanged with the client are counted.
+    fn encoding(&self) -> PositionEncoding {
+        if self.utf8_positions.load(Ordering::Relaxed) {
+            PositionEncoding::Utf8
+        } else {
+            PositionEncoding::Utf16
+        }
+    }
 
-        self.ast_map.insert(
-            params.uri.to_string(),
-            ThreadSafe::new(RefCell::new(parse_program(&params.text).0)),
-        );
+    /// The package `path` belongs to, discovering it on first use. With packages nested in
+    /// one another, the innermost one owns the file.
+    fn project_for(&self, path: &Path) -> Option<Project> {
+        let known = self
+            .projects
+            .iter()
+            .filter(|project| project.contains(path))
+            .max_by_key(|project| project.root.components().count())
+            .map(|project| project.clone());
+        if known.is_some() {
+            return known;
+        }
+        let project = Project::discover(path)?;
+        self.projects.insert(project.root.clone(), project.clone());
+        Some(project)
     }
-}
 
-#[tokio::main]
-async fn main() {
-    env_logger::init();
+    /// Forgets everything derived from the files on disk, the packages, their crates and the
+    /// indexes, and builds it all again. Only the open buffers are kept.
+    #[tracing::instrument(skip_all)]
+    async fn reload_workspace(&self) {
+        self.set_status(ServerStatus::Loading).await;
+        let token = self
+            .begin_progress("Reloading the workspace".to_string())
+            .await;
 
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
+        let known = self
+            .document_map
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        let open = known
+            .iter()
+            .filter_map(|uri| {
+                let uri = Url::parse(uri).ok()?;
+                let path = uri.to_file_path().ok()?;
+                let text = self.vfs.is_open(&path).then(|| self.vfs.read(&path))??;
+                let version = self.version_map.get(uri.as_str()).map_or(0, |v| *v);
+                Some(TextDocumentItem { uri, text, version })
+            })
+            .collect::<Vec<_>>();
 
-    let (service, socket) = LspService::build(|client| Backend {
-        client,
-        ast_map: DashMap::new(),
-        document_map: DashMap::new(),
-        // semantic_token_map: DashMap::new(),
-    })
-    .finish();
+        self.ast_map.clear();
+        // The open documents stay, since the editor goes on editing them.
+        self.document_map
+            .retain(|uri, _| open.iter().any(|document| document.uri.as_str() == uri));
+        self.gate_count_map.clear();
+        self.checked_packages.clear();
+        self.inlay_hint_cache.clear();
+        self.compile_diagnostics.clear();
+        self.projects.clear();
+        self.loaded_crates.clear();
+        self.dependency_index.clear();
+        self.indexed_dependencies.clear();
+        self.invalidate_stdlib().await;
+        self.ast_map.interner().collect();
 
-    serde_json::json!({"test": 20});
-    Server::new(stdin, stdout, socket).serve(service).await;
+        let folders = self
+            .workspace_folders
+            .iter()
+            .map(|folder| folder.clone())
+            .collect::<Vec<_>>();
+        for folder in folders {
+            self.add_workspace_folder(&folder).await;
+        }
+        for document in open {
+            self.on_change(document).await;
+        }
+        self.settle_status().await;
+
+        // Files that are gone would otherwise keep their diagnostics.
+        for uri in known {
+            if self.document_map.contains_key(&uri) {
+                continue;
+            }
+            if let Ok(uri) = Url::parse(&uri) {
+                self.client.publish_diagnostics(uri, Vec::new(), None).await;
+            }
+        }
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+    }
+
+    /// Reads the `Nargo.toml` of the package at `root` again after it changed, and rebuilds
+    /// what depends on it: the crate's files and the index of its dependencies.
+    async fn reload_project(&self, root: &Path) {
+        let token = self
+            .begin_progress(format!(
+                "Reloading {}",
+                root.join(workspace::MANIFEST).display()
+            ))
+            .await;
+
+        if let Some((_, previous)) = self.projects.remove(root) {
+            // Dependencies other packages still share stay indexed, the others are indexed
+            // again if the package still depends on them.
+            previous.dependencies.iter().for_each(|dependency| {
+                let shared = self.projects.iter().any(|project| {
+                    project
+                        .dependencies
+                        .iter()
+                        .any(|other| other.root == dependency.root)
+                });
+                if !shared {
+                    self.indexed_dependencies.remove(&dependency.root);
+                    self.dependency_index
+                        .retain(|(root, _), _| root != &dependency.root);
+                }
+            });
+        }
+        let was_loaded = self.loaded_crates.remove(root).is_some();
+        // A deleted manifest leaves the files to whichever package encloses them, if any.
+        match Project::discover(root).filter(|project| project.root == root) {
+            Some(project) => {
+                tracing::info!("reloaded package {}", project);
+                self.projects.insert(project.root.clone(), project.clone());
+                if was_loaded {
+                    self.loaded_crates.insert(project.root.clone());
+                    self.load_crate(&project).await;
+                }
+            }
+            None => {
+                tracing::info!("package at {} removed", root.display());
+            }
+        }
+
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+    }
+
+    /// Catches up with files and folders created, deleted or renamed, which is both, outside
+    /// the editor's buffers: packages whose manifest came or went are reloaded, the analysis of
+    /// deleted sources dropped along with their diagnostics, and loaded crates gaining a source
+    /// loaded again to take it in.
+    async fn files_moved(&self, deleted: &[PathBuf], created: &[PathBuf]) {
+        let manifests = deleted
+            .iter()
+            .chain(created)
+            .filter(|path| path.ends_with(workspace::MANIFEST))
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect::<HashSet<_>>();
+        for root in &manifests {
+            self.reload_project(root).await;
+        }
+
+        let gone = self
+            .document_map
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|uri| {
+                Url::parse(uri)
+                    .ok()
+                    .and_then(|uri| uri.to_file_path().ok())
+                    .is_some_and(|path| {
+                        !self.vfs.is_open(&path)
+                            && deleted.iter().any(|deleted| path.starts_with(deleted))
+                    })
+            })
+            .collect::<Vec<_>>();
+        for uri in gone {
+            self.forget(&uri);
+            self.compile_diagnostics.remove(&uri);
+            if let Ok(uri) = Url::parse(&uri) {
+                self.client.publish_diagnostics(uri, Vec::new(), None).await;
+            }
+        }
+
+        let mut reloaded = HashSet::new();
+        for path in created {
+            let project = match self.project_for(path) {
+                Some(project) => project,
+                None => continue,
+            };
+            if manifests.contains(&project.root)
+                || !self.loaded_crates.contains(&project.root)
+                || !reloaded.insert(project.root.clone())
+            {
+                continue;
+            }
+            self.load_crate(&project).await;
+        }
+        self.evict_closed_files();
+    }
+
+    /// Analyses the closed file at `path` again after it changed on disk.
+    async fn reread(&self, uri: Url, path: &Path) {
+        let text = match self.vfs.read(path) {
+            Some(text) => text,
+            None => return,
+        };
+        self.document_map
+            .insert(uri.to_string(), Rope::from_str(&text));
+        self.inlay_hint_cache.remove(uri.as_str());
+        match self.ast_map.set_text(uri.as_str(), text, None).await {
+            Some(true) => {
+                if let Some(module) = self.ast_map.get(uri.as_str()) {
+                    self.estimate_opcodes(&uri, &module);
+                }
+            }
+            Some(false) => {
+                self.gate_count_map.remove(uri.as_str());
+            }
+            None => return,
+        }
+        let mut diagnostics = self.local_diagnostics(&uri).await;
+        diagnostics.extend(self.compiled_diagnostics(uri.as_str()));
+        self.client
+            .publish_diagnostics(uri, diagnostics, None)
+            .await;
+    }
+
+    /// Tells the client what the server is up to, unless it already knows.
+    async fn set_status(&self, status: ServerStatus) {
+        {
+            let mut current = self.status.write().unwrap_or_else(PoisonError::into_inner);
+            if *current == status {
+                return;
+            }
+            *current = status.clone();
+        }
+        self.client
+            .send_notification::<ServerStatusNotification>(status)
+            .await;
+    }
+
+    /// Sets the status back to what it is between operations: an error while there is no
+    /// nargo, failed compilation while `nargo check` reported errors, and otherwise ready.
+    async fn settle_status(&self) {
+        let status = if self
+            .nargo
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none()
+        {
+            ServerStatus::Error {
+                message: "nargo not found".to_string(),
+            }
+        } else {
+            let errors = self
+                .compile_diagnostics
+                .iter()
+                .flat_map(|entry| entry.value().clone())
+                .filter(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR))
+                .count();
+            if errors > 0 {
+                ServerStatus::CompileFailed { errors }
+            } else {
+                ServerStatus::Ready
+            }
+        };
+        self.set_status(status).await;
+    }
+
+    /// Shows a progress indicator titled `title` in the editor, if the client supports them
+    /// and agrees to one.
+    async fn begin_progress(&self, title: String) -> Option<NumberOrString> {
+        if !self.work_done_progress_support.load(Ordering::Relaxed) {
+            return None;
+        }
+        // Unique even when several operations of the same title run at once.
+        let id = self.progress_tokens.fetch_add(1, Ordering::Relaxed);
+        let token = NumberOrString::String(format!("noir/{}", id));
+        self.client
+            .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .ok()?;
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(WorkDoneProgress::Begin(
+                    WorkDoneProgressBegin {
+                        title,
+                        ..WorkDoneProgressBegin::default()
+                    },
+                )),
+            })
+            .await;
+        Some(token)
+    }
+
+    /// Tells the editor how far the operation behind `token` has got.
+    async fn report_progress(&self, token: &NumberOrString, message: String, percentage: u32) {
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(WorkDoneProgress::Report(
+                    WorkDoneProgressReport {
+                        message: Some(message),
+                        percentage: Some(percentage),
+                        ..WorkDoneProgressReport::default()
+                    },
+                )),
+            })
+            .await;
+    }
+
+    async fn end_progress(&self, token: NumberOrString) {
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token,
+                value: ProgressParamsValue::WorkDone(WorkDoneProgress::End(
+                    WorkDoneProgressEnd::default(),
+                )),
+            })
+            .await;
+    }
+
+    /// The nargo binary to run. Without one found, `nargo` is left for the OS to look up,
+    /// and fails the same way.
+    fn nargo(&self) -> PathBuf {
+        self.nargo
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("nargo"))
+    }
+
+    /// The nargo binary to run on `project`: the one of the toolchain version its toolchain
+    /// file pins, if it has one and neither the `nargoPath` nor the `toolchain` setting
+    /// says otherwise, and `nargo()` else.
+    async fn project_nargo(&self, project: &Project) -> PathBuf {
+        let overridden = {
+            let config = self.config.read().unwrap_or_else(PoisonError::into_inner);
+            config.nargo_path.is_some() || config.toolchain.is_some()
+        };
+        let pinned = match workspace::pinned_toolchain(&project.root) {
+            Some(pinned) if !overridden => pinned,
+            _ => return self.nargo(),
+        };
+        // Read again each time, so an edited toolchain file takes effect on the next run.
+        if let Some(located) = self.pinned_nargo.get(&project.root) {
+            if located.0 == pinned {
+                return located.1.clone().unwrap_or_else(|| self.nargo());
+            }
+        }
+        let located = nargo::locate(None, Some(&pinned)).await;
+        if located.is_none() {
+            let message = format!(
+                "nargo {} pinned by {} not found. Install it with `noirup -v {}`.",
+                pinned, project, pinned
+            );
+            self.client
+                .show_message(MessageType::WARNING, message)
+                .await;
+        }
+        self.pinned_nargo
+            .insert(project.root.clone(), (pinned, located.clone()));
+        located.unwrap_or_else(|| self.nargo())
+    }
+
+    /// Looks for nargo, of the toolchain version the `toolchain` setting pins if there is
+    /// one, and warns when its version isn't the one of the frontend the server is built
+    /// with, since the two would then disagree on what compiles. Packages pinning a version
+    /// of their own get theirs from `project_nargo`.
+    async fn find_nargo(&self) {
+        let (configured, pinned) = {
+            let config = self.config.read().unwrap_or_else(PoisonError::into_inner);
+            (config.nargo_path.clone(), config.toolchain.clone())
+        };
+        self.pinned_nargo.clear();
+        let nargo = nargo::locate(configured.as_deref(), pinned.as_deref()).await;
+        *self.nargo.write().unwrap_or_else(PoisonError::into_inner) = nargo.clone();
+        let nargo = match nargo {
+            Some(nargo) => nargo,
+            None => {
+                let message = match pinned {
+                    Some(pinned) => format!(
+                        "nargo {} not found. Install it with `noirup -v {}`.",
+                        pinned, pinned
+                    ),
+                    None => {
+                        "nargo not found. Install it with noirup or set `nargoPath`.".to_string()
+                    }
+                };
+                self.client
+                    .show_message(MessageType::WARNING, message)
+                    .await;
+                return;
+            }
+        };
+
+        let version = nargo::version(&nargo).await;
+        tracing::info!(
+            "using nargo {} at {}",
+            version.as_deref().unwrap_or("of unknown version"),
+            nargo.display()
+        );
+        if let (Some(version), Some(frontend)) = (version, nargo::frontend_version()) {
+            if version != frontend {
+                self.client
+                    .show_message(
+                        MessageType::WARNING,
+                        format!(
+                            "nargo {} differs from the noir {} this server was built with; \
+                             diagnostics may not match what nargo reports.",
+                            version, frontend
+                        ),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    /// The settings the client has now, asked for with `workspace/configuration` when it
+    /// supports it.
+    async fn fetch_config(&self) -> Option<Config> {
+        if !self.configuration_support.load(Ordering::Relaxed) {
+            return None;
+        }
+        let item = ConfigurationItem {
+            scope_uri: None,
+            section: Some(config::SECTION.to_string()),
+        };
+        let settings = self.client.configuration(vec![item]).await.ok()?;
+        Some(Config::from_settings(settings.first()?))
+    }
+
+    /// Replaces the configuration every subsystem reads, and redoes what depends on the
+    /// settings that changed.
+    async fn apply_config(&self, config: Config) {
+        let (nargo_changed, diagnostics_changed) = {
+            let previous = self.config.read().unwrap_or_else(PoisonError::into_inner);
+            (
+                config.nargo_path != previous.nargo_path || config.toolchain != previous.toolchain,
+                config.diagnostics != previous.diagnostics,
+            )
+        };
+        telemetry::set_enabled(config.telemetry);
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = config;
+        self.inlay_hint_cache.clear();
+        self.evict_closed_files();
+        tracing::info!("configuration changed");
+        if diagnostics_changed {
+            self.publish_all_diagnostics().await;
+        }
+        if nargo_changed {
+            self.find_nargo().await;
+            self.invalidate_stdlib().await;
+            self.settle_status().await;
+        }
+    }
+
+    /// Publishes the diagnostics of every document again, as after the settings changed.
+    async fn publish_all_diagnostics(&self) {
+        let uris = self
+            .document_map
+            .iter()
+            .filter_map(|entry| Url::parse(entry.key()).ok())
+            .collect::<Vec<_>>();
+        for uri in uris {
+            let mut diagnostics = self.local_diagnostics(&uri).await;
+            diagnostics.extend(self.compiled_diagnostics(uri.as_str()));
+            let version = self.version_map.get(uri.as_str()).map(|v| *v);
+            self.client
+                .publish_diagnostics(uri, diagnostics, version)
+                .await;
+        }
+    }
+
+    /// Indexes the standard library unless it already is, as after the warm-up, and again
+    /// after the toolchain changed. Requests coming in meanwhile wait for that one index.
+    async fn ensure_stdlib(&self) {
+        let mut indexed = self.stdlib_indexed.lock().await;
+        if !*indexed {
+            self.index_stdlib().await;
+            *indexed = true;
+        }
+    }
+
+    /// Indexes the standard library and loads every package of the workspace as soon as the
+    /// client is ready, each behind its own progress, rather than when the first request
+    /// needs them and has to wait seconds for it.
+    async fn warm_up(&self) {
+        self.ensure_stdlib().await;
+        let projects = self
+            .projects
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect::<Vec<_>>();
+        for project in projects {
+            if self.loaded_crates.insert(project.root.clone()) {
+                self.load_crate(&project).await;
+            }
+        }
+        self.evict_closed_files();
+    }
+
+    /// Makes the next request needing the standard library index it again.
+    async fn invalidate_stdlib(&self) {
+        *self.stdlib_indexed.lock().await = false;
+    }
+
+    /// Parses the standard library of the toolchain in use, or the bundled one, and replaces
+    /// the index of the previous one.
+    #[tracing::instrument(skip_all)]
+    async fn index_stdlib(&self) {
+        self.set_status(ServerStatus::Indexing).await;
+        let token = self
+            .begin_progress("Indexing the standard library".to_string())
+            .await;
+        let nargo = self
+            .nargo
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        // Parsing the whole standard library takes a while, so keep it off the async workers,
+        // and only do it when the cache has no index of the same sources.
+        // The items' locations depend on the encoding, so it is part of what is cached.
+        let encoding = self.encoding();
+        let kind = format!("stdlib-{}", encoding.kind().as_str());
+        let modules = tokio::task::spawn_blocking(move || {
+            let root = stdlib::locate(nargo.as_deref())?;
+            let modules = index_cache::cached(&kind, &root, || stdlib::index(&root, encoding));
+            Some((modules, root))
+        })
+        .await;
+        match modules {
+            Ok(Some((modules, root))) => {
+                self.stdlib_index.clear();
+                let interner = self.ast_map.interner();
+                for (module_path, mut items) in modules {
+                    for item in &mut items {
+                        item.name = interner.intern(&item.name);
+                    }
+                    self.stdlib_index.insert(module_path, items);
+                }
+                interner.collect();
+                tracing::info!("indexed the standard library at {}", root.display());
+            }
+            _ => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        "standard library not found, set NOIR_STDLIB to its location",
+                    )
+                    .await;
+            }
+        }
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        self.settle_status().await;
+    }
+
+    /// Registers the package a workspace folder is in, or every package inside it when the
+    /// folder holds several.
+    async fn add_workspace_folder(&self, folder: &Path) {
+        self.workspace_folders.insert(folder.to_path_buf());
+        let mut packages = workspace::find_packages(folder);
+        if packages.is_empty() {
+            packages.extend(Project::discover(folder));
+        }
+        for project in packages {
+            tracing::info!("found package {}", project);
+            self.projects.insert(project.root.clone(), project);
+        }
+    }
+
+    /// The standard library or dependency item the path under `position` refers to, and the
+    /// range of the path's last segment. The path is read from the current text and resolved
+    /// through the imports of the last tree, without waiting for pending edits to be analysed.
+    fn library_item_at(&self, uri: &Url, position: Position) -> Option<(StdItem, Range)> {
+        let document = self.document_map.get(uri.as_str())?.clone();
+        let offset = position_to_offset(position, &document, self.encoding())?;
+        let (path_start, path) = stdlib::path_at(&document, offset, true)?;
+        let module = self.ast_map.get(uri.as_str())?;
+        let (full, items) = match stdlib::resolve(&module, &path) {
+            Some(full) => {
+                let items = full
+                    .rsplit_once("::")
+                    .and_then(|(module_path, _)| self.stdlib_index.get(module_path))
+                    .map(|items| items.clone());
+                (full, items)
+            }
+            None => {
+                let full = stdlib::expand(&module, &path);
+                let items = full
+                    .rsplit_once("::")
+                    .and_then(|(module_path, _)| self.dependency_modules(uri).remove(module_path));
+                (full, items)
+            }
+        };
+        let name = full.rsplit_once("::")?.1;
+        let item = items?.into_iter().find(|item| item.name.as_ref() == name)?;
+
+        let path_end = path_start + path.chars().count();
+        let name_start = path_end - name.chars().count();
+        let range = Range::new(
+            offset_to_position(name_start, &document, self.encoding())?,
+            offset_to_position(path_end, &document, self.encoding())?,
+        );
+        Some((item, range))
+    }
+
+    /// The name the cursor at `position` in the document at `uri` is on, where it is declared
+    /// or referred to, as of the last tree.
+    fn name_at(&self, uri: &Url, position: Position) -> Option<Arc<str>> {
+        let document = self.document_map.get(uri.as_str())?.clone();
+        let byte = position_to_byte(position, &document, self.encoding())?;
+        let file = self.ast_map.symbols().file(uri.as_str())?;
+        file.name_at(byte).cloned()
+    }
+
+    /// The files of the package the document at `uri` is in that declare or refer to `name`.
+    fn package_files_using(&self, uri: &Url, name: &str) -> Vec<(Url, Arc<FileSymbols>)> {
+        let root = |uri: &Url| {
+            let path = uri.to_file_path().ok()?;
+            Some(self.project_for(&path)?.root)
+        };
+        let package = match root(uri) {
+            Some(package) => package,
+            None => return Vec::new(),
+        };
+        self.ast_map
+            .symbols()
+            .files_using(name)
+            .into_iter()
+            .filter_map(|(key, file)| {
+                let uri = Url::parse(&key).ok()?;
+                (root(&uri)? == package).then_some((uri, file))
+            })
+            .collect()
+    }
+
+    /// The edits to the package of the file at `old` that follow its module to `new`: paths
+    /// through it, and its declaration in the parent module, moved to the new parent if the
+    /// file changes directory.
+    async fn module_rename_edits(
+        &self,
+        old: &Url,
+        new: &Url,
+        changes: &mut HashMap<Url, Vec<TextEdit>>,
+    ) {
+        let (from, to) = match (utils::module_path(old), utils::module_path(new)) {
+            (Some(from), Some(to)) if from != to => (from, to),
+            _ => return,
+        };
+        let package = |uri: &Url| {
+            let path = uri.to_file_path().ok()?;
+            self.project_for(&path)
+        };
+        let project = match (package(old), package(new)) {
+            (Some(project), Some(other)) if project.root == other.root => project,
+            _ => return,
+        };
+        if self.loaded_crates.insert(project.root.clone()) {
+            self.load_crate(&project).await;
+        }
+
+        let from = from.split("::").skip(1).collect::<Vec<_>>();
+        let to = to.split("::").skip(1).collect::<Vec<_>>();
+        let (old_name, new_name) = match (from.last(), to.last()) {
+            (Some(old_name), Some(new_name)) => (*old_name, *new_name),
+            _ => return,
+        };
+        let parent = |segments: &[&str]| {
+            std::iter::once("crate")
+                .chain(segments[..segments.len() - 1].iter().copied())
+                .collect::<Vec<_>>()
+                .join("::")
+        };
+        let (old_parent, new_parent) = (parent(&from), parent(&to));
+
+        for (key, module) in self.ast_map.snapshot() {
+            let uri = match Url::parse(&key) {
+                Ok(uri) if package(&uri).is_some_and(|other| other.root == project.root) => uri,
+                _ => continue,
+            };
+            let document = match self.document_map.get(uri.as_str()) {
+                Some(rope) => rope.clone(),
+                None => continue,
+            };
+            let module_path = utils::module_path(&uri);
+            let crate_root = module_path.as_deref() == Some("crate");
+            let mut edits = file_operations::path_edits(
+                &module,
+                &document,
+                &from,
+                &to,
+                crate_root,
+                self.encoding(),
+            );
+            if module_path.as_deref() == Some(old_parent.as_str()) {
+                if old_parent == new_parent {
+                    edits.extend(file_operations::rename_declaration(
+                        &module,
+                        &document,
+                        old_name,
+                        new_name,
+                        self.encoding(),
+                    ));
+                } else {
+                    edits.extend(file_operations::remove_declaration(
+                        &module, &document, old_name,
+                    ));
+                }
+            } else if module_path.as_deref() == Some(new_parent.as_str()) {
+                edits.push(file_operations::add_declaration(
+                    &module, &document, new_name,
+                ));
+            }
+            if !edits.is_empty() {
+                changes.entry(uri).or_default().extend(edits);
+            }
+        }
+    }
+
+    /// Where the package of the document at `uri` declares the items named `name`, with
+    /// `declarations`, and where it refers to them, with `references`.
+    fn occurrences(
+        &self,
+        uri: &Url,
+        name: &str,
+        declarations: bool,
+        references: bool,
+    ) -> Vec<Location> {
+        self.package_files_using(uri, name)
+            .into_iter()
+            .flat_map(|(uri, file)| {
+                let document = match self.document_map.get(uri.as_str()) {
+                    Some(rope) => rope.clone(),
+                    None => return Vec::new(),
+                };
+                let declared = file
+                    .definitions
+                    .iter()
+                    .filter(|symbol| declarations && *symbol.name == *name)
+                    .map(|symbol| symbol.span);
+                let used = file
+                    .references
+                    .iter()
+                    .filter(|occurrence| references && *occurrence.name == *name)
+                    .map(|occurrence| occurrence.span);
+                declared
+                    .chain(used)
+                    .filter_map(|(start, end)| {
+                        Some(Location {
+                            uri: uri.clone(),
+                            range: span_range(start, end, &document, self.encoding())?,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// The modules of the dependencies of the package the document at `uri` is in, keyed by
+    /// the path it imports them by, such as `dep::merkle::tree`, with their items.
+    fn dependency_modules(&self, uri: &Url) -> HashMap<String, Vec<StdItem>> {
+        let project = match uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return HashMap::new(),
+        };
+        let mut modules = HashMap::new();
+        for entry in self.dependency_index.iter() {
+            let (root, module_path) = entry.key();
+            for dependency in project.dependencies.iter().filter(|d| &d.root == root) {
+                let import_path =
+                    module_path.replacen("crate", &format!("dep::{}", dependency.name), 1);
+                modules.insert(import_path, entry.value().clone());
+            }
+        }
+        modules
+    }
+
+    /// The items and submodules of `module_path`, a path of the package of the document at
+    /// `uri` such as `crate::merkle`.
+    fn crate_completions(&self, uri: &Url, module_path: &str) -> Vec<CompletionItem> {
+        if module_path != "crate" && !module_path.starts_with("crate::") {
+            return Vec::new();
+        }
+        let root = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+            .map(|project| project.root);
+        let mut completions = Vec::new();
+        let mut submodules = HashSet::new();
+        for (key, file) in self.ast_map.symbols().files() {
+            let in_package = Url::parse(&key)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+                .and_then(|path| self.project_for(&path))
+                .map(|project| project.root);
+            let path = match (&file.module_path, in_package == root) {
+                (Some(path), true) => path,
+                _ => continue,
+            };
+            if path == module_path {
+                completions.extend(
+                    file.definitions
+                        .iter()
+                        .filter(|symbol| symbol.kind != SymbolKind::METHOD)
+                        .map(|symbol| CompletionItem {
+                            label: symbol.name.to_string(),
+                            kind: Some(completion_kind(symbol.kind)),
+                            ..CompletionItem::default()
+                        }),
+                );
+            } else if let Some(rest) = path.strip_prefix(&format!("{}::", module_path)) {
+                submodules.extend(rest.split("::").next().map(str::to_string));
+            }
+        }
+        completions.extend(submodules.into_iter().map(|name| CompletionItem {
+            label: name,
+            kind: Some(CompletionItemKind::MODULE),
+            ..CompletionItem::default()
+        }));
+        completions
+    }
+
+    /// Every code action on offer for `params`, edits included, with the provider that
+    /// offers it, or only those of the provider `only`.
+    fn code_actions(
+        &self,
+        params: &CodeActionParams,
+        only: Option<&str>,
+    ) -> Vec<(&'static str, CodeAction)> {
+        let uri = &params.text_document.uri;
+        let document = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.clone(),
+            None => return Vec::new(),
+        };
+
+        let crate_files = self
+            .crate_documents(uri)
+            .into_iter()
+            .filter(|(other, _, _)| other != uri)
+            .collect::<Vec<_>>();
+
+        let (module, version) = match self.current_tree(uri.as_str()) {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let ctx = code_actions::ActionContext {
+            params,
+            uri,
+            document: &document,
+            module: &module,
+            crate_files: &crate_files,
+            annotate_changes: self.change_annotation_support.load(Ordering::Relaxed),
+            encoding: self.encoding(),
+        };
+
+        let mut actions = Vec::new();
+        for provider in code_actions::PROVIDERS {
+            if only.is_some_and(|only| only != provider) {
+                continue;
+            }
+            let provided = code_actions::provide(provider, &ctx, || self.import_definitions(uri));
+            actions.extend(provided.into_iter().map(|action| (provider, action)));
+        }
+        if self.outdated(uri.as_str(), version) {
+            return Vec::new();
+        }
+        actions
+    }
+
+    /// Top-level names of every analysed file other than the one at `uri`, of the
+    /// dependencies and of the standard library, keyed by the path to import them by.
+    fn import_definitions(&self, uri: &Url) -> HashMap<String, Vec<Arc<str>>> {
+        let mut definitions: HashMap<String, Vec<Arc<str>>> = HashMap::new();
+        for (key, file) in self.ast_map.symbols().files() {
+            if key == uri.as_str() {
+                continue;
+            }
+            if let Some(module_path) = &file.module_path {
+                definitions.entry(module_path.clone()).or_default().extend(
+                    file.definitions
+                        .iter()
+                        .filter(|symbol| symbol.kind != SymbolKind::METHOD)
+                        .map(|symbol| Arc::clone(&symbol.name)),
+                );
+            }
+        }
+
+        for (module_path, items) in self.dependency_modules(uri) {
+            definitions
+                .entry(module_path)
+                .or_default()
+                .extend(items.iter().map(|item| item.name.clone()));
+        }
+        self.stdlib_index.iter().for_each(|entry| {
+            definitions
+                .entry(format!("dep::{}", entry.key()))
+                .or_default()
+                .extend(entry.value().iter().map(|item| item.name.clone()));
+        });
+        definitions
+    }
+
+    /// The edit a `Prover.toml` command makes, for the package of the document at `uri`.
+    fn prover_toml_edit(&self, command: &str, uri: &Url) -> Option<WorkspaceEdit> {
+        let document = self.document_map.get(uri.as_str())?.clone();
+        let module = self.ast_map.get(uri.as_str())?;
+        let main = module
+            .functions
+            .iter()
+            .find(|func| func.def.name.0.contents == "main")?;
+
+        let path = self.project_for(&uri.to_file_path().ok()?)?.prover_toml();
+        let toml_uri = Url::from_file_path(&path).ok()?;
+        let toml = self.vfs.read(&path);
+        match command {
+            prover_toml::GENERATE_PROVER_TOML => prover_toml::fill_prover_toml(
+                main,
+                &module.types,
+                toml_uri,
+                toml.as_deref(),
+                self.encoding(),
+            ),
+            prover_toml::ADD_MAIN_PARAMETERS => prover_toml::add_main_parameters(
+                main,
+                &document,
+                uri.clone(),
+                &toml?,
+                self.encoding(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Parses every file of `project`'s crate that isn't open, so the items of other modules
+    /// are known before their files are opened.
+    #[tracing::instrument(skip_all, fields(package = %project))]
+    async fn load_crate(&self, project: &Project) {
+        self.set_status(ServerStatus::Indexing).await;
+        let token = self.begin_progress(format!("Loading {}", project)).await;
+        let files = self.parse_crate(project).await;
+        let steps = files.len() + project.dependencies.len();
+        let mut loaded = Vec::new();
+        for (i, (path, text, module)) in files.into_iter().enumerate() {
+            if let Some(token) = &token {
+                let relative = path.strip_prefix(&project.root).unwrap_or(&path);
+                let message = relative.display().to_string();
+                self.report_progress(token, message, percentage(i, steps))
+                    .await;
+            }
+            let uri = match Url::from_file_path(&path) {
+                Ok(uri) => uri,
+                Err(_) => continue,
+            };
+            if self.vfs.is_open(&path) {
+                continue;
+            }
+            self.document_map
+                .insert(uri.to_string(), Rope::from_str(&text));
+            self.ast_map.insert(uri.to_string(), &text, module);
+            loaded.push(uri);
+        }
+        // Only once every file is in, since whether an item is used depends on the others.
+        for uri in loaded {
+            let mut diagnostics = self.local_diagnostics(&uri).await;
+            diagnostics.extend(self.compiled_diagnostics(uri.as_str()));
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
+
+        if !project.dependencies.is_empty() {
+            if let Some(token) = &token {
+                let parsed = steps - project.dependencies.len();
+                let message = format!("{} dependencies", project.dependencies.len());
+                self.report_progress(token, message, percentage(parsed, steps))
+                    .await;
+            }
+            // Each dependency is parsed on a blocking thread of its own, so they are indexed
+            // side by side.
+            futures::future::join_all(
+                project
+                    .dependencies
+                    .iter()
+                    .map(|dependency| self.index_dependency(dependency)),
+            )
+            .await;
+        }
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        self.settle_status().await;
+    }
+
+    /// `noir/listTests`: every test of the package the document belongs to.
+    async fn list_tests(&self, params: ListTestsParams) -> Result<Vec<TestItem>> {
+        let project = params
+            .text_document
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path));
+        Ok(match project {
+            Some(project) => self.package_tests(&project).await,
+            None => Vec::new(),
+        })
+    }
+
+    /// `noir/runTests`: runs tests of the document's package and reports how each went.
+    async fn run_test_suite(&self, params: RunTestsParams) -> Result<Vec<TestOutcome>> {
+        let project = match params
+            .text_document
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return Ok(Vec::new()),
+        };
+        let tests = self
+            .package_tests(&project)
+            .await
+            .into_iter()
+            .filter(|test| {
+                params
+                    .names
+                    .as_ref()
+                    .map_or(true, |names| names.contains(&test.name))
+            })
+            .collect::<Vec<_>>();
+
+        let nargo = self.project_nargo(&project).await;
+        let token = self.begin_progress(format!("nargo test {}", project)).await;
+        let mut outcomes = Vec::new();
+        for test in tests {
+            let (status, message, location) =
+                match nargo::test(&nargo, &project.root, &test.name).await {
+                    Some((true, _)) => (TestStatus::Passed, None, None),
+                    Some((false, output)) => (
+                        TestStatus::Failed,
+                        Some(test_explorer::failure_message(&output)),
+                        test_explorer::failure_location(&output, &project.root),
+                    ),
+                    None => (
+                        TestStatus::Errored,
+                        Some("could not run `nargo test`".to_string()),
+                        None,
+                    ),
+                };
+            self.test_statuses
+                .insert((test.uri.to_string(), test.name.clone()), status);
+            outcomes.push(TestOutcome {
+                test,
+                status,
+                message,
+                location,
+            });
+        }
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        Ok(outcomes)
+    }
+
+    /// Runs `main` of the package of the document at `uri` on the inputs in `Prover.toml`.
+    async fn execute(&self, uri: &Url) -> Option<prover_toml::Execution> {
+        let project = self.project_for(&uri.to_file_path().ok()?)?;
+        let toml = self.vfs.read(&project.prover_toml()).unwrap_or_default();
+        let public_inputs = {
+            let (module, _) = parse_program(&self.vfs.read(&project.crate_root)?);
+            let main = module
+                .functions
+                .iter()
+                .find(|func| func.def.name.0.contents == "main")?;
+            prover_toml::public_inputs(main, &toml)
+        };
+
+        let token = self
+            .begin_progress(format!("nargo execute {}", project))
+            .await;
+        let nargo = self.project_nargo(&project).await;
+        let result = nargo::execute(&nargo, &project.root).await;
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        let (return_value, error) = match result {
+            Some(Ok(return_value)) => (return_value, None),
+            Some(Err(output)) => (None, Some(output.trim().to_string())),
+            None => (None, Some("could not run `nargo execute`".to_string())),
+        };
+        Some(prover_toml::Execution {
+            return_value,
+            public_inputs,
+            error,
+        })
+    }
+
+    /// The ABI of `function` of `contract` in the package of the document at `uri`. Failures
+    /// to compile are shown to the user.
+    async fn show_abi(&self, uri: &Url, contract: &str, function: &str) -> Option<Value> {
+        let project = self.project_for(&uri.to_file_path().ok()?)?;
+        let token = self
+            .begin_progress(format!("nargo compile {}", project))
+            .await;
+        let nargo = self.project_nargo(&project).await;
+        let abi = nargo::contract_abi(&nargo, &project.root, contract, function).await;
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        match abi {
+            Some(Ok(abi)) => Some(abi),
+            Some(Err(error)) => {
+                self.client
+                    .show_message(MessageType::ERROR, error.trim())
+                    .await;
+                None
+            }
+            None => {
+                self.client
+                    .log_message(MessageType::ERROR, "could not run `nargo compile`")
+                    .await;
+                None
+            }
+        }
+    }
+
+    /// Proves `main` of the package of the document at `uri` for the inputs in `Prover.toml`.
+    async fn prove(&self, uri: &Url) -> Option<prover_toml::Proof> {
+        let project = self.project_for(&uri.to_file_path().ok()?)?;
+        let token = self
+            .begin_progress(format!("nargo prove {}", project))
+            .await;
+        let nargo = self.project_nargo(&project).await;
+        let result = nargo::prove(&nargo, &project.root).await;
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        let proof = |name: &str| {
+            let path = project.root.join("proofs").join(format!("{}.proof", name));
+            Url::from_file_path(path).ok()
+        };
+        Some(match result {
+            Some(Ok(())) => prover_toml::Proof {
+                uri: project.name.as_deref().and_then(proof),
+                error: None,
+            },
+            Some(Err(output)) => prover_toml::Proof {
+                uri: None,
+                error: Some(output.trim().to_string()),
+            },
+            None => prover_toml::Proof {
+                uri: None,
+                error: Some("could not run `nargo prove`".to_string()),
+            },
+        })
+    }
+
+    /// `$/setTrace`: how much of the server's log to send the client as `$/logTrace`.
+    async fn set_trace(&self, params: SetTraceParams) {
+        trace::set(params.value);
+    }
+
+    /// `noir/status`: how long the server has run, what it has indexed and what is left.
+    async fn status_report(&self) -> Result<StatusReport> {
+        let (indexed_files, indexed_symbols) = self.ast_map.symbols().counts();
+        Ok(StatusReport {
+            uptime_seconds: self.started.elapsed().as_secs(),
+            server: self
+                .status
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            open_documents: self.vfs.open_count(),
+            indexed_files,
+            indexed_symbols,
+            pending_analyses: self.unanalysed.len(),
+            last_compile: self
+                .last_compile
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        })
+    }
+
+    /// `noir/memoryUsage`: what the documents, syntax trees and indexes the server keeps take,
+    /// by package.
+    async fn memory_usage(&self) -> Result<memory::MemoryUsage> {
+        let documents = self
+            .document_map
+            .iter()
+            .map(|entry| (entry.key().clone(), memory::rope_bytes(entry.value())))
+            .collect::<Vec<_>>();
+        let mut crates: HashMap<Option<PathBuf>, memory::CrateMemory> = HashMap::new();
+        for (uri, bytes) in documents {
+            let root = Url::parse(&uri)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+                .and_then(|path| self.project_for(&path))
+                .map(|project| project.root);
+            let usage = crates
+                .entry(root.clone())
+                .or_insert_with(|| memory::CrateMemory {
+                    root,
+                    ..memory::CrateMemory::default()
+                });
+            usage.files += 1;
+            usage.documents += bytes;
+            usage.syntax_trees += self
+                .ast_map
+                .syntax_tree_bytes(&uri)
+                .map_or(0, |bytes| *bytes);
+        }
+        let mut crates = crates.into_values().collect::<Vec<_>>();
+        crates.sort_by_key(|usage| std::cmp::Reverse(usage.total()));
+
+        let stdlib_index = self
+            .stdlib_index
+            .iter()
+            .map(|entry| memory::library_module_bytes(entry.key(), entry.value()))
+            .sum::<usize>();
+        let dependency_index = self
+            .dependency_index
+            .iter()
+            .map(|entry| memory::library_module_bytes(&entry.key().1, entry.value()))
+            .sum::<usize>();
+        let names = self.ast_map.interner().bytes();
+        Ok(memory::MemoryUsage {
+            total: crates.iter().map(memory::CrateMemory::total).sum::<usize>()
+                + stdlib_index
+                + dependency_index
+                + names,
+            crates,
+            stdlib_index,
+            dependency_index,
+            names,
+        })
+    }
+
+    /// `noir/viewAcir`: compiles the document's package and returns its ACIR as text.
+    async fn view_acir(&self, params: acir::ViewAcirParams) -> Result<Option<acir::AcirDocument>> {
+        let project = match params
+            .text_document
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return Ok(None),
+        };
+        let uri = match acir::acir_uri(&project) {
+            Some(uri) => uri,
+            None => return Ok(None),
+        };
+
+        let token = self
+            .begin_progress(format!("nargo compile {}", project))
+            .await;
+        let nargo = self.project_nargo(&project).await;
+        let acir = nargo::print_acir(&nargo, &project.root).await;
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        let text = match acir {
+            Some(Ok(acir)) => acir,
+            Some(Err(errors)) => format!("// {} failed to compile\n\n{}", project, errors),
+            None => {
+                self.client
+                    .log_message(MessageType::ERROR, "could not run `nargo compile`")
+                    .await;
+                return Ok(None);
+            }
+        };
+        self.acir_views.insert(project.root.clone());
+        Ok(Some(acir::AcirDocument { uri, text }))
+    }
+
+    /// `noir/viewAst`: the syntax tree of the document, with its pending edits analysed.
+    async fn view_ast(&self, params: ast::ViewAstParams) -> Result<Option<ast::AstDocument>> {
+        let uri = &params.text_document.uri;
+        self.analyse_edits(uri).await;
+        let module = match self.ast_map.get(uri.as_str()) {
+            Some(module) => module,
+            None => return Ok(None),
+        };
+        Ok(ast::ast_uri(uri).map(|ast_uri| ast::AstDocument {
+            uri: ast_uri,
+            text: ast::render(uri, &module),
+        }))
+    }
+
+    /// `textDocument/inlineCompletion`: ghost text closing the `assert` or filling in the
+    /// struct literal being typed. Like completions, it goes by the last tree rather than
+    /// waiting for the edits being typed to be analysed.
+    async fn inline_completion(
+        &self,
+        params: inline_completion::InlineCompletionParams,
+    ) -> Result<Option<Vec<inline_completion::InlineCompletionItem>>> {
+        let position = params.text_document_position;
+        let uri = &position.text_document.uri;
+        let document = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.clone(),
+            None => return Ok(None),
+        };
+        let offset = match position_to_offset(position.position, &document, self.encoding()) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let line = document.char_to_line(offset);
+        let line_start = document.line_to_char(line);
+        let line_end = line_start + document.line(line).len_chars();
+        let before = document.slice(line_start..offset).to_string();
+        // Only the end of a line is continued.
+        if !document
+            .slice(offset..line_end)
+            .to_string()
+            .trim()
+            .is_empty()
+        {
+            return Ok(None);
+        }
+
+        let module = self.ast_map.get(uri.as_str());
+        let byte = document.char_to_byte(offset) as u32;
+        let func = module
+            .as_ref()
+            .and_then(|module| utils::enclosing_function(module, &document, byte));
+        let insert_text = match inline_completion::close_assert(&before, func, byte) {
+            Some(text) => Some(inline_completion::InsertText::Text(text)),
+            None => {
+                let names = func.map(utils::names_in_function).unwrap_or_default();
+                let fields_of = |name: &str| self.struct_fields(uri, module.as_deref(), name);
+                inline_completion::complete_struct(&before, fields_of, &names)
+            }
+        };
+        Ok(insert_text.map(|insert_text| {
+            vec![inline_completion::InlineCompletionItem {
+                insert_text,
+                range: Range::new(position.position, position.position),
+            }]
+        }))
+    }
+
+    /// The fields of the struct `name`, declared in `module`, the document at `uri`, or
+    /// elsewhere in its package.
+    fn struct_fields(
+        &self,
+        uri: &Url,
+        module: Option<&ParsedModule>,
+        name: &str,
+    ) -> Option<Vec<String>> {
+        let fields = |module: &ParsedModule| {
+            let typ = module
+                .types
+                .iter()
+                .find(|typ| typ.name.0.contents == name)?;
+            Some(
+                typ.fields
+                    .iter()
+                    .map(|(field, _)| field.0.contents.clone())
+                    .collect(),
+            )
+        };
+        module.and_then(fields).or_else(|| {
+            self.package_files_using(uri, name)
+                .into_iter()
+                .find_map(|(uri, _)| fields(&*self.ast_map.get(uri.as_str())?))
+        })
+    }
+
+    /// The opcodes of the circuit of the package of the document at `uri`, as `nargo info`
+    /// counts them, and the estimated opcodes of each of its functions, costliest first.
+    async fn gate_report(&self, uri: &Url) -> Option<circuit_size::GateReport> {
+        let project = self.project_for(&uri.to_file_path().ok()?)?;
+        let functions = self.function_costs(&project).await;
+        let ((acir_opcodes, _), error) = self.measure_circuit(&project).await;
+        Some(circuit_size::GateReport {
+            package: project.to_string(),
+            acir_opcodes,
+            functions,
+            error,
+        })
+    }
+
+    /// The estimated opcodes of every function of `project`, costliest first.
+    async fn function_costs(&self, project: &Project) -> Vec<circuit_size::FunctionCost> {
+        if self.loaded_crates.insert(project.root.clone()) {
+            self.load_crate(project).await;
+        }
+        let mut functions = self
+            .ast_map
+            .snapshot()
+            .into_iter()
+            .filter_map(|(key, module)| {
+                let uri = Url::parse(&key).ok()?;
+                if !project.contains(&uri.to_file_path().ok()?) {
+                    return None;
+                }
+                let document = self.document_map.get(&key)?.clone();
+                Some(circuit_size::function_costs(
+                    &uri,
+                    &module,
+                    &document,
+                    self.encoding(),
+                ))
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        functions.sort_by(|a, b| b.estimated_opcodes.cmp(&a.estimated_opcodes));
+        functions
+    }
+
+    /// The ACIR opcodes and backend gates of `project`'s circuit from `nargo info`, or why
+    /// they couldn't be counted.
+    async fn measure_circuit(
+        &self,
+        project: &Project,
+    ) -> ((Option<usize>, Option<usize>), Option<String>) {
+        let token = self.begin_progress(format!("nargo info {}", project)).await;
+        let nargo = self.project_nargo(project).await;
+        let measured = nargo::circuit_size(&nargo, &project.root).await;
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        match measured {
+            Some(Ok(sizes)) => (sizes, None),
+            Some(Err(output)) => ((None, None), Some(output.trim().to_string())),
+            None => ((None, None), Some("could not run `nargo info`".to_string())),
+        }
+    }
+
+    /// Measures the circuit of the package of the document at `uri` with the proving backend,
+    /// and ranks its functions by their estimated share of it.
+    async fn profile(&self, uri: &Url) -> Option<circuit_size::Profile> {
+        let project = self.project_for(&uri.to_file_path().ok()?)?;
+        let functions = self.function_costs(&project).await;
+        let ((acir_opcodes, backend_gates), error) = self.measure_circuit(&project).await;
+        Some(circuit_size::Profile {
+            package: project.to_string(),
+            acir_opcodes,
+            backend_gates,
+            hotspots: circuit_size::hotspots(functions, backend_gates),
+            error,
+        })
+    }
+
+    /// `noir/circuitInfo`: compiles the document's package and sums up its circuit.
+    async fn circuit_info(
+        &self,
+        params: circuit_size::CircuitInfoParams,
+    ) -> Result<Option<circuit_size::CircuitInfo>> {
+        let project = match params
+            .text_document
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return Ok(None),
+        };
+        let mut info = circuit_size::CircuitInfo {
+            package: project.to_string(),
+            ..circuit_size::CircuitInfo::default()
+        };
+        let token = self.begin_progress(format!("nargo info {}", project)).await;
+        // One after the other, since both build the package into the same `target`.
+        let nargo = self.project_nargo(&project).await;
+        let acir = nargo::print_acir(&nargo, &project.root).await;
+        let sizes = nargo::circuit_size(&nargo, &project.root).await;
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        match acir {
+            Some(Ok(acir)) => info.read_acir(&acir),
+            Some(Err(output)) => info.error = Some(output.trim().to_string()),
+            None => info.error = Some("could not run `nargo compile`".to_string()),
+        }
+        if let Some(Ok((acir_opcodes, backend_gates))) = sizes {
+            info.acir_opcodes = acir_opcodes;
+            info.backend_gates = backend_gates;
+        }
+        Ok(Some(info))
+    }
+
+    /// The tests in every file of `project`'s crate, parsing the crate first if need be.
+    async fn package_tests(&self, project: &Project) -> Vec<TestItem> {
+        if self.loaded_crates.insert(project.root.clone()) {
+            self.load_crate(project).await;
+        }
+        self.ast_map
+            .snapshot()
+            .into_iter()
+            .filter_map(|(key, module)| {
+                let uri = Url::parse(&key).ok()?;
+                if !project.contains(&uri.to_file_path().ok()?) {
+                    return None;
+                }
+                let document = self.document_map.get(&key)?.clone();
+                Some(test_explorer::tests_in(
+                    &uri,
+                    &module,
+                    &document,
+                    self.encoding(),
+                ))
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Publishes what is wrong with the open `Prover.toml` and `Verifier.toml` of `project`
+    /// given the parameters of its `main`. Libraries have no entry point to check against.
+    async fn validate_inputs(&self, project: &Project) {
+        if project.kind == PackageKind::Lib {
+            return;
+        }
+        let main = self
+            .vfs
+            .read(&project.crate_root)
+            .map(|text| parse_program(&text).0);
+        for (file, verifier) in [
+            (prover_toml::PROVER_TOML, false),
+            (prover_toml::VERIFIER_TOML, true),
+        ] {
+            let path = project.root.join(file);
+            let uri = match Url::from_file_path(&path) {
+                Ok(uri) if self.vfs.is_open(&path) => uri,
+                _ => continue,
+            };
+            let diagnostics = match (&main, self.vfs.read(&path)) {
+                (Some(module), Some(toml)) => module
+                    .functions
+                    .iter()
+                    .find(|func| func.def.name.0.contents == "main")
+                    .map(|main| prover_toml::validate(main, &toml, verifier))
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            let version = self.version_map.get(uri.as_str()).map(|v| *v);
+            self.client
+                .publish_diagnostics(uri, diagnostics, version)
+                .await;
+        }
+    }
+
+    /// Runs `nargo check` on `project` and publishes what it reports next to the server's own
+    /// diagnostics, clearing those of files that no longer have errors.
+    #[tracing::instrument(skip_all, fields(package = %project))]
+    async fn check_project(&self, project: &Project) {
+        let package = Url::from_file_path(&project.root).ok();
+        if let Some(package) = &package {
+            self.send_compilation_status(package.clone(), CompilationState::Started, 0, None)
+                .await;
+        }
+        let token = self
+            .begin_progress(format!("nargo check {}", project))
+            .await;
+        let started = Instant::now();
+        let nargo = self.project_nargo(project).await;
+        let reported = nargo::check(&nargo, &project.root).await;
+        let elapsed = started.elapsed();
+        if let Some(token) = token {
+            self.end_progress(token).await;
+        }
+        let reported = match reported {
+            Some(reported) => reported,
+            None => {
+                self.set_checked(project, false).await;
+                if let Some(package) = package {
+                    self.send_compilation_status(
+                        package,
+                        CompilationState::Failed,
+                        0,
+                        Some(elapsed),
+                    )
+                    .await;
+                }
+                self.client
+                    .log_message(MessageType::ERROR, "could not run `nargo check`")
+                    .await;
+                self.set_status(ServerStatus::Error {
+                    message: "could not run `nargo check`".to_string(),
+                })
+                .await;
+                return;
+            }
+        };
+
+        let errors = reported
+            .iter()
+            .filter(|(_, diagnostic)| diagnostic.severity == Some(DiagnosticSeverity::ERROR))
+            .count();
+        self.set_checked(project, errors == 0).await;
+        if let Some(package) = package {
+            let state = if errors > 0 {
+                CompilationState::Failed
+            } else {
+                CompilationState::Succeeded
+            };
+            self.send_compilation_status(package, state, errors, Some(elapsed))
+                .await;
+        }
+
+        let mut by_file: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+        for (path, diagnostic) in reported {
+            if let Ok(uri) = Url::from_file_path(&path) {
+                by_file.entry(uri.to_string()).or_default().push(diagnostic);
+            }
+        }
+        let mut affected = by_file.keys().cloned().collect::<HashSet<_>>();
+        self.compile_diagnostics.retain(|uri, _| {
+            let in_project = Url::parse(uri)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+                .is_some_and(|path| project.contains(&path));
+            if in_project {
+                affected.insert(uri.clone());
+            }
+            !in_project
+        });
+        self.compile_diagnostics.extend(by_file);
+
+        for uri in affected {
+            let uri = match Url::parse(&uri) {
+                Ok(uri) => uri,
+                Err(_) => continue,
+            };
+            let mut diagnostics = self.local_diagnostics(&uri).await;
+            diagnostics.extend(self.compiled_diagnostics(uri.as_str()));
+            let version = self.version_map.get(uri.as_str()).map(|v| *v);
+            self.client
+                .publish_diagnostics(uri, diagnostics, version)
+                .await;
+        }
+        self.settle_status().await;
+        // nargo writes a `Prover.toml` for packages that have none, which changes `main`'s
+        // lenses.
+        self.refresh_code_lenses().await;
+    }
+
+    /// Records whether the last `nargo check` of `project` passed, which decides whether its
+    /// documents get gate count hints, and has the client ask for their hints again if that
+    /// changed.
+    async fn set_checked(&self, project: &Project, passed: bool) {
+        let changed = if passed {
+            self.checked_packages.insert(project.root.clone())
+        } else {
+            self.checked_packages.remove(&project.root).is_some()
+        };
+        if !changed {
+            return;
+        }
+        self.inlay_hint_cache.retain(|uri, _| {
+            !Url::parse(uri)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+                .is_some_and(|path| project.contains(&path))
+        });
+        if !self.inlay_hint_refresh_support.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(err) = self.client.inlay_hint_refresh().await {
+            tracing::debug!("inlay hint refresh failed: {}", err);
+        }
+    }
+
+    /// Has the client ask for the lenses of its documents again, if it can.
+    async fn refresh_code_lenses(&self) {
+        if !self.code_lens_refresh_support.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(err) = self.client.code_lens_refresh().await {
+            tracing::debug!("code lens refresh failed: {}", err);
+        }
+    }
+
+    async fn send_compilation_status(
+        &self,
+        package: Url,
+        state: CompilationState,
+        errors: usize,
+        duration: Option<Duration>,
+    ) {
+        let status = CompilationStatus {
+            package,
+            state,
+            errors,
+            duration_ms: duration.map(|duration| duration.as_millis() as u64),
+        };
+        if state != CompilationState::Started {
+            *self
+                .last_compile
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = Some(status.clone());
+        }
+        self.client
+            .send_notification::<CompilationStatusNotification>(status)
+            .await;
+    }
+
+    /// The diagnostics the server computes itself for the document at `uri`.
+    async fn local_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        if !self
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .diagnostics
+        {
+            return Vec::new();
+        }
+        let reached = self.reached_in_binary(uri);
+        let document = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.clone(),
+            None => return Vec::new(),
+        };
+        match self.ast_map.get(uri.as_str()) {
+            Some(module) => {
+                self.diagnose(uri, &module, &document, reached.as_ref())
+                    .await
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// What the server finds wrong with `module`, the document at `uri`, by itself. Binaries
+    /// need a `main`, and are told about the functions that neither it nor a test reaches,
+    /// `reached` being those that are.
+    ///
+    /// Functions are checked `DIAGNOSTIC_CHUNK` at a time, yielding in between, so a very
+    /// large file doesn't hold up the requests waiting on the same worker.
+    async fn diagnose(
+        &self,
+        uri: &Url,
+        module: &ParsedModule,
+        document: &Rope,
+        reached: Option<&HashSet<String>>,
+    ) -> Vec<Diagnostic> {
+        let functions = utils::all_functions(module).collect::<Vec<_>>();
+        let mut found = Vec::new();
+        for chunk in functions.chunks(DIAGNOSTIC_CHUNK) {
+            for func in chunk {
+                found.extend(diagnostics::unused_variables_in(
+                    func,
+                    document,
+                    self.encoding(),
+                ));
+            }
+            tokio::task::yield_now().await;
+        }
+        let project = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Some((self.project_for(&path)?, path)));
+        if let Some((project, path)) = project {
+            if project.kind == PackageKind::Bin && path == project.crate_root {
+                found.extend(diagnostics::missing_main(module));
+            }
+        }
+        if let Some(reached) = reached {
+            found.extend(diagnostics::unreachable_functions(
+                module,
+                document,
+                reached,
+                self.encoding(),
+            ));
+        }
+        found
+    }
+
+    /// The functions `main` and the tests of the binary the document at `uri` is part of
+    /// reach, over every file of its crate. `None` for other kinds of packages, which are
+    /// used from outside, and for binaries without a `main`, where nothing would be.
+    fn reached_in_binary(&self, uri: &Url) -> Option<HashSet<String>> {
+        let project = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+            .filter(|project| project.kind == PackageKind::Bin)?;
+        let mut has_main = false;
+        let mut roots = Vec::new();
+        let mut references = Vec::new();
+        for (key, module) in self.ast_map.snapshot() {
+            let path = match Url::parse(&key)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+            {
+                Some(path) if project.contains(&path) => path,
+                _ => continue,
+            };
+            if path == project.crate_root {
+                has_main = module
+                    .functions
+                    .iter()
+                    .any(|func| func.def.name.0.contents == "main");
+            }
+            if let Some(document) = self.document_map.get(&key) {
+                roots.extend(
+                    utils::test_functions(&module, &document)
+                        .into_iter()
+                        .map(|func| func.def.name.0.contents.clone()),
+                );
+            }
+            references.extend(self.ast_map.function_references(&key));
+        }
+        if !has_main {
+            return None;
+        }
+        roots.push("main".to_string());
+        let references = references
+            .iter()
+            .map(|file| file.as_ref())
+            .collect::<Vec<_>>();
+        Some(diagnostics::reachable_functions(&references, roots))
+    }
+
+    /// Keeps the estimated opcodes of the functions of the document at `uri` for its gate
+    /// count hints.
+    fn estimate_opcodes(&self, uri: &Url, module: &ParsedModule) {
+        let module_path = utils::module_path(uri).unwrap_or_else(|| "crate".to_string());
+        let opcodes = circuit_size::estimate_opcodes(module, &module_path);
+        self.gate_count_map.insert(uri.to_string(), opcodes);
+    }
+
+    fn compiled_diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
+        self.compile_diagnostics
+            .get(uri)
+            .map(|diagnostics| diagnostics.clone())
+            .unwrap_or_default()
+    }
+
+    /// Every file of the crate the document at `uri` is part of, with its text and tree.
+    /// Files opened or loaded with the crate keep the tree they have, if it is of their
+    /// current text, and only the others are read and parsed.
+    fn crate_documents(&self, uri: &Url) -> Vec<(Url, Rope, Arc<ParsedModule>)> {
+        let project = match uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path))
+        {
+            Some(project) => project,
+            None => return Vec::new(),
+        };
+        project
+            .load_crate_files(|path| {
+                let uri = Url::from_file_path(path).ok()?;
+                let document = self.document_map.get(uri.as_str()).map(|rope| rope.clone());
+                let (document, module) = match (document, self.current_tree(uri.as_str())) {
+                    (Some(document), Some((module, _))) => (document, module),
+                    (document, _) => {
+                        let text = match document {
+                            Some(document) => document.to_string(),
+                            None => self.vfs.read(path)?,
+                        };
+                        let (module, _) = parse_program(&text);
+                        (Rope::from_str(&text), Arc::new(module))
+                    }
+                };
+                Some(((uri, document), module))
+            })
+            .into_iter()
+            .map(|(_, (uri, document), module)| (uri, document, module))
+            .collect()
+    }
+
+    /// Every file of `project`'s crate with its text and syntax tree, parsed on a blocking
+    /// thread.
+    async fn parse_crate(&self, project: &Project) -> Vec<(PathBuf, String, ParsedModule)> {
+        let vfs = self.vfs.snapshot();
+        let project = project.clone();
+        tokio::task::spawn_blocking(move || project.crate_files(|path| vfs.read(path)))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Adds the top-level items of every module of `dependency` to the dependency index,
+    /// unless another package depending on it already did.
+    async fn index_dependency(&self, dependency: &Dependency) {
+        if self.indexed_dependencies.contains(&dependency.root) {
+            return;
+        }
+        // Not fetched yet, unless discovery stops at the dependency's own manifest.
+        let package = match Project::discover(&dependency.root) {
+            Some(package) if package.root == dependency.root => package,
+            _ => return,
+        };
+        self.indexed_dependencies.insert(dependency.root.clone());
+        let vfs = self.vfs.snapshot();
+        // The items' locations depend on the encoding, so it is part of what is cached.
+        let encoding = self.encoding();
+        let kind = format!("dependency-{}", encoding.kind().as_str());
+        let modules = tokio::task::spawn_blocking(move || {
+            let index = || {
+                package
+                    .crate_files(|path| vfs.read(path))
+                    .into_iter()
+                    .filter_map(|(path, text, module)| {
+                        let uri = Url::from_file_path(&path).ok()?;
+                        let document = Rope::from_str(&text);
+                        let items = stdlib::items(&uri, &document, &module, encoding);
+                        Some((utils::module_path(&uri)?, items))
+                    })
+                    .collect::<Vec<_>>()
+            };
+            // The cache is keyed by what is on disk, which unsaved buffers don't match.
+            if vfs.is_open_in(&package.root) {
+                index()
+            } else {
+                index_cache::cached(&kind, &package.root, index)
+            }
+        })
+        .await
+        .unwrap_or_default();
+        let interner = self.ast_map.interner();
+        for (module_path, mut items) in modules {
+            for item in &mut items {
+                item.name = interner.intern(&item.name);
+            }
+            self.dependency_index
+                .insert((dependency.root.clone(), module_path), items);
+        }
+    }
+
+    /// Analyses the document at `uri` once it has gone `QUIET_PERIOD` without edits after
+    /// `version`, on a task of its own so the notification is done with right away. Each
+    /// edit aborts the wait of the one before.
+    fn debounce(&self, uri: Url, version: i32) {
+        let backend = self.clone();
+        let key = uri.to_string();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(QUIET_PERIOD).await;
+            // Once out of the map, no edit aborts the task any more, so the analysis isn't cut
+            // short halfway through. An edit that came in first has replaced it there.
+            let waiting = backend
+                .debounced
+                .remove_if(uri.as_str(), |_, (pending, _)| *pending == version);
+            if waiting.is_some() {
+                backend.analyse_edits(&uri).await;
+            }
+        });
+        if let Some((_, previous)) = self.debounced.insert(key, (version, task.abort_handle())) {
+            previous.abort();
+        }
+    }
+
+    /// Marks the edits up to `version` of the document at `uri` as analysed, once the tree
+    /// parsed from them is stored.
+    fn analysed(&self, uri: &Url, version: i32) {
+        self.unanalysed
+            .remove_if(uri.as_str(), |_, pending| *pending == version);
+    }
+
+    /// Keeps `rope` as the text of the document at `uri` at `version`. Callers do this before
+    /// analysing it, which reads the text from here.
+    fn set_document(&self, uri: &Url, rope: Rope, version: i32) {
+        self.document_map.insert(uri.to_string(), rope);
+        self.version_map.insert(uri.to_string(), version);
+        self.inlay_hint_cache.remove(uri.as_str());
+    }
+
+    /// Drops everything the server keeps about the document at `uri`.
+    fn forget(&self, uri: &str) {
+        self.ast_map.remove(uri);
+        self.document_map.remove(uri);
+        self.version_map.remove(uri);
+        self.unanalysed.remove(uri);
+        if let Some((_, (_, task))) = self.debounced.remove(uri) {
+            task.abort();
+        }
+        self.out_of_sync.remove(uri);
+        self.gate_count_map.remove(uri);
+        self.inlay_hint_cache.remove(uri);
+    }
+
+    /// Keeps the analysis of files that aren't open under `CLOSED_FILES_CAPACITY`, and under
+    /// the configured cache size. A crate that loses files this way is loaded again the next
+    /// time one of its files changes.
+    fn evict_closed_files(&self) {
+        let is_open = |uri: &str| {
+            Url::parse(uri)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+                .is_some_and(|path| self.vfs.is_open(&path))
+        };
+        let budget = self
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .cache_budget();
+        let document_bytes = |uri: &str| {
+            self.document_map
+                .get(uri)
+                .map_or(0, |document| memory::rope_bytes(&document))
+        };
+        let evicted = self.ast_map.evict(
+            analysis::CLOSED_FILES_CAPACITY,
+            budget,
+            is_open,
+            document_bytes,
+        );
+        for uri in evicted {
+            self.forget(&uri);
+            let path = Url::parse(&uri)
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok());
+            if let Some(project) = path.and_then(|path| self.project_for(&path)) {
+                self.loaded_crates.remove(&project.root);
+            }
+        }
+    }
+
+    /// The tree of the document at `uri` with the version it was parsed from, as long as that
+    /// is the version the client has now. Results holding positions are computed from it
+    /// rather than from whatever tree was parsed last, which may be behind the text.
+    fn current_tree(&self, uri: &str) -> Option<(Arc<ParsedModule>, Option<i32>)> {
+        let version = self.version_map.get(uri).map(|version| *version);
+        Some((self.ast_map.get_version(uri, version)?, version))
+    }
+
+    /// Whether the document at `uri` is no longer at `version`, the one a result was computed
+    /// from: the client would show it against text it no longer has.
+    fn outdated(&self, uri: &str, version: Option<i32>) -> bool {
+        self.version_map.get(uri).map(|current| *current) != version
+    }
+
+    /// Whether the document at `uri` has been edited past `version`, which makes what was
+    /// being computed for that version useless: the edit's own analysis replaces it.
+    fn superseded(&self, uri: &Url, version: i32) -> bool {
+        self.version_map
+            .get(uri.as_str())
+            .is_some_and(|current| *current != version)
+    }
+
+    /// Analyses the edits made to the document at `uri` since it was last analysed, if any,
+    /// without waiting for the quiet period, for requests that need the current tree. The
+    /// edits stay marked as unanalysed until their tree is stored, so requests coming in
+    /// meanwhile analyse them too rather than reading the previous tree.
+    async fn analyse_edits(&self, uri: &Url) {
+        let version = match self.unanalysed.get(uri.as_str()) {
+            Some(version) => *version,
+            None => return,
+        };
+        let text = match self.document_map.get(uri.as_str()) {
+            Some(rope) => rope.to_string(),
+            None => return,
+        };
+        self.on_change(TextDocumentItem {
+            uri: uri.clone(),
+            text,
+            version,
+        })
+        .await;
+    }
+
+    /// Analyses the document at `params.uri`, whose text callers have already kept with
+    /// `set_document`, and publishes its diagnostics.
+    #[tracing::instrument(skip_all, fields(uri = %params.uri, version = params.version))]
+    async fn on_change(&self, params: TextDocumentItem) {
+        let project = params
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| self.project_for(&path));
+        if let Some(project) = &project {
+            if self.loaded_crates.insert(project.root.clone()) {
+                self.load_crate(project).await;
+            }
+        }
+
+        // The inputs files aren't Noir; they are checked against `main` instead.
+        let path = params.uri.to_file_path().ok();
+        let is_inputs = path.as_ref().is_some_and(|path| {
+            path.ends_with(prover_toml::PROVER_TOML) || path.ends_with(prover_toml::VERIFIER_TOML)
+        });
+        if is_inputs {
+            self.analysed(&params.uri, params.version);
+            if let Some(project) = &project {
+                self.validate_inputs(project).await;
+            }
+            return;
+        }
+
+        // Unchanged text, as when a file is opened or saved, keeps the tree it had.
+        let parsed = self
+            .ast_map
+            .set_text(params.uri.as_str(), params.text, Some(params.version))
+            .await;
+        self.analysed(&params.uri, params.version);
+        if self.superseded(&params.uri, params.version) {
+            return;
+        }
+        let module = match self.ast_map.get(params.uri.as_str()) {
+            Some(module) => module,
+            None => return,
+        };
+        match parsed {
+            Some(true) => self.estimate_opcodes(&params.uri, &module),
+            Some(false) => {
+                self.gate_count_map.remove(params.uri.as_str());
+            }
+            None => {}
+        }
+
+        let mut diagnostics = self.local_diagnostics(&params.uri).await;
+        diagnostics.extend(self.compiled_diagnostics(params.uri.as_str()));
+        // Edits may have come in while a large file was diagnosed.
+        if self.superseded(&params.uri, params.version) {
+            return;
+        }
+
+        self.client
+            .publish_diagnostics(params.uri, diagnostics, Some(params.version))
+            .await;
+
+        if let (Some(project), Some(path)) = (&project, &path) {
+            if *path == project.crate_root {
+                self.validate_inputs(project).await;
+            }
+        }
+    }
+}
+
+/// How long edits to a document have to stop before it is analysed again, so typing doesn't
+/// re-parse it on every keystroke.
+const QUIET_PERIOD: Duration = Duration::from_millis(200);
+
+/// The kind of completion for an item of `kind`.
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+        SymbolKind::CONSTANT => CompletionItemKind::CONSTANT,
+        SymbolKind::METHOD => CompletionItemKind::METHOD,
+        _ => CompletionItemKind::FUNCTION,
+    }
+}
+
+/// How many functions are diagnosed between yields to the executor.
+const DIAGNOSTIC_CHUNK: usize = 64;
+
+/// Gives a `$/cancelRequest` the client sent since the request being handled came in the
+/// chance to drop it before its work starts. tower-lsp cancels a request by dropping its
+/// future, which only takes effect at an await.
+async fn checkpoint() {
+    tokio::task::yield_now().await
+}
+
+/// How much of an operation of `total` steps is done once `done` of them are.
+fn percentage(done: usize, total: usize) -> u32 {
+    (done * 100 / total.max(1)) as u32
+}
+
+/// The command line: how to reach the client, and where to log.
+#[derive(Debug, Parser)]
+#[command(version, about = "Language server for Noir")]
+struct Cli {
+    /// Talk to the client over stdin and stdout. This is the default.
+    #[arg(long, conflicts_with_all = ["tcp", "port", "pipe"])]
+    stdio: bool,
+    /// Wait for the client to connect to ADDRESS, such as 127.0.0.1:9257.
+    #[arg(
+        long,
+        visible_alias = "listen",
+        value_name = "ADDRESS",
+        conflicts_with_all = ["port", "pipe"]
+    )]
+    tcp: Option<String>,
+    /// Wait for the client to connect to PORT on localhost.
+    #[arg(long, conflicts_with = "pipe")]
+    port: Option<u16>,
+    /// Connect to the Unix domain socket, or named pipe on Windows, the client created.
+    #[arg(long, value_name = "NAME")]
+    pipe: Option<String>,
+    /// Write the server's logs to PATH rather than stderr.
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Passed by some clients along with the transport. The server exits with its connection
+    /// rather than watching the process.
+    #[arg(long = "clientProcessId", hide = true)]
+    client_process_id: Option<u32>,
+}
+
+/// How the server and the client exchange messages.
+enum Transport {
+    Stdio,
+    Tcp(String),
+    Pipe(String),
+}
+
+impl Cli {
+    fn transport(&self) -> Transport {
+        if self.stdio {
+            Transport::Stdio
+        } else if let Some(name) = &self.pipe {
+            Transport::Pipe(name.clone())
+        } else if let Some(address) = &self.tcp {
+            Transport::Tcp(address.clone())
+        } else if let Some(port) = self.port {
+            Transport::Tcp(format!("127.0.0.1:{}", port))
+        } else {
+            Transport::Stdio
+        }
+    }
+}
+
+/// Logs to `log_file`, or to stderr, at the levels `RUST_LOG` asks for, and to the client as
+/// far as it asked through `$/setTrace`.
+fn init_logging(log_file: Option<&Path>) {
+    let opened = log_file.map(|path| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+    });
+    let (writer, error) = match opened {
+        Some(Ok(file)) => (BoxMakeWriter::new(std::sync::Mutex::new(file)), None),
+        Some(Err(err)) => (BoxMakeWriter::new(std::io::stderr), Some(err)),
+        None => (BoxMakeWriter::new(std::io::stderr), None),
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("{}=info", env!("CARGO_CRATE_NAME"))));
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .with_filter(filter),
+        )
+        .with(trace::ClientLayer)
+        .init();
+    // Only now that there is somewhere to log to, stderr in its place.
+    if let (Some(path), Some(err)) = (log_file, error) {
+        tracing::warn!("cannot open log file {}: {}", path.display(), err);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    init_logging(cli.log_file.as_deref());
+    if let Some(pid) = cli.client_process_id {
+        tracing::debug!(pid, "started by the client");
+    }
+    let log_file = cli.log_file.clone();
+
+    let (service, socket) = LspService::build(|client| {
+        Backend(Arc::new(State {
+            client: {
+                trace::forward_to(client.clone());
+                catch_panic::report_panics(client.clone(), log_file);
+                telemetry::report_usage(client.clone());
+                client
+            },
+            ast_map: AnalysisStore::default(),
+            document_map: DashMap::new(),
+            vfs: Vfs::default(),
+            gate_count_map: DashMap::new(),
+            checked_packages: DashSet::new(),
+            version_map: DashMap::new(),
+            unanalysed: DashMap::new(),
+            debounced: DashMap::new(),
+            out_of_sync: DashSet::new(),
+            acir_views: DashSet::new(),
+            compile_diagnostics: DashMap::new(),
+            inlay_hint_cache: DashMap::new(),
+            config: RwLock::new(Config::default()),
+            nargo: RwLock::new(None),
+            pinned_nargo: DashMap::new(),
+            utf8_positions: AtomicBool::new(false),
+            resolve_code_action_edits: AtomicBool::new(false),
+            change_annotation_support: AtomicBool::new(false),
+            markdown_hovers: AtomicBool::new(false),
+            configuration_support: AtomicBool::new(false),
+            code_lens_refresh_support: AtomicBool::new(false),
+            inlay_hint_refresh_support: AtomicBool::new(false),
+            work_done_progress_support: AtomicBool::new(false),
+            progress_tokens: AtomicU64::new(0),
+            dynamic: RwLock::new(registration::Dynamic::default()),
+            workspace_folders: DashSet::new(),
+            projects: DashMap::new(),
+            loaded_crates: DashSet::new(),
+            dependency_index: DashMap::new(),
+            stdlib_index: DashMap::new(),
+            stdlib_indexed: tokio::sync::Mutex::new(false),
+            indexed_dependencies: DashSet::new(),
+            test_statuses: DashMap::new(),
+            status: RwLock::new(ServerStatus::Loading),
+            last_compile: RwLock::new(None),
+            started: Instant::now(),
+            // semantic_token_map: DashMap::new(),
+        }))
+    })
+    .custom_method(test_explorer::LIST_TESTS, Backend::list_tests)
+    .custom_method(test_explorer::RUN_TESTS, Backend::run_test_suite)
+    .custom_method(acir::VIEW_ACIR, Backend::view_acir)
+    .custom_method(ast::VIEW_AST, Backend::view_ast)
+    .custom_method(
+        inline_completion::INLINE_COMPLETION,
+        Backend::inline_completion,
+    )
+    .custom_method(circuit_size::CIRCUIT_INFO, Backend::circuit_info)
+    .custom_method(memory::MEMORY_USAGE, Backend::memory_usage)
+    .custom_method(status::STATUS, Backend::status_report)
+    .custom_method("$/setTrace", Backend::set_trace)
+    .finish();
+
+    serde_json::json!({"test": 20});
+    // A panicking handler fails its own request rather than the server.
+    let service = CatchPanic(telemetry::Timed(service));
+    match cli.transport() {
+        Transport::Pipe(name) => match connect_pipe(&name).await {
+            Ok(stream) => {
+                let (read, write) = tokio::io::split(stream);
+                Server::new(read, write, socket).serve(service).await;
+            }
+            Err(err) => tracing::error!("cannot connect to the pipe {}: {}", name, err),
+        },
+        Transport::Tcp(address) => match accept_tcp(&address).await {
+            Ok(stream) => {
+                let (read, write) = tokio::io::split(stream);
+                Server::new(read, write, socket).serve(service).await;
+            }
+            Err(err) => tracing::error!("cannot serve on {}: {}", address, err),
+        },
+        Transport::Stdio => {
+            let stdin = tokio::io::stdin();
+            let stdout = tokio::io::stdout();
+            Server::new(stdin, stdout, socket).serve(service).await;
+        }
+    }
+}
+
+/// Waits for the client to connect on `address`.
+async fn accept_tcp(address: &str) -> std::io::Result<tokio::net::TcpStream> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tracing::info!("waiting for the client on {}", address);
+    let (stream, _) = listener.accept().await?;
+    Ok(stream)
+}
+
+/// Connects to the pipe the client created and named with `--pipe`: a Unix domain socket,
+/// or a named pipe on Windows.
+#[cfg(unix)]
+async fn connect_pipe(name: &str) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(name).await
 }
 
-fn offset_to_position(offset: usize, rope: &Rope) -> Option<Position> {
-    let line = rope.try_char_to_line(offset).ok()?;
-    let first_char_of_line = rope.try_line_to_char(line).ok()?;
-    let column = offset - first_char_of_line;
-    Some(Position::new(line as u32, column as u32))
+#[cfg(windows)]
+async fn connect_pipe(
+    name: &str,
+) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(name)
 }
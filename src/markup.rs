@@ -0,0 +1,23 @@
+use tower_lsp::lsp_types::{MarkupContent, MarkupKind};
+
+/// Whether a client listing `formats` for some content, most preferred first, renders
+/// markdown there. Clients that list nothing are only sure to show plain text.
+pub fn supports_markdown(formats: Option<&Vec<MarkupKind>>) -> bool {
+    formats.is_some_and(|formats| formats.contains(&MarkupKind::Markdown))
+}
+
+/// Noir source, fenced as a code block for clients that render markdown and as is for the
+/// others, which would show the fence.
+pub fn code(source: &str, markdown: bool) -> MarkupContent {
+    if markdown {
+        MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```noir\n{}\n```", source),
+        }
+    } else {
+        MarkupContent {
+            kind: MarkupKind::PlainText,
+            value: source.to_string(),
+        }
+    }
+}
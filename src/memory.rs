@@ -0,0 +1,82 @@
+use std::mem::{size_of, size_of_val};
+use std::path::PathBuf;
+
+use noirc_frontend::{NoirFunction, ParsedModule};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::stdlib::StdItem;
+
+/// Reports how much memory the server holds for documents, syntax trees and indexes, to
+/// tell what grows in a large workspace. Takes no parameters.
+pub const MEMORY_USAGE: &str = "noir/memoryUsage";
+
+/// Bytes held, in total and by what holds them. Sizes are estimates: text is counted as
+/// stored, syntax trees by their items and statements but not the expressions within.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    pub total: usize,
+    /// Documents grouped by the package they belong to, largest first.
+    pub crates: Vec<CrateMemory>,
+    pub stdlib_index: usize,
+    pub dependency_index: usize,
+    /// The names the indexes and syntax trees share, counted here rather than where used.
+    pub names: usize,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrateMemory {
+    /// The package's directory, or `None` for documents outside any package.
+    pub root: Option<PathBuf>,
+    pub files: usize,
+    /// Text of the documents, open or loaded from disk.
+    pub documents: usize,
+    pub syntax_trees: usize,
+}
+
+impl CrateMemory {
+    pub fn total(&self) -> usize {
+        self.documents + self.syntax_trees
+    }
+}
+
+/// The bytes `document`'s text takes, including what its buffers leave unused.
+pub fn rope_bytes(document: &Rope) -> usize {
+    document.capacity()
+}
+
+/// A lower bound of the bytes `module` takes: its items and function bodies' statements.
+pub fn syntax_tree_bytes(module: &ParsedModule) -> usize {
+    let functions = |functions: &[NoirFunction]| {
+        size_of_val(functions)
+            + functions
+                .iter()
+                .map(|func| size_of_val(func.def.body.0.as_slice()))
+                .sum::<usize>()
+    };
+    size_of::<ParsedModule>()
+        + functions(&module.functions)
+        + size_of_val(module.impls.as_slice())
+        + module
+            .impls
+            .iter()
+            .map(|imp| functions(&imp.methods))
+            .sum::<usize>()
+        + size_of_val(module.types.as_slice())
+        + size_of_val(module.imports.as_slice())
+        + size_of_val(module.globals.as_slice())
+        + size_of_val(module.module_decls.as_slice())
+}
+
+/// The bytes the items of one standard library or dependency module take, with its path.
+pub fn library_module_bytes(module_path: &str, items: &[StdItem]) -> usize {
+    module_path.len()
+        + items
+            .iter()
+            .map(|item| {
+                size_of::<StdItem>() + item.signature.len() + item.location.uri.as_str().len()
+            })
+            .sum::<usize>()
+}
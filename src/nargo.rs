@@ -0,0 +1,479 @@
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use tokio::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+use crate::workspace::manifest_value;
+
+/// Source reported on the diagnostics of a nargo run.
+pub const SOURCE: &str = "nargo";
+
+/// The manifest of the noir checkout the frontend is built from, for its version.
+const NOIR_MANIFEST: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../noir/Cargo.toml"));
+
+/// The version of the frontend the server parses with.
+pub fn frontend_version() -> Option<String> {
+    manifest_value(NOIR_MANIFEST, "workspace.package", "version")
+}
+
+/// Where nargo is: `configured` if given, otherwise the first `nargo` on `PATH`, then in
+/// the noirup install directory. With a `pinned` version, a candidate has to report that
+/// version, and `~/.nargo/toolchains/<version>/bin` is tried first.
+pub async fn locate(configured: Option<&str>, pinned: Option<&str>) -> Option<PathBuf> {
+    let binary = if cfg!(windows) { "nargo.exe" } else { "nargo" };
+    let nargo_home = std::env::var_os("NARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".nargo")));
+    let candidates = match configured {
+        Some(configured) => vec![PathBuf::from(configured)],
+        None => {
+            let toolchain = pinned.and_then(|version| {
+                Some(
+                    nargo_home
+                        .as_ref()?
+                        .join("toolchains")
+                        .join(version)
+                        .join("bin"),
+                )
+            });
+            let on_path = std::env::var_os("PATH")
+                .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+                .unwrap_or_default();
+            toolchain
+                .into_iter()
+                .chain(on_path)
+                .chain(nargo_home.map(|home| home.join("bin")))
+                .map(|dir| dir.join(binary))
+                .collect()
+        }
+    };
+
+    for candidate in candidates.into_iter().filter(|path| path.is_file()) {
+        match pinned {
+            Some(pinned) if version(&candidate).await.as_deref() != Some(pinned) => continue,
+            _ => return Some(candidate),
+        }
+    }
+    None
+}
+
+/// The version `nargo --version` reports, e.g. `0.7.1` from
+/// `nargo 0.7.1 (git version hash: .., is dirty: false)`.
+pub async fn version(nargo: &Path) -> Option<String> {
+    let output = Command::new(nargo).arg("--version").output().await.ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+/// Runs `nargo check` in the package at `root` and collects the errors and warnings it
+/// reports, by the file they are in. `None` when nargo couldn't be run at all.
+pub async fn check(nargo: &Path, root: &Path) -> Option<Vec<(PathBuf, Diagnostic)>> {
+    let output = Command::new(nargo)
+        .arg("check")
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    // Errors go to stderr, but older versions print some of them to stdout.
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    Some(parse_diagnostics(&text, root))
+}
+
+/// Runs the test called exactly `name` in the package at `root`. Returns whether it passed,
+/// with everything nargo printed along the way.
+pub async fn test(nargo: &Path, root: &Path, name: &str) -> Option<(bool, String)> {
+    let output = Command::new(nargo)
+        .args(["test", "--exact", name])
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some((output.status.success(), strip_ansi(&text)))
+}
+
+/// Compiles the package at `root` and returns the ACIR nargo prints for it, or what nargo
+/// printed instead when it failed to compile.
+pub async fn print_acir(nargo: &Path, root: &Path) -> Option<Result<String, String>> {
+    let output = Command::new(nargo)
+        .args(["compile", "--print-acir"])
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+    if output.status.success() {
+        Some(Ok(stdout))
+    } else {
+        Some(Err(format!(
+            "{}{}",
+            stdout,
+            strip_ansi(&String::from_utf8_lossy(&output.stderr))
+        )))
+    }
+}
+
+/// Compiles the contract package at `root` and returns the ABI of `function` in `contract`
+/// from the artifact nargo writes to `target`, or what nargo printed when it failed.
+pub async fn contract_abi(
+    nargo: &Path,
+    root: &Path,
+    contract: &str,
+    function: &str,
+) -> Option<Result<Value, String>> {
+    let output = Command::new(nargo)
+        .arg("compile")
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return Some(Err(format!(
+            "{}{}",
+            strip_ansi(&String::from_utf8_lossy(&output.stdout)),
+            strip_ansi(&String::from_utf8_lossy(&output.stderr))
+        )));
+    }
+    // One artifact per contract, each listing its functions.
+    let artifacts = std::fs::read_dir(root.join("target")).ok()?;
+    let abi = artifacts
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .filter_map(|text| serde_json::from_str::<Value>(&text).ok())
+        .filter(|artifact| artifact["name"] == contract)
+        .find_map(|artifact| {
+            artifact["functions"]
+                .as_array()?
+                .iter()
+                .find(|func| func["name"] == function)
+                .map(|func| func["abi"].clone())
+        });
+    Some(abi.ok_or_else(|| {
+        format!(
+            "no ABI for `{}::{}` in the build artifacts",
+            contract, function
+        )
+    }))
+}
+
+/// The ACIR opcodes and backend gates of the package at `root`'s circuit, from `nargo info`
+/// or, for versions before it was renamed, `nargo gates`. They print e.g.
+///
+/// ```text
+/// Total ACIR opcodes generated for language PLONKCSat { width: 3 }: 12
+/// Backend circuit size: 3547
+/// ```
+pub async fn circuit_size(
+    nargo: &Path,
+    root: &Path,
+) -> Option<Result<(Option<usize>, Option<usize>), String>> {
+    let mut output = Command::new(nargo)
+        .arg("info")
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        output = Command::new(nargo)
+            .arg("gates")
+            .current_dir(root)
+            .output()
+            .await
+            .ok()?;
+    }
+    let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+    if !output.status.success() {
+        return Some(Err(format!(
+            "{}{}",
+            stdout,
+            strip_ansi(&String::from_utf8_lossy(&output.stderr))
+        )));
+    }
+    let count = |label: &str| {
+        stdout.lines().find_map(|line| {
+            let (before, count) = line.rsplit_once(':')?;
+            if !before.contains(label) {
+                return None;
+            }
+            count.trim().parse::<usize>().ok()
+        })
+    };
+    Some(Ok((count("ACIR opcodes"), count("circuit size"))))
+}
+
+/// Solves the witness of the package at `root` for the inputs in its `Prover.toml`. Returns
+/// the value `main` returned, if any, or what nargo printed when solving failed.
+pub async fn execute(nargo: &Path, root: &Path) -> Option<Result<Option<String>, String>> {
+    let output = Command::new(nargo)
+        .arg("execute")
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+    if !output.status.success() {
+        return Some(Err(format!(
+            "{}{}",
+            stdout,
+            strip_ansi(&String::from_utf8_lossy(&output.stderr))
+        )));
+    }
+    let return_value = stdout.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("Circuit output:")?.trim();
+        Some(value.to_string()).filter(|value| !value.is_empty() && value != "None")
+    });
+    Some(Ok(return_value))
+}
+
+/// Proves the package at `root` for the inputs in its `Prover.toml`, which nargo writes to
+/// `proofs/<package>.proof`. Fails with what nargo printed.
+pub async fn prove(nargo: &Path, root: &Path) -> Option<Result<(), String>> {
+    let output = Command::new(nargo)
+        .arg("prove")
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    if output.status.success() {
+        return Some(Ok(()));
+    }
+    Some(Err(format!(
+        "{}{}",
+        strip_ansi(&String::from_utf8_lossy(&output.stdout)),
+        strip_ansi(&String::from_utf8_lossy(&output.stderr))
+    )))
+}
+
+/// Reads the reports nargo renders with codespan, e.g.
+///
+/// ```text
+/// error: cannot find `x` in this scope
+///    ┌─ src/main.nr:3:5
+///    │
+///  3 │     x
+///    │     - not found in this scope
+/// ```
+///
+/// Relative file names are taken to be relative to `root`.
+fn parse_diagnostics(output: &str, root: &Path) -> Vec<(PathBuf, Diagnostic)> {
+    let mut diagnostics: Vec<(PathBuf, Diagnostic)> = Vec::new();
+    let mut header = None;
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let line = strip_ansi(line);
+        let trimmed = line.trim();
+        if let Some(message) = trimmed.strip_prefix("error:") {
+            header = Some((DiagnosticSeverity::ERROR, message.trim().to_string()));
+            continue;
+        }
+        if let Some(message) = trimmed.strip_prefix("warning:") {
+            header = Some((DiagnosticSeverity::WARNING, message.trim().to_string()));
+            continue;
+        }
+        let location = match trimmed.strip_prefix("┌─") {
+            Some(location) => location.trim(),
+            None => continue,
+        };
+        let (severity, message) = match header.take() {
+            Some(header) => header,
+            None => continue,
+        };
+        let (file, range) = match reported_span(location, &mut lines) {
+            Some(span) => span,
+            None => continue,
+        };
+        diagnostics.push((
+            root.join(file),
+            Diagnostic {
+                range,
+                severity: Some(severity),
+                source: Some(SOURCE.to_string()),
+                message,
+                ..Diagnostic::default()
+            },
+        ));
+    }
+    diagnostics
+}
+
+/// Where a failed `nargo test` says the test went wrong, such as the assertion that didn't
+/// hold: the first location it reports, read like [`parse_diagnostics`] reads them.
+pub fn failure_location(output: &str, root: &Path) -> Option<(PathBuf, Range)> {
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let line = strip_ansi(line);
+        if let Some(location) = line.trim().strip_prefix("┌─") {
+            if let Some((file, range)) = reported_span(location.trim(), &mut lines) {
+                return Some((root.join(file), range));
+            }
+        }
+    }
+    None
+}
+
+/// The file and range of the report at `location`, with the width taken from the `^^^` or
+/// `---` under the source line that follows in `lines`.
+fn reported_span<'a>(
+    location: &str,
+    lines: &mut Peekable<impl Iterator<Item = &'a str>>,
+) -> Option<(String, Range)> {
+    let (file, position) = location_of(location)?;
+    let mut width = 1;
+    while let Some(next) = lines.peek() {
+        let next = strip_ansi(next);
+        let code = match next.split_once('│') {
+            Some((_, code)) => code.trim_start(),
+            None => break,
+        };
+        lines.next();
+        if code.starts_with(['^', '-']) {
+            width = code.chars().take_while(|c| *c == '^' || *c == '-').count() as u32;
+            break;
+        }
+    }
+    Some((
+        file.to_string(),
+        Range::new(
+            position,
+            Position::new(position.line, position.character + width),
+        ),
+    ))
+}
+
+/// Splits `src/main.nr:3:5` into the file and the zero-based position.
+fn location_of(location: &str) -> Option<(&str, Position)> {
+    let (rest, column) = location.rsplit_once(':')?;
+    let (file, line) = rest.rsplit_once(':')?;
+    let line = line.parse::<u32>().ok()?.checked_sub(1)?;
+    let column = column.parse::<u32>().ok()?.checked_sub(1)?;
+    Some((file, Position::new(line, column)))
+}
+
+/// Drops the colour escapes nargo adds when it thinks it writes to a terminal.
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "\
+warning: unused variable y
+   ┌─ src/main.nr:2:9
+   │
+ 2 │     let y = 1;
+   │         - unused variable
+
+error: cannot find `x` in this scope
+   ┌─ src/lib/hash.nr:3:5
+   │
+ 3 │     x + 1
+   │     ^ not found in this scope
+
+error: Aborting due to 1 previous error
+";
+
+    #[test]
+    fn reports_are_read_with_their_file_and_span() {
+        let diagnostics = parse_diagnostics(REPORT, Path::new("/package"));
+        assert_eq!(diagnostics.len(), 2);
+
+        let (file, warning) = &diagnostics[0];
+        assert_eq!(file, Path::new("/package/src/main.nr"));
+        assert_eq!(warning.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(warning.message, "unused variable y");
+        assert_eq!(
+            warning.range,
+            Range::new(Position::new(1, 8), Position::new(1, 9))
+        );
+
+        let (file, error) = &diagnostics[1];
+        assert_eq!(file, Path::new("/package/src/lib/hash.nr"));
+        assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(error.message, "cannot find `x` in this scope");
+        assert_eq!(error.source.as_deref(), Some(SOURCE));
+    }
+
+    #[test]
+    fn the_underline_gives_the_width() {
+        let report = "error: expected type Field, found type bool
+   ┌─ src/main.nr:4:12
+   │
+ 4 │     assert(x == true);
+   │            ^^^^^^^^^ expected Field
+";
+        let diagnostics = parse_diagnostics(report, Path::new("/package"));
+        assert_eq!(
+            diagnostics[0].1.range,
+            Range::new(Position::new(3, 11), Position::new(3, 20))
+        );
+    }
+
+    #[test]
+    fn colours_are_ignored() {
+        let report = "\u{1b}[1;31merror\u{1b}[0m: cannot find `x` in this scope
+   \u{1b}[34m┌─\u{1b}[0m src/main.nr:1:1
+";
+        let diagnostics = parse_diagnostics(report, Path::new("/package"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].1.message, "cannot find `x` in this scope");
+    }
+
+    #[test]
+    fn test_failures_point_at_what_failed() {
+        let output = "[hash] Running 1 test function
+[hash] Testing test_double... error: Failed constraint
+   ┌─ src/main.nr:7:12
+   │
+ 7 │     assert(double(x) == 5);
+   │            --------------
+   │
+   = Call stack:
+     1. src/main.nr:7:12
+[hash] 1 test failed
+";
+        let (file, range) = failure_location(output, Path::new("/package")).unwrap();
+        assert_eq!(file, Path::new("/package/src/main.nr"));
+        assert_eq!(
+            range,
+            Range::new(Position::new(6, 11), Position::new(6, 25))
+        );
+        assert!(failure_location("[hash] 1 test failed", Path::new("/package")).is_none());
+    }
+
+    #[test]
+    fn absolute_files_are_kept() {
+        let (file, position) = location_of("/elsewhere/src/main.nr:10:3").unwrap();
+        assert_eq!(
+            Path::new("/package").join(file),
+            Path::new("/elsewhere/src/main.nr")
+        );
+        assert_eq!(position, Position::new(9, 2));
+        assert!(location_of("src/main.nr").is_none());
+        assert!(location_of("src/main.nr:0:1").is_none());
+    }
+}
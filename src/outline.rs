@@ -0,0 +1,204 @@
+use noirc_frontend::{Ident, NoirFunction, ParsedModule, Pattern};
+use ropey::Rope;
+use tower_lsp::lsp_types::{DocumentSymbol, Range, SymbolKind};
+
+use crate::contracts::Contract;
+use crate::utils::{
+    block_end, function_end, offset_to_position, span_offset, span_position, PositionEncoding,
+};
+
+/// The items of a document for the editor's outline: its functions, `impl` blocks with their
+/// methods, structs with their fields, globals and submodules, and `contracts` with the
+/// functions they export.
+pub fn document_symbols(
+    module: &ParsedModule,
+    document: &Rope,
+    contracts: &[Contract],
+    encoding: PositionEncoding,
+) -> Vec<DocumentSymbol> {
+    let functions = module
+        .functions
+        .iter()
+        .filter_map(|func| function_symbol(func, document, encoding));
+    let impls = module.impls.iter().filter_map(|imp| {
+        let methods = imp
+            .methods
+            .iter()
+            .filter_map(|func| function_symbol(func, document, encoding))
+            .collect::<Vec<_>>();
+        let type_name = imp.type_path.segments.last()?;
+        // The parser keeps no span for the block itself, so it spans its methods.
+        let range = Range::new(methods.first()?.range.start, methods.last()?.range.end);
+        Some(symbol(
+            format!("impl {}", type_name.0.contents),
+            SymbolKind::CLASS,
+            range,
+            range,
+            Some(methods),
+        ))
+    });
+    let structs = module.types.iter().filter_map(|typ| {
+        let fields = typ
+            .fields
+            .iter()
+            .filter_map(|(name, typ)| {
+                let range = ident_range(name, document, encoding)?;
+                let mut field = symbol(
+                    name.0.contents.clone(),
+                    SymbolKind::FIELD,
+                    range,
+                    range,
+                    None,
+                );
+                field.detail = Some(typ.to_string());
+                Some(field)
+            })
+            .collect();
+        let name_end = span_offset(typ.name.span().end(), document)?;
+        Some(symbol(
+            typ.name.0.contents.clone(),
+            SymbolKind::STRUCT,
+            line_range(
+                span_offset(typ.name.span().start(), document)?,
+                block_end(name_end, document)?,
+                document,
+                encoding,
+            )?,
+            ident_range(&typ.name, document, encoding)?,
+            Some(fields),
+        ))
+    });
+    let globals = module
+        .globals
+        .iter()
+        .filter_map(|global| match &global.pattern {
+            Pattern::Identifier(name) => {
+                let range = ident_range(name, document, encoding)?;
+                Some(symbol(
+                    name.0.contents.clone(),
+                    SymbolKind::CONSTANT,
+                    range,
+                    range,
+                    None,
+                ))
+            }
+            _ => None,
+        });
+    let modules = module.module_decls.iter().filter_map(|name| {
+        let range = ident_range(name, document, encoding)?;
+        Some(symbol(
+            name.0.contents.clone(),
+            SymbolKind::MODULE,
+            range,
+            range,
+            None,
+        ))
+    });
+    let contracts = contracts.iter().filter_map(|contract| {
+        let functions = contract
+            .functions
+            .iter()
+            .filter_map(|func| {
+                let end = block_end(func.name_start, document)?;
+                let selection = name_range(func.name_start, &func.name, document, encoding)?;
+                Some(symbol(
+                    func.name.clone(),
+                    SymbolKind::FUNCTION,
+                    line_range(func.name_start, end, document, encoding)?,
+                    selection,
+                    None,
+                ))
+            })
+            .collect();
+        Some(symbol(
+            contract.name.clone(),
+            SymbolKind::NAMESPACE,
+            line_range(contract.name_start, contract.end, document, encoding)?,
+            name_range(contract.name_start, &contract.name, document, encoding)?,
+            Some(functions),
+        ))
+    });
+
+    let mut symbols = functions
+        .chain(impls)
+        .chain(structs)
+        .chain(globals)
+        .chain(modules)
+        .chain(contracts)
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|symbol| symbol.range.start);
+    symbols
+}
+
+fn function_symbol(
+    func: &NoirFunction,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Option<DocumentSymbol> {
+    let name = &func.def.name;
+    Some(symbol(
+        name.0.contents.clone(),
+        SymbolKind::FUNCTION,
+        line_range(
+            span_offset(name.span().start(), document)?,
+            function_end(func, document)?,
+            document,
+            encoding,
+        )?,
+        ident_range(name, document, encoding)?,
+        None,
+    ))
+}
+
+#[allow(deprecated)] // `DocumentSymbol::deprecated` is superseded by `tags`.
+fn symbol(
+    name: String,
+    kind: SymbolKind,
+    range: Range,
+    selection_range: Range,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range,
+        children,
+    }
+}
+
+fn ident_range(ident: &Ident, document: &Rope, encoding: PositionEncoding) -> Option<Range> {
+    Some(Range::new(
+        span_position(ident.span().start(), document, encoding)?,
+        span_position(ident.span().end(), document, encoding)?,
+    ))
+}
+
+fn name_range(
+    start: usize,
+    name: &str,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Option<Range> {
+    Some(Range::new(
+        offset_to_position(start, document, encoding)?,
+        offset_to_position(start + name.chars().count(), document, encoding)?,
+    ))
+}
+
+/// From the start of the line `start` is on, to keep `pub` and the like, up to `end`.
+fn line_range(
+    start: usize,
+    end: usize,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Option<Range> {
+    let line = document.try_char_to_line(start).ok()?;
+    Some(Range::new(
+        offset_to_position(document.line_to_char(line), document, encoding)?,
+        offset_to_position(end, document, encoding)?,
+    ))
+}
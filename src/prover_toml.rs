@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+
+use noirc_abi::AbiVisibility;
+use noirc_frontend::{NoirFunction, NoirStruct, Pattern, UnresolvedType};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{
+    CreateFile, Diagnostic, DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::diagnostics::SOURCE;
+use crate::utils::{offset_to_position, span_offset, PositionEncoding};
+
+/// The inputs nargo proves `main` with, next to `Nargo.toml`.
+pub const PROVER_TOML: &str = "Prover.toml";
+/// The public inputs and return value of the last proof, written by `nargo prove`.
+pub const VERIFIER_TOML: &str = "Verifier.toml";
+
+/// Adds a value for every parameter of `main` that `Prover.toml` is missing, creating the
+/// file if there is none.
+pub const GENERATE_PROVER_TOML: &str = "noir.generateProverToml";
+/// Adds a parameter to `main` for every key of `Prover.toml` it doesn't take yet.
+pub const ADD_MAIN_PARAMETERS: &str = "noir.addMainParameters";
+/// Solves the circuit for the inputs in `Prover.toml` without proving, returning an
+/// `Execution`.
+pub const EXECUTE: &str = "noir.execute";
+
+/// Proves `main` for the inputs in `Prover.toml` with the package's backend, returning a
+/// `Proof`.
+pub const PROVE: &str = "noir.prove";
+
+/// What running `main` on the inputs in `Prover.toml` computed.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Execution {
+    /// `None` when `main` returns nothing or the inputs failed a constraint.
+    pub return_value: Option<String>,
+    /// The `pub` parameters of `main` with their values, as `Prover.toml` writes them.
+    pub public_inputs: Vec<(String, String)>,
+    /// Why the circuit could not be solved.
+    pub error: Option<String>,
+}
+
+/// The outcome of proving `main` on the inputs in `Prover.toml`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proof {
+    /// The file nargo wrote the proof to.
+    pub uri: Option<Url>,
+    /// Why no proof could be made.
+    pub error: Option<String>,
+}
+
+/// What a top-level key of `Prover.toml` holds, as far as it matters for typing a
+/// parameter.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Input {
+    Scalar,
+    Bool,
+    Array(usize),
+    Table,
+}
+
+/// The top-level keys and tables of a TOML document, in order. Only as much of TOML is
+/// understood as nargo writes: `key = value` lines, arrays that may span lines, and
+/// `[table]` headers.
+pub fn inputs(text: &str) -> Vec<(String, Input)> {
+    let mut inputs: Vec<(String, Input)> = Vec::new();
+    let mut in_table = false;
+    let mut pending: Option<(String, String)> = None;
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some((key, value)) = &mut pending {
+            value.push_str(line);
+            if brackets_balanced(value) {
+                let array = Input::Array(array_length(value));
+                inputs.push((std::mem::take(key), array));
+                pending = None;
+            }
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            in_table = true;
+            let name = unquote(
+                header
+                    .trim_start_matches('[')
+                    .split(['.', ']'])
+                    .next()
+                    .unwrap(),
+            );
+            if !inputs.iter().any(|(key, _)| *key == name) {
+                inputs.push((name, Input::Table));
+            }
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if !in_table => (unquote(key), value.trim()),
+            _ => continue,
+        };
+        let input = if value.starts_with('[') {
+            if !brackets_balanced(value) {
+                pending = Some((key, value.to_string()));
+                continue;
+            }
+            Input::Array(array_length(value))
+        } else if value == "true" || value == "false" {
+            Input::Bool
+        } else {
+            Input::Scalar
+        };
+        inputs.push((key, input));
+    }
+    inputs
+}
+
+/// The top-level keys of a TOML document with their values as written. A table comes out
+/// as one inline table, e.g. `point = { x = 1, y = 2 }`.
+pub fn values(text: &str) -> Vec<(String, String)> {
+    entries(text)
+        .into_iter()
+        .map(|entry| (entry.key, entry.value))
+        .collect()
+}
+
+/// A top-level key or table of a TOML document.
+struct Entry {
+    key: String,
+    /// The line the key or the table's header is on.
+    line: u32,
+    value: String,
+}
+
+fn entries(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut tables: Vec<Entry> = Vec::new();
+    let mut fields = Vec::new();
+    let mut pending: Option<(String, u32, String)> = None;
+    for (number, line) in text.lines().enumerate() {
+        let number = number as u32;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let (key, line_number, value) = if let Some((key, start, value)) = &mut pending {
+            value.push(' ');
+            value.push_str(line);
+            if !brackets_balanced(value) {
+                continue;
+            }
+            let entry = (std::mem::take(key), *start, std::mem::take(value));
+            pending = None;
+            entry
+        } else if let Some(header) = line.strip_prefix('[') {
+            if let Some(table) = tables.last_mut() {
+                table.value = format!("{{ {} }}", std::mem::take(&mut fields).join(", "));
+            }
+            tables.push(Entry {
+                key: unquote(header.trim_start_matches('[').trim_end_matches(']')),
+                line: number,
+                value: String::new(),
+            });
+            continue;
+        } else {
+            match line.split_once('=') {
+                Some((key, value)) if brackets_balanced(value) => {
+                    (unquote(key), number, value.trim().to_string())
+                }
+                Some((key, value)) => {
+                    pending = Some((unquote(key), number, value.trim().to_string()));
+                    continue;
+                }
+                None => continue,
+            }
+        };
+        if tables.is_empty() {
+            entries.push(Entry {
+                key,
+                line: line_number,
+                value,
+            });
+        } else {
+            fields.push(format!("{} = {}", key, value));
+        }
+    }
+    if let Some(table) = tables.last_mut() {
+        table.value = format!("{{ {} }}", fields.join(", "));
+    }
+    entries.extend(tables);
+    entries
+}
+
+/// Checks the inputs in a `Prover.toml`, or with `verifier` a `Verifier.toml`, against the
+/// parameters of `main`: every key has to be a parameter, arrays have to be as long as their
+/// type says and integers have to fit their width. Only `Prover.toml` has to have a value
+/// for every parameter.
+pub fn validate(main: &NoirFunction, toml: &str, verifier: bool) -> Vec<Diagnostic> {
+    let parameters = main
+        .def
+        .parameters
+        .iter()
+        .filter_map(|(pattern, typ, _)| Some((parameter_name(pattern)?, typ)))
+        .collect::<Vec<_>>();
+    let entries = entries(toml);
+    let line_range = |line: u32| {
+        let length = toml
+            .lines()
+            .nth(line as usize)
+            .unwrap_or_default()
+            .chars()
+            .count();
+        Range::new(Position::new(line, 0), Position::new(line, length as u32))
+    };
+    let diagnostic = |range, severity, message| Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some(SOURCE.to_string()),
+        message,
+        ..Diagnostic::default()
+    };
+
+    let mut diagnostics = Vec::new();
+    for entry in &entries {
+        match parameters.iter().find(|(name, _)| *name == entry.key) {
+            Some((_, typ)) => {
+                if let Some(problem) = check_value(typ, &entry.value) {
+                    diagnostics.push(diagnostic(
+                        line_range(entry.line),
+                        DiagnosticSeverity::ERROR,
+                        format!("`{}`: {}", entry.key, problem),
+                    ));
+                }
+            }
+            // The value `main` returned, which nargo writes next to the public inputs.
+            None if verifier && entry.key == "return" => {}
+            None => diagnostics.push(diagnostic(
+                line_range(entry.line),
+                DiagnosticSeverity::WARNING,
+                format!("`{}` is not a parameter of `main`", entry.key),
+            )),
+        }
+    }
+    if !verifier {
+        parameters
+            .iter()
+            .filter(|(name, _)| !entries.iter().any(|entry| entry.key == *name))
+            .for_each(|(name, _)| {
+                diagnostics.push(diagnostic(
+                    Range::default(),
+                    DiagnosticSeverity::ERROR,
+                    format!("missing input for `{}`", name),
+                ))
+            });
+    }
+    diagnostics
+}
+
+/// What is wrong with `value` as an input of type `typ`, if anything.
+fn check_value(typ: &UnresolvedType, value: &str) -> Option<String> {
+    let value = value.trim();
+    match typ {
+        UnresolvedType::Array(length, element_type) => {
+            let inner = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                Some(inner) => inner,
+                None => return Some("expected an array".to_string()),
+            };
+            let elements = array_elements(inner);
+            let expected = length
+                .as_ref()
+                .and_then(|length| length.to_string().parse::<usize>().ok());
+            match expected {
+                Some(expected) if expected != elements.len() => Some(format!(
+                    "expected {} elements, found {}",
+                    expected,
+                    elements.len()
+                )),
+                _ => elements
+                    .iter()
+                    .find_map(|element| check_value(element_type, element)),
+            }
+        }
+        UnresolvedType::Bool(_) => {
+            (value != "true" && value != "false").then(|| "expected `true` or `false`".to_string())
+        }
+        UnresolvedType::Named(..) | UnresolvedType::String(_) | UnresolvedType::Tuple(_) => None,
+        _ => check_integer(&typ.to_string(), value),
+    }
+}
+
+/// Checks a `Field`, `uN` or `iN` value, written bare or as a string, in decimal or hex.
+fn check_integer(typ: &str, value: &str) -> Option<String> {
+    let typ = typ.trim_start_matches("comptime ");
+    let value = value.trim_matches('"');
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let (digits, radix) = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => (hex, 16),
+        None => (digits, 10),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Some("expected a number".to_string());
+    }
+
+    let (signed, bits) = match typ.split_at(1) {
+        ("u", bits) => (false, bits.parse::<u32>().ok()?),
+        ("i", bits) => (true, bits.parse::<u32>().ok()?),
+        // Field elements are wider than a `u128`, leave their range to nargo.
+        _ => return None,
+    };
+    let fits = match u128::from_str_radix(digits, radix) {
+        Ok(magnitude) => match (signed, negative) {
+            (false, true) => magnitude == 0,
+            (false, false) => bits >= 128 || magnitude < 1 << bits,
+            (true, true) => bits > 128 || magnitude <= 1 << (bits - 1),
+            (true, false) => bits > 128 || magnitude < 1 << (bits - 1),
+        },
+        Err(_) => false,
+    };
+    (!fits).then(|| format!("value does not fit in `{}`", typ))
+}
+
+/// The top-level elements of the inside of an array literal.
+fn array_elements(inner: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                elements.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(inner[start..].trim());
+    elements.retain(|element| !element.is_empty());
+    elements
+}
+
+/// The `pub` parameters of `main` with their values in `toml`.
+pub fn public_inputs(main: &NoirFunction, toml: &str) -> Vec<(String, String)> {
+    let values = values(toml);
+    main.def
+        .parameters
+        .iter()
+        .filter(|(_, _, visibility)| is_public(visibility))
+        .filter_map(|(pattern, _, _)| {
+            let name = parameter_name(pattern)?;
+            let value = values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map_or_else(|| "<missing>".to_string(), |(_, value)| value.clone());
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// Whether a parameter with `visibility` is written `name: pub Field`.
+pub fn is_public(visibility: &AbiVisibility) -> bool {
+    matches!(visibility, AbiVisibility::Public)
+}
+
+/// Fills in `main`'s missing inputs. Plain values go above the first table so they stay
+/// top-level keys, struct parameters become tables at the end.
+pub fn fill_prover_toml(
+    main: &NoirFunction,
+    structs: &[NoirStruct],
+    uri: Url,
+    toml: Option<&str>,
+    encoding: PositionEncoding,
+) -> Option<WorkspaceEdit> {
+    let present = toml.map(inputs).unwrap_or_default();
+    let mut keys = String::new();
+    let mut tables = String::new();
+    main.def
+        .parameters
+        .iter()
+        .filter_map(|(pattern, typ, _)| Some((parameter_name(pattern)?, typ)))
+        .filter(|(name, _)| !present.iter().any(|(key, _)| key == name))
+        .for_each(|(name, typ)| match struct_of(typ, structs) {
+            Some(typ) => table(&name, typ, structs, &mut tables),
+            None => keys.push_str(&format!("{} = {}\n", name, value(typ))),
+        });
+    if keys.is_empty() && tables.is_empty() {
+        return None;
+    }
+
+    let toml = match toml {
+        Some(toml) => toml,
+        None => {
+            let new_text = format!("{}{}", keys, tables.trim_start());
+            let created = TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: None,
+                },
+                edits: vec![OneOf::Left(TextEdit {
+                    range: Range::default(),
+                    new_text,
+                })],
+            };
+            return Some(WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Operations(vec![
+                    DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                        uri,
+                        options: None,
+                        annotation_id: None,
+                    })),
+                    DocumentChangeOperation::Edit(created),
+                ])),
+                ..WorkspaceEdit::default()
+            });
+        }
+    };
+
+    let document = Rope::from_str(toml);
+    let end = offset_to_position(document.len_chars(), &document, encoding)?;
+    let first_table = toml
+        .lines()
+        .position(|line| line.trim_start().starts_with('['))
+        .map(|line| Position::new(line as u32, 0));
+    let mut edits = Vec::new();
+    if !keys.is_empty() {
+        let (at, keys) = match first_table {
+            Some(at) => (at, keys),
+            None if toml.is_empty() || toml.ends_with('\n') => (end, keys),
+            None => (end, format!("\n{}", keys)),
+        };
+        edits.push(TextEdit {
+            range: Range::new(at, at),
+            new_text: keys,
+        });
+    }
+    if !tables.is_empty() {
+        let separator = if toml.is_empty() || toml.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        edits.push(TextEdit {
+            range: Range::new(end, end),
+            new_text: format!("{}{}", separator, tables),
+        });
+    }
+    Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(uri, edits)])),
+        ..WorkspaceEdit::default()
+    })
+}
+
+/// Adds a parameter to `main` for each top-level key of `toml` it doesn't take, typed from
+/// the value. Tables are left out since there is no telling which struct they hold.
+pub fn add_main_parameters(
+    main: &NoirFunction,
+    document: &Rope,
+    uri: Url,
+    toml: &str,
+    encoding: PositionEncoding,
+) -> Option<WorkspaceEdit> {
+    let taken = main
+        .def
+        .parameters
+        .iter()
+        .filter_map(|(pattern, _, _)| parameter_name(pattern))
+        .collect::<Vec<_>>();
+    let parameters = inputs(toml)
+        .into_iter()
+        .filter(|(key, _)| !taken.contains(key))
+        .filter_map(|(key, input)| {
+            let typ = match input {
+                Input::Scalar => "Field".to_string(),
+                Input::Bool => "bool".to_string(),
+                Input::Array(length) => format!("[Field; {}]", length),
+                Input::Table => return None,
+            };
+            Some(format!("{}: {}", key, typ))
+        })
+        .collect::<Vec<_>>();
+    if parameters.is_empty() {
+        return None;
+    }
+
+    let close = parameters_end(main, document)?;
+    let new_text = if main.def.parameters.is_empty() {
+        parameters.join(", ")
+    } else {
+        let before = document.get_slice(..close)?.to_string();
+        let separator = if before.trim_end().ends_with(',') {
+            " "
+        } else {
+            ", "
+        };
+        format!("{}{}", separator, parameters.join(", "))
+    };
+    let at = offset_to_position(close, document, encoding)?;
+    Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(
+            uri,
+            vec![TextEdit {
+                range: Range::new(at, at),
+                new_text,
+            }],
+        )])),
+        ..WorkspaceEdit::default()
+    })
+}
+
+/// Offset of the `)` closing `func`'s parameter list.
+fn parameters_end(func: &NoirFunction, document: &Rope) -> Option<usize> {
+    let start = span_offset(func.def.name.span().end(), document)?;
+    let mut depth = 0;
+    for (i, c) in document.get_chars_at(start)?.enumerate() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn parameter_name(pattern: &Pattern) -> Option<String> {
+    match pattern {
+        Pattern::Identifier(ident) => Some(ident.0.contents.clone()),
+        Pattern::Mutable(pattern, _) => parameter_name(pattern),
+        _ => None,
+    }
+}
+
+fn struct_of<'a>(typ: &UnresolvedType, structs: &'a [NoirStruct]) -> Option<&'a NoirStruct> {
+    match typ {
+        UnresolvedType::Named(path, _) => {
+            let name = &path.segments.last()?.0.contents;
+            structs.iter().find(|typ| &typ.name.0.contents == name)
+        }
+        _ => None,
+    }
+}
+
+/// `[name]` with a value for every field, followed by tables for fields that are structs.
+fn table(name: &str, typ: &NoirStruct, structs: &[NoirStruct], out: &mut String) {
+    out.push_str(&format!("\n[{}]\n", name));
+    let mut nested = Vec::new();
+    typ.fields
+        .iter()
+        .for_each(|(field, field_type)| match struct_of(field_type, structs) {
+            Some(field_struct) => nested.push((format!("{}.{}", name, field), field_struct)),
+            None => out.push_str(&format!("{} = {}\n", field, value(field_type))),
+        });
+    nested
+        .into_iter()
+        .for_each(|(name, typ)| table(&name, typ, structs, out));
+}
+
+/// A placeholder for an input of type `typ`, written the way nargo reads it back.
+fn value(typ: &UnresolvedType) -> String {
+    match typ {
+        UnresolvedType::Bool(_) => "false".to_string(),
+        UnresolvedType::String(_) => "\"\"".to_string(),
+        UnresolvedType::Array(length, element_type) => {
+            let length = length
+                .as_ref()
+                .and_then(|length| length.to_string().parse().ok())
+                .unwrap_or(0);
+            format!("[{}]", vec![value(element); length].join(", "))
+        }
+        _ => "\"0\"".to_string(),
+    }
+}
+
+fn array_length(value: &str) -> usize {
+    let inner = value.trim().trim_start_matches('[').trim_end_matches(']');
+    inner
+        .split(',')
+        .filter(|element| !element.trim().is_empty())
+        .count()
+}
+
+fn brackets_balanced(value: &str) -> bool {
+    value.matches('[').count() == value.matches(']').count()
+}
+
+fn unquote(key: &str) -> String {
+    key.trim().trim_matches('"').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use noirc_frontend::parse_program;
+
+    use super::*;
+
+    #[test]
+    fn public_inputs_follow_the_parameters_visibility() {
+        let (module, _) = parse_program("fn main(x: Field, pubkey: pub Field, y: pub [u8; 2]) {}");
+        let main = &module.functions[0];
+        let toml = "x = \"1\"\npubkey = \"2\"\n";
+        assert_eq!(
+            public_inputs(main, toml),
+            vec![
+                ("pubkey".to_string(), "\"2\"".to_string()),
+                ("y".to_string(), "<missing>".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use serde_json::json;
+use tower_lsp::lsp_types::{
+    ClientCapabilities, DidChangeWatchedFilesRegistrationOptions, FileSystemWatcher, GlobPattern,
+    Registration,
+};
+
+use crate::{prover_toml, workspace};
+
+/// The providers the client registers when the server asks, after `initialized`, rather than
+/// through the capabilities `initialize` answers with. Those are scoped to Noir documents,
+/// and a client that can't register something dynamically gets it statically instead, or
+/// not at all for file watchers.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Dynamic {
+    pub inlay_hints: bool,
+    pub code_lens: bool,
+    /// Watching sources, manifests and `Prover.toml` files changed outside the editor, for
+    /// which there is no static capability.
+    pub watchers: bool,
+}
+
+impl Dynamic {
+    pub fn of(capabilities: &ClientCapabilities) -> Dynamic {
+        let text_document = capabilities.text_document.as_ref();
+        Dynamic {
+            inlay_hints: text_document
+                .and_then(|text_document| text_document.inlay_hint.as_ref())
+                .and_then(|inlay_hint| inlay_hint.dynamic_registration)
+                .unwrap_or(false),
+            code_lens: text_document
+                .and_then(|text_document| text_document.code_lens.as_ref())
+                .and_then(|code_lens| code_lens.dynamic_registration)
+                .unwrap_or(false),
+            watchers: capabilities
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+                .and_then(|watched_files| watched_files.dynamic_registration)
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn registrations(&self) -> Vec<Registration> {
+        let documents = json!([{ "language": "noir" }]);
+        let mut registrations = Vec::new();
+        if self.watchers {
+            let watchers = DidChangeWatchedFilesRegistrationOptions {
+                watchers: ["*.nr", workspace::MANIFEST, prover_toml::PROVER_TOML]
+                    .into_iter()
+                    .map(|file| FileSystemWatcher {
+                        glob_pattern: GlobPattern::String(format!("**/{}", file)),
+                        kind: None,
+                    })
+                    .collect(),
+            };
+            registrations.push(Registration {
+                id: "file-watcher".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(watchers).ok(),
+            });
+        }
+        if self.inlay_hints {
+            registrations.push(Registration {
+                id: "inlay-hints".to_string(),
+                method: "textDocument/inlayHint".to_string(),
+                register_options: Some(json!({ "documentSelector": documents })),
+            });
+        }
+        if self.code_lens {
+            registrations.push(Registration {
+                id: "code-lens".to_string(),
+                method: "textDocument/codeLens".to_string(),
+                register_options: Some(json!({
+                    "documentSelector": documents,
+                    "resolveProvider": true,
+                })),
+            });
+        }
+        registrations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{
+        DidChangeWatchedFilesClientCapabilities, WorkspaceClientCapabilities,
+    };
+
+    use super::*;
+
+    #[test]
+    fn manifests_are_watched_when_the_client_can_register_watchers() {
+        let capabilities = ClientCapabilities {
+            workspace: Some(WorkspaceClientCapabilities {
+                did_change_watched_files: Some(DidChangeWatchedFilesClientCapabilities {
+                    dynamic_registration: Some(true),
+                    ..DidChangeWatchedFilesClientCapabilities::default()
+                }),
+                ..WorkspaceClientCapabilities::default()
+            }),
+            ..ClientCapabilities::default()
+        };
+        let registrations = Dynamic::of(&capabilities).registrations();
+        let watcher = registrations
+            .iter()
+            .find(|registration| registration.method == "workspace/didChangeWatchedFiles")
+            .unwrap();
+        let options: DidChangeWatchedFilesRegistrationOptions =
+            serde_json::from_value(watcher.register_options.clone().unwrap()).unwrap();
+        assert!(options.watchers.iter().any(|watcher| matches!(
+            &watcher.glob_pattern,
+            GlobPattern::String(glob) if glob == "**/Nargo.toml"
+        )));
+    }
+
+    #[test]
+    fn nothing_is_watched_without_dynamic_registration() {
+        let registrations = Dynamic::of(&ClientCapabilities::default()).registrations();
+        assert!(registrations.is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+/// Sent whenever the health of the analysis changes, for the client's status bar.
+pub const SERVER_STATUS: &str = "noir/serverStatus";
+
+/// Sent when a background `nargo check` of a package starts and when it ends.
+pub const COMPILATION_STATUS: &str = "noir/compilationStatus";
+
+/// Reports how long the server has run and how much it has analysed and has yet to, for the
+/// client to show on request. Takes no parameters.
+pub const STATUS: &str = "noir/status";
+
+/// What the server is doing, or what keeps it from working, e.g.
+/// `{ "state": "compileFailed", "errors": 3 }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum ServerStatus {
+    /// Discovering packages and looking for nargo.
+    Loading,
+    /// Parsing crates, their dependencies or the standard library.
+    Indexing,
+    Ready,
+    /// Something the server needs is missing, such as nargo.
+    Error {
+        message: String,
+    },
+    /// The last `nargo check` reported errors.
+    CompileFailed {
+        errors: usize,
+    },
+}
+
+/// Where a package's background compile is, e.g.
+/// `{ "package": "file:///home/me/circuit", "state": "failed", "errors": 3, "durationMs": 820 }`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilationStatus {
+    /// The package's directory.
+    pub package: Url,
+    pub state: CompilationState,
+    /// The errors nargo reported, none until the compile ends.
+    pub errors: usize,
+    /// How long the compile took, once it has ended.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompilationState {
+    Started,
+    Succeeded,
+    /// nargo reported errors, or couldn't be run at all.
+    Failed,
+}
+
+/// The answer to `noir/status`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusReport {
+    pub uptime_seconds: u64,
+    /// What the server is doing, as last sent with `noir/serverStatus`.
+    pub server: ServerStatus,
+    pub open_documents: usize,
+    /// Files whose items are in the symbol index, open or loaded with their package.
+    pub indexed_files: usize,
+    /// Items those files declare.
+    pub indexed_symbols: usize,
+    /// Open documents edited since they were last analysed.
+    pub pending_analyses: usize,
+    /// How the last background compile that finished went, if any did.
+    pub last_compile: Option<CompilationStatus>,
+}
@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use noirc_frontend::{ParsedModule, Pattern};
+use rayon::prelude::*;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{CompletionItemKind, Location, Url};
+
+use crate::utils::{import_path, module_path, span_offset, span_range, PositionEncoding};
+use crate::workspace::crate_files;
+
+/// An item a library, the standard library or a dependency, declares at the top level of one
+/// of its modules.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StdItem {
+    /// Interned once the index is in the server's hands.
+    pub name: Arc<str>,
+    pub kind: CompletionItemKind,
+    /// The item's declaration as written, without a body.
+    pub signature: String,
+    pub location: Location,
+}
+
+/// Where the standard library's sources are: `NOIR_STDLIB` when it is set, then the
+/// `noir_stdlib` installed next to the `bin` directory of `nargo`, and otherwise the one of
+/// the noir checkout the frontend is built from.
+pub fn locate(nargo: Option<&Path>) -> Option<PathBuf> {
+    let toolchain = nargo
+        .and_then(|nargo| nargo.parent()?.parent())
+        .map(|toolchain| toolchain.join("noir_stdlib"));
+    std::env::var_os("NOIR_STDLIB")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(toolchain)
+        .chain([Path::new(env!("CARGO_MANIFEST_DIR")).join("../noir/noir_stdlib")])
+        .find(|root| root.join("src/lib.nr").is_file())
+}
+
+/// Every module of the standard library rooted at `root`, keyed by its path such as
+/// `std::hash`, with the items it declares, located in `encoding`.
+pub fn index(root: &Path, encoding: PositionEncoding) -> Vec<(String, Vec<StdItem>)> {
+    crate_files(&root.join("src/lib.nr"), |path| {
+        std::fs::read_to_string(path).ok()
+    })
+    .into_par_iter()
+    .filter_map(|(path, text, module)| {
+        let uri = Url::from_file_path(&path).ok()?;
+        let module_path = module_path(&uri)?.replacen("crate", "std", 1);
+        let document = Rope::from_str(&text);
+        Some((module_path, items(&uri, &document, &module, encoding)))
+    })
+    .collect()
+}
+
+/// The top-level items of `module`, the file at `uri`, located in `encoding`.
+pub fn items(
+    uri: &Url,
+    document: &Rope,
+    module: &ParsedModule,
+    encoding: PositionEncoding,
+) -> Vec<StdItem> {
+    let location = |start: u32, end: u32| {
+        Some(Location {
+            uri: uri.clone(),
+            range: span_range(start, end, document, encoding)?,
+        })
+    };
+
+    let functions = module.functions.iter().filter_map(|func| {
+        let name = &func.def.name;
+        // From the start of the line, to keep `pub` and friends, up to the body.
+        let line = document.char_to_line(span_offset(name.span().start(), document)?);
+        let start = document.line_to_char(line);
+        let signature = document
+            .chars_at(start)
+            .take_while(|c| *c != '{')
+            .collect::<String>();
+        Some(StdItem {
+            name: name.0.contents.as_str().into(),
+            kind: CompletionItemKind::FUNCTION,
+            signature: signature.split_whitespace().collect::<Vec<_>>().join(" "),
+            location: location(name.span().start(), name.span().end())?,
+        })
+    });
+    let structs = module.types.iter().filter_map(|typ| {
+        let fields = typ
+            .fields
+            .iter()
+            .map(|(name, typ)| format!("    {}: {},\n", name.0.contents, typ))
+            .collect::<String>();
+        Some(StdItem {
+            name: typ.name.0.contents.as_str().into(),
+            kind: CompletionItemKind::STRUCT,
+            signature: format!("struct {} {{\n{}}}", typ.name.0.contents, fields),
+            location: location(typ.name.span().start(), typ.name.span().end())?,
+        })
+    });
+    let globals = module
+        .globals
+        .iter()
+        .filter_map(|global| match &global.pattern {
+            Pattern::Identifier(name) => Some(StdItem {
+                name: name.0.contents.as_str().into(),
+                kind: CompletionItemKind::CONSTANT,
+                signature: format!("global {}", name.0.contents),
+                location: location(name.span().start(), name.span().end())?,
+            }),
+            _ => None,
+        });
+    functions.chain(structs).chain(globals).collect()
+}
+
+/// Where the `::`-separated path the cursor at `offset` is on starts, and the path up to the
+/// cursor or, with `whole_segment`, to the end of the segment under it. That is e.g.
+/// `std::hash::pedersen`, or `hash::pe` while typing.
+pub fn path_at(document: &Rope, offset: usize, whole_segment: bool) -> Option<(usize, String)> {
+    let is_path_char = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    let line = document.char_to_line(offset);
+    let line_start = document.line_to_char(line);
+    let text = document.line(line).chars().collect::<Vec<_>>();
+    let cursor = offset - line_start;
+    let start = (0..cursor)
+        .rev()
+        .take_while(|i| is_path_char(text[*i]))
+        .last()
+        .unwrap_or(cursor);
+    let end = if whole_segment {
+        (cursor..text.len())
+            .take_while(|i| text[*i].is_alphanumeric() || text[*i] == '_')
+            .last()
+            .map_or(cursor, |i| i + 1)
+    } else {
+        cursor
+    };
+    let path = text[start..end].iter().collect::<String>();
+    (!path.is_empty()).then_some((line_start + start, path))
+}
+
+/// The standard library path `path` stands for in `module`: written out from `std` or
+/// `dep::std`, or starting with the name a `use` of the standard library brought in scope.
+pub fn resolve(module: &ParsedModule, path: &str) -> Option<String> {
+    let full = expand(module, path.strip_prefix("dep::").unwrap_or(path));
+    let full = full.strip_prefix("dep::").unwrap_or(&full).to_string();
+    (full == "std" || full.starts_with("std::")).then_some(full)
+}
+
+/// `path` with the name a `use` brought in scope at its start replaced by the path it
+/// imports, such as `dep::merkle::tree::root` for `tree::root` after `use dep::merkle::tree`.
+pub fn expand(module: &ParsedModule, path: &str) -> String {
+    let (first, rest) = match path.split_once("::") {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (path, None),
+    };
+    let imported = module.imports.iter().find_map(|import| {
+        let name = import
+            .alias
+            .as_ref()
+            .or_else(|| import.path.segments.last())?;
+        (name.0.contents == first).then(|| import_path(&import.path))
+    });
+    match (imported, rest) {
+        (Some(imported), Some(rest)) => format!("{}::{}", imported, rest),
+        (Some(imported), None) => imported,
+        (None, _) => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noirc_frontend::parse_program;
+
+    use super::*;
+
+    #[test]
+    fn paths_start_from_what_is_imported() {
+        let (module, _) = parse_program("use dep::merkle::tree; use dep::std::hash;");
+        assert_eq!(expand(&module, "tree::root"), "dep::merkle::tree::root");
+        assert_eq!(expand(&module, "other::root"), "other::root");
+        assert_eq!(
+            resolve(&module, "hash::pedersen").as_deref(),
+            Some("std::hash::pedersen")
+        );
+        assert_eq!(resolve(&module, "tree::root"), None);
+    }
+
+    #[test]
+    fn items_are_located_with_their_signature() {
+        let text = "global N = 3;\nstruct Point { x: Field }\npub fn root(x: Field) -> Field {\n    x\n}\n";
+        let (module, _) = parse_program(text);
+        let uri = Url::parse("file:///merkle/src/lib.nr").unwrap();
+        let found = items(
+            &uri,
+            &Rope::from_str(text),
+            &module,
+            PositionEncoding::Utf16,
+        );
+        let root = found
+            .iter()
+            .find(|item| item.name.as_ref() == "root")
+            .unwrap();
+        assert_eq!(root.signature, "pub fn root(x: Field) -> Field");
+        assert_eq!(
+            root.location.range.start,
+            tower_lsp::lsp_types::Position::new(2, 7)
+        );
+        assert_eq!(found.len(), 3);
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::future::{BoxFuture, FutureExt};
+use serde::Serialize;
+use tower::Service;
+use tower_lsp::jsonrpc::{Request, Response};
+use tower_lsp::Client;
+
+/// How often what was recorded is sent, as one `telemetry/event`.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Upper bounds, in milliseconds, of the latency buckets. The last bucket takes the rest.
+const BUCKETS: [u64; 8] = [1, 5, 10, 50, 100, 500, 1_000, 5_000];
+
+/// Whether the user opted in, with the `telemetry` setting. Nothing is recorded otherwise.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The latencies recorded since the last report, by method.
+static USAGE: Mutex<BTreeMap<String, Histogram>> = Mutex::new(BTreeMap::new());
+
+/// How many times a method was handled and how long that took. Only the method's name is
+/// kept, nothing of what it was asked about.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Histogram {
+    count: u64,
+    /// How many took at most each of `BUCKETS` milliseconds, and more than the last.
+    latency_ms: [u64; BUCKETS.len() + 1],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageEvent {
+    interval_seconds: u64,
+    buckets_ms: [u64; BUCKETS.len()],
+    methods: BTreeMap<String, Histogram>,
+}
+
+pub fn set_enabled(enabled: bool) {
+    let was_enabled = ENABLED.swap(enabled, Ordering::Relaxed);
+    if was_enabled && !enabled {
+        // Opting out drops what was recorded but not sent yet.
+        USAGE.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
+
+/// Sends what was recorded to the client every `REPORT_INTERVAL`, while the user has opted
+/// in and something was recorded.
+pub fn report_usage(client: Client) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REPORT_INTERVAL);
+        // The first tick is immediate.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let methods =
+                std::mem::take(&mut *USAGE.lock().unwrap_or_else(PoisonError::into_inner));
+            if methods.is_empty() || !ENABLED.load(Ordering::Relaxed) {
+                continue;
+            }
+            let event = UsageEvent {
+                interval_seconds: REPORT_INTERVAL.as_secs(),
+                buckets_ms: BUCKETS,
+                methods,
+            };
+            if let Ok(event) = serde_json::to_value(event) {
+                client.telemetry_event(event).await;
+            }
+        }
+    });
+}
+
+fn record(method: &str, elapsed: Duration) {
+    let millis = elapsed.as_millis() as u64;
+    let bucket = BUCKETS
+        .iter()
+        .position(|bound| millis <= *bound)
+        .unwrap_or(BUCKETS.len());
+    let mut usage = USAGE.lock().unwrap_or_else(PoisonError::into_inner);
+    let histogram = usage.entry(method.to_string()).or_default();
+    histogram.count += 1;
+    histogram.latency_ms[bucket] += 1;
+}
+
+/// The server's service, timing every request and notification it handles for the usage
+/// reports once the user opted in.
+#[derive(Debug)]
+pub struct Timed<S>(pub S);
+
+impl<S> Service<Request> for Timed<S>
+where
+    S: Service<Request, Response = Option<Response>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Option<Response>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let timed = ENABLED
+            .load(Ordering::Relaxed)
+            .then(|| (request.method().to_string(), Instant::now()));
+        let response = self.0.call(request);
+        async move {
+            let response = response.await;
+            if let Some((method, started)) = timed {
+                record(&method, started.elapsed());
+            }
+            response
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use noirc_frontend::ParsedModule;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Location, Range, TextDocumentIdentifier, Url};
+
+use crate::nargo;
+use crate::utils::{span_range, test_functions, PositionEncoding};
+
+pub const LIST_TESTS: &str = "noir/listTests";
+pub const RUN_TESTS: &str = "noir/runTests";
+
+/// Asks for the tests of the package `text_document` belongs to.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTestsParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// Runs tests of the package `text_document` belongs to: the ones in `names`, or all of them
+/// when it is left out.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTestsParams {
+    pub text_document: TextDocumentIdentifier,
+    #[serde(default)]
+    pub names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TestItem {
+    pub name: String,
+    pub uri: Url,
+    /// The test function's name.
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    /// nargo couldn't be run, so the test's outcome is unknown.
+    Errored,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TestOutcome {
+    #[serde(flatten)]
+    pub test: TestItem,
+    pub status: TestStatus,
+    /// Why the test failed, as nargo put it.
+    pub message: Option<String>,
+    /// Where the test failed, such as the assertion that didn't hold, when nargo says.
+    pub location: Option<Location>,
+}
+
+/// The `#[test]` functions of one file of a package.
+pub fn tests_in(
+    uri: &Url,
+    module: &ParsedModule,
+    document: &Rope,
+    encoding: PositionEncoding,
+) -> Vec<TestItem> {
+    test_functions(module, document)
+        .into_iter()
+        .filter_map(|func| {
+            let span = func.def.name.span();
+            Some(TestItem {
+                name: func.def.name.0.contents.clone(),
+                uri: uri.clone(),
+                range: span_range(span.start(), span.end(), document, encoding)?,
+            })
+        })
+        .collect()
+}
+
+/// [`nargo::failure_location`] of a failed test of the package at `root`, for the client.
+pub fn failure_location(output: &str, root: &Path) -> Option<Location> {
+    let (file, range) = nargo::failure_location(output, root)?;
+    Some(Location::new(Url::from_file_path(file).ok()?, range))
+}
+
+/// The part of `nargo test`'s output that says why a test failed, leaving out the progress
+/// lines such as `[package] Running 1 test function` but not an error one of them ends in.
+pub fn failure_message(output: &str) -> String {
+    output
+        .lines()
+        .filter_map(|line| {
+            if line.trim_start().starts_with('[') {
+                line.find("error:").map(|error| &line[error..])
+            } else {
+                Some(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "[hash] Running 1 test function
+[hash] Testing test_double... error: Failed constraint
+   ┌─ src/main.nr:7:12
+   │
+ 7 │     assert(double(x) == 5);
+   │            --------------
+";
+
+    #[test]
+    fn failures_are_located_in_the_package() {
+        let location = failure_location(OUTPUT, Path::new("/package")).unwrap();
+        assert_eq!(location.uri.as_str(), "file:///package/src/main.nr");
+        assert_eq!(
+            location.range.start,
+            tower_lsp::lsp_types::Position::new(6, 11)
+        );
+    }
+
+    #[test]
+    fn progress_lines_are_left_out_of_the_message() {
+        let message = failure_message(OUTPUT);
+        assert!(message.starts_with("error: Failed constraint\n   ┌─ src/main.nr:7:12"));
+        assert!(!message.contains("Running"));
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+use tokio::sync::mpsc;
+use tower_lsp::lsp_types::notification::LogTrace;
+use tower_lsp::lsp_types::{LogTraceParams, TraceValue};
+use tower_lsp::Client;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+const OFF: u8 = 0;
+const MESSAGES: u8 = 1;
+const VERBOSE: u8 = 2;
+
+/// How much the client asked to be sent, through `initialize` and `$/setTrace`.
+static TRACE: AtomicU8 = AtomicU8::new(OFF);
+
+/// Where events go on their way to the client, once `forward_to` connected it.
+static SENDER: OnceLock<mpsc::UnboundedSender<LogTraceParams>> = OnceLock::new();
+
+pub fn set(value: TraceValue) {
+    let trace = match value {
+        TraceValue::Off => OFF,
+        TraceValue::Messages => MESSAGES,
+        TraceValue::Verbose => VERBOSE,
+    };
+    TRACE.store(trace, Ordering::Relaxed);
+}
+
+/// Sends the events `ClientLayer` picks up to `client` as `$/logTrace`. Events are recorded
+/// wherever the server happens to be, so a task of its own sends them on.
+pub fn forward_to(client: Client) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    if SENDER.set(sender).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        while let Some(params) = receiver.recv().await {
+            client.send_notification::<LogTrace>(params).await;
+        }
+    });
+}
+
+/// Passes the server's own events on to the client while it has tracing on: those of level
+/// info and up with their message for `messages`, and every event with its fields and the
+/// spans it happened in for `verbose`. Events of the libraries underneath stay out, since
+/// sending a notification is one of them.
+pub struct ClientLayer;
+
+impl<S> Layer<S> for ClientLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let trace = TRACE.load(Ordering::Relaxed);
+        let metadata = event.metadata();
+        if trace == OFF
+            || (trace == MESSAGES && *metadata.level() > Level::INFO)
+            || !metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+        {
+            return;
+        }
+        let sender = match SENDER.get() {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let verbose = (trace == VERBOSE).then(|| {
+            let mut verbose = fields.rest;
+            for span in ctx
+                .event_scope(event)
+                .into_iter()
+                .flat_map(|scope| scope.from_root())
+            {
+                let _ = write!(verbose, " in {}", span.name());
+            }
+            verbose.trim().to_string()
+        });
+        let _ = sender.send(LogTraceParams {
+            message: format!("[{}] {}", metadata.level(), fields.message),
+            verbose,
+        });
+    }
+}
+
+/// An event's message, and its other fields as `name=value`.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.rest, "{}={:?} ", field.name(), value);
+        }
+    }
+}
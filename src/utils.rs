@@ -0,0 +1,703 @@
+use std::collections::HashSet;
+
+use noirc_frontend::{
+    ArrayLiteral, Expression, ExpressionKind, Ident, LValue, Literal, NoirFunction, ParsedModule,
+    Path, PathKind, Pattern, Statement,
+};
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    Position, PositionEncodingKind, Range, TextDocumentContentChangeEvent, Url,
+};
+
+/// How the client counts the columns of positions, as agreed on in `initialize`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PositionEncoding {
+    /// UTF-8 bytes, what the frontend's spans count.
+    Utf8,
+    /// UTF-16 code units, the protocol's default.
+    #[default]
+    Utf16,
+}
+
+impl PositionEncoding {
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+}
+
+/// The client's position of the char at `offset`, with its column in `encoding`.
+pub fn offset_to_position(
+    offset: usize,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Option<Position> {
+    let line = rope.try_char_to_line(offset).ok()?;
+    let first_char_of_line = rope.try_line_to_char(line).ok()?;
+    let column = match encoding {
+        PositionEncoding::Utf8 => rope.char_to_byte(offset) - rope.char_to_byte(first_char_of_line),
+        PositionEncoding::Utf16 => {
+            rope.char_to_utf16_cu(offset) - rope.char_to_utf16_cu(first_char_of_line)
+        }
+    };
+    Some(Position::new(line as u32, column as u32))
+}
+
+/// The char offset of `byte`, an offset from a span of the frontend, whose lexer counts
+/// bytes where the rope counts chars.
+pub fn span_offset(byte: u32, rope: &Rope) -> Option<usize> {
+    rope.try_byte_to_char(byte as usize).ok()
+}
+
+/// The offset the frontend's spans give the char at `offset`, the inverse of `span_offset`.
+pub fn byte_offset(offset: usize, rope: &Rope) -> Option<u32> {
+    Some(rope.try_char_to_byte(offset).ok()? as u32)
+}
+
+/// The client's position of `byte`, an offset from a span of the frontend.
+pub fn span_position(byte: u32, rope: &Rope, encoding: PositionEncoding) -> Option<Position> {
+    offset_to_position(span_offset(byte, rope)?, rope, encoding)
+}
+
+/// The client's range of the bytes from `start` to `end`, offsets from a span of the
+/// frontend.
+pub fn span_range(start: u32, end: u32, rope: &Rope, encoding: PositionEncoding) -> Option<Range> {
+    Some(Range::new(
+        span_position(start, rope, encoding)?,
+        span_position(end, rope, encoding)?,
+    ))
+}
+
+/// The text of the bytes from `start` to `end`, offsets from a span of the frontend.
+pub fn span_text(start: u32, end: u32, rope: &Rope) -> Option<String> {
+    let slice = rope.get_slice(span_offset(start, rope)?..span_offset(end, rope)?)?;
+    Some(slice.to_string())
+}
+
+/// Offset just past the names a pattern binds, which is where a `: Type` annotation goes.
+pub fn pattern_end(pattern: &Pattern) -> u32 {
+    match pattern {
+        Pattern::Identifier(ident) => ident.span().end(),
+        // Past the name, whatever the span of the `mut` covers.
+        Pattern::Mutable(pattern, span) => span.end().max(pattern_end(pattern)),
+        Pattern::Tuple(_, span) | Pattern::Struct(_, _, span) => span.end(),
+    }
+}
+
+/// The variable an assignment target writes through, e.g. `a` for `a.b[i] = ..`.
+pub fn lvalue_root(lvalue: &LValue) -> &Ident {
+    match lvalue {
+        LValue::Ident(ident) => ident,
+        LValue::MemberAccess { object, .. } => lvalue_root(object),
+        LValue::Index { array, .. } => lvalue_root(array),
+    }
+}
+
+/// Every function in the module, including methods declared in `impl` blocks.
+pub fn all_functions(module: &ParsedModule) -> impl Iterator<Item = &NoirFunction> {
+    module
+        .functions
+        .iter()
+        .chain(module.impls.iter().flat_map(|imp| imp.methods.iter()))
+}
+
+/// Calls `f` on every expression reachable from `statements`, parents before children.
+pub fn walk_statements<'a>(statements: &'a [Statement], f: &mut dyn FnMut(&'a Expression)) {
+    statements.iter().for_each(|statement| match statement {
+        Statement::Let(val) => walk_expression(&val.expression, f),
+        Statement::Constrain(constrain) => {
+            walk_expression(&constrain.0.lhs, f);
+            walk_expression(&constrain.0.rhs, f);
+        }
+        Statement::Assign(assign) => walk_expression(&assign.expression, f),
+        Statement::Expression(expr) | Statement::Semi(expr) => walk_expression(expr, f),
+        Statement::Error => {}
+    })
+}
+
+pub fn walk_expression<'a>(expr: &'a Expression, f: &mut dyn FnMut(&'a Expression)) {
+    f(expr);
+    match &expr.kind {
+        ExpressionKind::Literal(Literal::Array(ArrayLiteral::Standard(elements))) => {
+            elements.iter().for_each(|e| walk_expression(e, f))
+        }
+        ExpressionKind::Literal(Literal::Array(ArrayLiteral::Repeated {
+            repeated_element,
+            length,
+        })) => {
+            walk_expression(repeated_element, f);
+            walk_expression(length, f);
+        }
+        ExpressionKind::Block(block) => walk_statements(&block.0, f),
+        ExpressionKind::Prefix(prefix) => walk_expression(&prefix.rhs, f),
+        ExpressionKind::Index(index) => {
+            walk_expression(&index.collection, f);
+            walk_expression(&index.index, f);
+        }
+        ExpressionKind::Call(call) => call.arguments.iter().for_each(|e| walk_expression(e, f)),
+        ExpressionKind::MethodCall(call) => {
+            walk_expression(&call.object, f);
+            call.arguments.iter().for_each(|e| walk_expression(e, f));
+        }
+        ExpressionKind::Constructor(constructor) => constructor
+            .fields
+            .iter()
+            .for_each(|(_, e)| walk_expression(e, f)),
+        ExpressionKind::MemberAccess(access) => walk_expression(&access.lhs, f),
+        ExpressionKind::Cast(cast) => walk_expression(&cast.lhs, f),
+        ExpressionKind::Infix(infix) => {
+            walk_expression(&infix.lhs, f);
+            walk_expression(&infix.rhs, f);
+        }
+        ExpressionKind::For(for_loop) => {
+            walk_expression(&for_loop.start_range, f);
+            walk_expression(&for_loop.end_range, f);
+            walk_expression(&for_loop.block, f);
+        }
+        ExpressionKind::If(if_expr) => {
+            walk_expression(&if_expr.condition, f);
+            walk_expression(&if_expr.consequence, f);
+            if let Some(alternative) = &if_expr.alternative {
+                walk_expression(alternative, f);
+            }
+        }
+        ExpressionKind::Tuple(elements) => elements.iter().for_each(|e| walk_expression(e, f)),
+        _ => {}
+    }
+}
+
+/// The path other modules of the crate import this file's items through, e.g.
+/// `src/merkle/tree.nr` is `crate::merkle::tree`. Crate roots and `mod.nr` files resolve to
+/// their directory.
+pub fn module_path(uri: &Url) -> Option<String> {
+    let path = uri.to_file_path().ok()?;
+    let components = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    let src = components.iter().rposition(|c| c == "src")?;
+    let mut segments = components[src + 1..].to_vec();
+    let file = segments.pop()?;
+    match file.strip_suffix(".nr")? {
+        "main" | "lib" | "mod" => {}
+        stem => segments.push(stem.to_string()),
+    }
+    segments.insert(0, "crate".to_string());
+    Some(segments.join("::"))
+}
+
+/// Names declared at the top level of a module, the ones other modules can `use`.
+pub fn top_level_names(module: &ParsedModule) -> Vec<String> {
+    module
+        .functions
+        .iter()
+        .map(|func| func.def.name.0.contents.clone())
+        .chain(module.types.iter().map(|typ| typ.name.0.contents.clone()))
+        .chain(
+            module
+                .globals
+                .iter()
+                .filter_map(|global| match &global.pattern {
+                    Pattern::Identifier(name) => Some(name.0.contents.clone()),
+                    _ => None,
+                }),
+        )
+        .collect()
+}
+
+/// The char offset of the client's `position`, its column counted in `encoding`. A column
+/// past the end of its line stops at the end of the line, as the protocol has it.
+pub fn position_to_offset(
+    position: Position,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Option<usize> {
+    let line = position.line as usize;
+    let line_start = rope.try_line_to_char(line).ok()?;
+    let line_end = line_start
+        + rope.get_line(line).map_or(0, |text| {
+            let breaks = text
+                .chars()
+                .rev()
+                .take_while(|c| *c == '\n' || *c == '\r')
+                .count();
+            text.len_chars() - breaks
+        });
+    let column = position.character as usize;
+    match encoding {
+        PositionEncoding::Utf8 => {
+            let byte = rope.char_to_byte(line_start) + column;
+            rope.try_byte_to_char(byte.min(rope.char_to_byte(line_end)))
+                .ok()
+        }
+        PositionEncoding::Utf16 => {
+            let unit = rope.char_to_utf16_cu(line_start) + column;
+            rope.try_utf16_cu_to_char(unit.min(rope.char_to_utf16_cu(line_end)))
+                .ok()
+        }
+    }
+}
+
+/// The offset of the client's `position` as the frontend's spans count it, to compare with
+/// them.
+pub fn position_to_byte(
+    position: Position,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Option<u32> {
+    byte_offset(position_to_offset(position, rope, encoding)?, rope)
+}
+
+/// Applies the edits of one `didChange` to `rope` in order, each range referring to the
+/// text as the edits before it left it. Returns the first edit whose range isn't in the
+/// text; the edits after it are not applied, since their positions assume it was.
+pub fn apply_changes<'a>(
+    rope: &mut Rope,
+    changes: &'a [TextDocumentContentChangeEvent],
+    encoding: PositionEncoding,
+) -> Result<(), &'a TextDocumentContentChangeEvent> {
+    changes
+        .iter()
+        .try_for_each(|change| apply_change(rope, change, encoding).ok_or(change))
+}
+
+/// Applies one `didChange` edit to `rope`: a replacement of its range, or of the whole text
+/// when it has none. `None`, with `rope` left as it was, when the range isn't in the text.
+pub fn apply_change(
+    rope: &mut Rope,
+    change: &TextDocumentContentChangeEvent,
+    encoding: PositionEncoding,
+) -> Option<()> {
+    let range = match change.range {
+        Some(range) => range,
+        None => {
+            *rope = Rope::from_str(&change.text);
+            return Some(());
+        }
+    };
+    let start = position_to_offset(range.start, rope, encoding)?.min(rope.len_chars());
+    let end = position_to_offset(range.end, rope, encoding)?.min(rope.len_chars());
+    rope.try_remove(start..end).ok()?;
+    rope.try_insert(start, &change.text).ok()?;
+    Some(())
+}
+
+/// Offset just past the closing brace of `func`'s body.
+pub fn function_end(func: &NoirFunction, rope: &Rope) -> Option<usize> {
+    block_end(span_offset(func.def.name.span().end(), rope)?, rope)
+}
+
+/// Offset just past the brace closing the first `{` at or after `start`. Braces inside
+/// string literals, line comments and block comments are skipped.
+pub fn block_end(start: usize, rope: &Rope) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = rope.get_chars_at(start)?.enumerate().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let mut escaped = false;
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                let mut star = false;
+                for (_, c) in chars.by_ref() {
+                    if star && c == '/' {
+                        break;
+                    }
+                    star = c == '*';
+                }
+            }
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Free functions marked `#[test]`. The attribute is looked for on the lines directly above
+/// the function, since the parser doesn't keep it.
+pub fn test_functions<'a>(module: &'a ParsedModule, rope: &Rope) -> Vec<&'a NoirFunction> {
+    module
+        .functions
+        .iter()
+        .filter(|func| {
+            let mut line = match span_offset(func.def.name.span().start(), rope) {
+                Some(offset) => rope.char_to_line(offset),
+                None => return false,
+            };
+            while line > 0 {
+                line -= 1;
+                let above = rope.line(line).to_string();
+                let above = above.trim();
+                if above.replace(' ', "") == "#[test]" {
+                    return true;
+                }
+                if !above.starts_with("#[") {
+                    break;
+                }
+            }
+            false
+        })
+        .collect()
+}
+
+/// The function whose definition spans `byte`, an offset as the frontend's spans count.
+pub fn enclosing_function<'a>(
+    module: &'a ParsedModule,
+    rope: &Rope,
+    byte: u32,
+) -> Option<&'a NoirFunction> {
+    let offset = span_offset(byte, rope)?;
+    all_functions(module).find(|func| {
+        span_offset(func.def.name.span().start(), rope).is_some_and(|start| start <= offset)
+            && function_end(func, rope).is_some_and(|end| offset <= end)
+    })
+}
+
+/// The span of a statement as far as the AST records it: from its pattern or first
+/// expression to the end of its last expression. Keywords such as `let` fall outside.
+pub fn statement_span(statement: &Statement) -> Option<(u32, u32)> {
+    match statement {
+        Statement::Let(val) => {
+            let start = match &val.pattern {
+                Pattern::Identifier(ident) => ident.span().start(),
+                Pattern::Mutable(_, span)
+                | Pattern::Tuple(_, span)
+                | Pattern::Struct(_, _, span) => span.start(),
+            };
+            Some((start, val.expression.span.end()))
+        }
+        Statement::Constrain(constrain) => {
+            Some((constrain.0.lhs.span.start(), constrain.0.rhs.span.end()))
+        }
+        Statement::Assign(assign) => {
+            Some((assign.expression.span.start(), assign.expression.span.end()))
+        }
+        Statement::Expression(expr) | Statement::Semi(expr) => {
+            Some((expr.span.start(), expr.span.end()))
+        }
+        Statement::Error => None,
+    }
+}
+
+/// The most deeply nested statement whose span contains `start..end`, looking inside
+/// blocks, loop bodies and `if` branches.
+pub fn innermost_statement<'a>(
+    statements: &'a [Statement],
+    start: u32,
+    end: u32,
+) -> Option<&'a Statement> {
+    let statement = statements
+        .iter()
+        .find(|statement| statement_span(statement).is_some_and(|(s, e)| s <= start && end <= e))?;
+    let mut nested = None;
+    let mut visit = |expr: &'a Expression| {
+        if nested.is_some() {
+            return;
+        }
+        let block = match &expr.kind {
+            ExpressionKind::Block(block) => block,
+            _ => return,
+        };
+        if expr.span.start() <= start && end <= expr.span.end() {
+            nested = innermost_statement(&block.0, start, end);
+        }
+    };
+    match statement {
+        Statement::Let(val) => walk_expression(&val.expression, &mut visit),
+        Statement::Assign(assign) => walk_expression(&assign.expression, &mut visit),
+        Statement::Expression(expr) | Statement::Semi(expr) => walk_expression(expr, &mut visit),
+        Statement::Constrain(_) | Statement::Error => {}
+    }
+    nested.or(Some(statement))
+}
+
+/// Every name the function binds or mentions, for picking names that don't clash.
+pub fn names_in_function(func: &NoirFunction) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let bind = |pattern: &Pattern, names: &mut HashSet<String>| {
+        if let Pattern::Identifier(ident) = pattern {
+            names.insert(ident.0.contents.clone());
+        }
+    };
+    func.def
+        .parameters
+        .iter()
+        .for_each(|(pattern, _, _)| bind(pattern, &mut names));
+    let bind_lets = |statements: &[Statement], names: &mut HashSet<String>| {
+        statements.iter().for_each(|statement| {
+            if let Statement::Let(val) = statement {
+                bind(&val.pattern, names);
+            }
+        })
+    };
+    bind_lets(&func.def.body.0, &mut names);
+    walk_statements(&func.def.body.0, &mut |expr| match &expr.kind {
+        ExpressionKind::Path(path) => path.segments.iter().for_each(|segment| {
+            names.insert(segment.0.contents.clone());
+        }),
+        ExpressionKind::Block(block) => bind_lets(&block.0, &mut names),
+        ExpressionKind::For(for_loop) => {
+            names.insert(for_loop.identifier.0.contents.clone());
+        }
+        _ => {}
+    });
+    names
+}
+
+/// `base`, or `base` with the smallest numeric suffix that is not in `taken`.
+pub fn fresh_name(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) {
+        return base.to_string();
+    }
+    (1..)
+        .map(|i| format!("{}{}", base, i))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
+
+/// The whitespace a line starts with.
+pub fn line_indent(rope: &Rope, line: usize) -> String {
+    rope.get_line(line)
+        .map(|line| {
+            line.chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Where the file for `mod name;` declared in `uri` lives: next to crate roots and `mod.nr`
+/// files, and in a directory named after the declaring file otherwise.
+pub fn child_module_file(uri: &Url, name: &str) -> Option<Url> {
+    let path = uri.to_file_path().ok()?;
+    let parent = path.parent()?;
+    let dir = match path.file_stem()?.to_str()? {
+        "main" | "lib" | "mod" => parent.to_path_buf(),
+        stem => parent.join(stem),
+    };
+    Url::from_file_path(dir.join(format!("{}.nr", name))).ok()
+}
+
+/// Whether `name` can name an item: a letter or `_` and then letters, digits and `_`, other
+/// than a keyword.
+pub fn is_identifier(name: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "as",
+        "assert",
+        "bool",
+        "comptime",
+        "constrain",
+        "contract",
+        "crate",
+        "dep",
+        "else",
+        "false",
+        "Field",
+        "fn",
+        "for",
+        "global",
+        "if",
+        "impl",
+        "in",
+        "let",
+        "mod",
+        "mut",
+        "pub",
+        "return",
+        "str",
+        "struct",
+        "true",
+        "use",
+        "_",
+    ];
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+pub fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// A `use` path as written, including the `crate::` or `dep::` prefix the parser keeps
+/// out of the segments.
+pub fn import_path(path: &Path) -> String {
+    let segments = path
+        .segments
+        .iter()
+        .map(|segment| segment.0.contents.as_str())
+        .collect::<Vec<_>>()
+        .join("::");
+    match path.kind {
+        PathKind::Crate => format!("crate::{}", segments),
+        PathKind::Dep => format!("dep::{}", segments),
+        PathKind::Plain => segments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_follow_the_encoding() {
+        // `é` is two UTF-8 bytes and one UTF-16 unit, `𝔽` four bytes and two units.
+        let rope = Rope::from_str("let é = 𝔽;\nx");
+        let x = "let é = 𝔽;\n".len() as u32;
+        let semicolon = "let é = 𝔽".len() as u32;
+
+        assert_eq!(
+            span_position(semicolon, &rope, PositionEncoding::Utf8),
+            Some(Position::new(0, 13))
+        );
+        assert_eq!(
+            span_position(semicolon, &rope, PositionEncoding::Utf16),
+            Some(Position::new(0, 10))
+        );
+        assert_eq!(
+            span_position(x, &rope, PositionEncoding::Utf16),
+            Some(Position::new(1, 0))
+        );
+        for encoding in [PositionEncoding::Utf8, PositionEncoding::Utf16] {
+            let position = span_position(semicolon, &rope, encoding).unwrap();
+            assert_eq!(position_to_byte(position, &rope, encoding), Some(semicolon));
+        }
+    }
+
+    #[test]
+    fn blocks_end_past_braces_in_strings_and_comments() {
+        let text = "fn main() {
+    // }
+    /* } */
+    /** { */
+    let s = \"}\\\"}\";
+}
+fn other() {}";
+        let end = text.find("}\nfn other").unwrap() + 1;
+        assert_eq!(block_end(0, &Rope::from_str(text)), Some(end));
+    }
+
+    #[test]
+    fn columns_past_the_line_stop_at_its_end() {
+        let rope = Rope::from_str("ab\r\ncd");
+        let offset = position_to_offset(Position::new(0, 40), &rope, PositionEncoding::Utf16);
+        assert_eq!(offset, Some(2));
+        assert_eq!(
+            position_to_offset(Position::new(7, 0), &rope, PositionEncoding::Utf16),
+            None
+        );
+    }
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|(start, end)| {
+                Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn changes_apply_in_order() {
+        let mut rope = Rope::from_str("let x = 1;");
+        // The second edit's range is in the text the first one left.
+        let changes = [
+            change(Some(((0, 4), (0, 5))), "total"),
+            change(Some(((0, 12), (0, 13))), "2"),
+            change(Some(((0, 14), (0, 14))), "\nlet y = total;"),
+        ];
+        assert!(apply_changes(&mut rope, &changes, PositionEncoding::Utf16).is_ok());
+        assert_eq!(rope.to_string(), "let total = 2;\nlet y = total;");
+    }
+
+    #[test]
+    fn whole_text_resets_the_changes_before_it() {
+        let mut rope = Rope::from_str("fn main() {}");
+        let changes = [
+            change(Some(((0, 0), (0, 2))), "unconstrained fn"),
+            change(None, "fn f() {}"),
+            change(Some(((0, 3), (0, 4))), "g"),
+        ];
+        assert!(apply_changes(&mut rope, &changes, PositionEncoding::Utf16).is_ok());
+        assert_eq!(rope.to_string(), "fn g() {}");
+    }
+
+    #[test]
+    fn changes_out_of_range_are_reported() {
+        let mut rope = Rope::from_str("a\nb");
+        let changes = [
+            change(Some(((0, 0), (0, 1))), "c"),
+            change(Some(((5, 0), (5, 1))), "d"),
+            change(Some(((0, 0), (0, 1))), "e"),
+        ];
+        let failed = apply_changes(&mut rope, &changes, PositionEncoding::Utf16).unwrap_err();
+        assert_eq!(failed.text, "d");
+        // A range the wrong way round isn't in the text either.
+        let mut rope = Rope::from_str("abc");
+        let backwards = [change(Some(((0, 2), (0, 1))), "x")];
+        assert!(apply_changes(&mut rope, &backwards, PositionEncoding::Utf16).is_err());
+        assert_eq!(rope.to_string(), "abc");
+    }
+
+    #[test]
+    fn change_ranges_count_code_units() {
+        // `𝔽` is two UTF-16 units and four UTF-8 bytes.
+        let mut rope = Rope::from_str("a𝔽b𝔽c");
+        let utf16 = [change(Some(((0, 3), (0, 4))), "x")];
+        assert!(apply_changes(&mut rope, &utf16, PositionEncoding::Utf16).is_ok());
+        assert_eq!(rope.to_string(), "a𝔽x𝔽c");
+
+        let mut rope = Rope::from_str("a𝔽b𝔽c");
+        let utf8 = [change(Some(((0, 5), (0, 10))), "")];
+        assert!(apply_changes(&mut rope, &utf8, PositionEncoding::Utf8).is_ok());
+        assert_eq!(rope.to_string(), "a𝔽c");
+    }
+
+    #[test]
+    fn span_text_reads_bytes() {
+        let rope = Rope::from_str("let é = 1;");
+        let start = "let ".len() as u32;
+        assert_eq!(span_text(start, start + 2, &rope).as_deref(), Some("é"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+use dashmap::DashMap;
+use ropey::Rope;
+
+/// The contents of files as analysis should see them: the editor's buffer for open files,
+/// which may hold unsaved edits, and what is on disk for every other file.
+#[derive(Debug, Default, Clone)]
+pub struct Vfs {
+    overlays: DashMap<PathBuf, Rope>,
+}
+
+impl Vfs {
+    /// Makes `text` the contents of `path` until the buffer is closed.
+    pub fn set(&self, path: &Path, text: Rope) {
+        self.overlays.insert(path.to_path_buf(), text);
+    }
+
+    /// Drops the buffer of `path`, so its contents come from disk again. Returns whether the
+    /// buffer held edits that were never saved, which analysis now has to forget.
+    pub fn close(&self, path: &Path) -> bool {
+        match self.overlays.remove(path) {
+            Some((_, buffer)) => self.read_disk(path).map_or(true, |disk| disk != buffer),
+            None => false,
+        }
+    }
+
+    /// A copy of the buffers as they are now, to read files with away from the server.
+    pub fn snapshot(&self) -> Vfs {
+        self.clone()
+    }
+
+    /// Whether a file in `dir` or below it is open, in which case what is on disk may not be
+    /// what analysis sees.
+    pub fn is_open_in(&self, dir: &Path) -> bool {
+        self.overlays
+            .iter()
+            .any(|entry| entry.key().starts_with(dir))
+    }
+
+    pub fn open_count(&self) -> usize {
+        self.overlays.len()
+    }
+
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.overlays.contains_key(path)
+    }
+
+    pub fn read(&self, path: &Path) -> Option<String> {
+        match self.overlays.get(path) {
+            Some(buffer) => Some(buffer.to_string()),
+            None => std::fs::read_to_string(path).ok(),
+        }
+    }
+
+    fn read_disk(&self, path: &Path) -> Option<Rope> {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|text| Rope::from_str(&text))
+    }
+}
@@ -0,0 +1,328 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use noirc_frontend::{parse_program, ParsedModule};
+use rayon::prelude::*;
+
+/// The manifest nargo reads a package from.
+pub const MANIFEST: &str = "Nargo.toml";
+/// Drops everything the server knows about the workspace and reads it all again.
+pub const RELOAD_WORKSPACE: &str = "noir.reloadWorkspace";
+
+/// Pins the nargo version a package is built with, as `[toolchain] version = "0.7.1"`.
+pub const TOOLCHAIN_FILE: &str = "noir-toolchain.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageKind {
+    Bin,
+    Lib,
+    Contract,
+}
+
+/// A Noir package: the directory holding a `Nargo.toml` and the file its crate starts from.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub name: Option<String>,
+    pub kind: PackageKind,
+    /// `src/main.nr` for binaries and contracts, `src/lib.nr` for libraries.
+    pub crate_root: PathBuf,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// An entry of `[dependencies]`, imported as `dep::name`.
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    /// Where the package is on disk. Git dependencies are only there once nargo has fetched
+    /// them.
+    pub root: PathBuf,
+}
+
+impl Project {
+    /// The package `path` belongs to, found by walking up from it to the closest directory
+    /// with a `Nargo.toml`. `path` may be a file or a directory.
+    pub fn discover(path: &Path) -> Option<Project> {
+        let root = path
+            .ancestors()
+            .find(|dir| dir.join(MANIFEST).is_file())?
+            .to_path_buf();
+        let manifest = std::fs::read_to_string(root.join(MANIFEST)).ok()?;
+        let name = manifest_value(&manifest, "package", "name");
+
+        // Manifests written before `type` existed leave the kind to the crate root on disk.
+        let kind = match manifest_value(&manifest, "package", "type").as_deref() {
+            Some("lib") => PackageKind::Lib,
+            Some("contract") => PackageKind::Contract,
+            Some("bin") => PackageKind::Bin,
+            _ if root.join("src/lib.nr").is_file() && !root.join("src/main.nr").is_file() => {
+                PackageKind::Lib
+            }
+            _ => PackageKind::Bin,
+        };
+        let crate_root = match kind {
+            PackageKind::Lib => root.join("src/lib.nr"),
+            PackageKind::Bin | PackageKind::Contract => root.join("src/main.nr"),
+        };
+        let dependencies = manifest_entries(&manifest, "dependencies")
+            .into_iter()
+            .filter_map(|(name, source)| {
+                let root = dependency_root(&root, &source)?;
+                Some(Dependency { name, root })
+            })
+            .collect();
+        Some(Project {
+            root,
+            name,
+            kind,
+            crate_root,
+            dependencies,
+        })
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    /// Every file of the crate with its text and syntax tree. `read` supplies the text, so
+    /// that open buffers can take precedence over what is on disk.
+    pub fn crate_files(
+        &self,
+        read: impl Fn(&Path) -> Option<String> + Sync,
+    ) -> Vec<(PathBuf, String, ParsedModule)> {
+        crate_files(&self.crate_root, read)
+    }
+
+    /// Every file of the crate with what `load` gives for it, see [`load_crate_files`].
+    pub fn load_crate_files<T, M>(
+        &self,
+        load: impl Fn(&Path) -> Option<(T, M)> + Sync,
+    ) -> Vec<(PathBuf, T, M)>
+    where
+        T: Send,
+        M: Borrow<ParsedModule> + Send,
+    {
+        load_crate_files(&self.crate_root, load)
+    }
+
+    /// Where nargo reads the prover's inputs from.
+    pub fn prover_toml(&self) -> PathBuf {
+        self.root.join(crate::prover_toml::PROVER_TOML)
+    }
+}
+
+/// The nargo version the package at `root` pins in its toolchain file, if any.
+pub fn pinned_toolchain(root: &Path) -> Option<String> {
+    let file = std::fs::read_to_string(root.join(TOOLCHAIN_FILE)).ok()?;
+    manifest_value(&file, "toolchain", "version")
+}
+
+/// Every package in `dir` or below it, for workspace folders that hold several packages
+/// rather than being one. Hidden directories and nargo's `target` are skipped.
+pub fn find_packages(dir: &Path) -> Vec<Project> {
+    let mut packages = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if dir.join(MANIFEST).is_file() {
+            packages.extend(Project::discover(&dir));
+        }
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        pending.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| !name.starts_with('.') && name != "target")
+                }),
+        );
+    }
+    packages
+}
+
+impl std::fmt::Display for Project {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            PackageKind::Bin => "bin",
+            PackageKind::Lib => "lib",
+            PackageKind::Contract => "contract",
+        };
+        write!(
+            f,
+            "{} ({}) rooted at {}",
+            self.name.as_deref().unwrap_or("unnamed package"),
+            kind,
+            self.crate_root.display()
+        )
+    }
+}
+
+/// Every file of the crate starting at `crate_root`, found by following `mod` declarations.
+/// Which files a module declares is only known once it is parsed, so the crate is parsed one
+/// level of the module tree at a time, with the files of a level parsed in parallel.
+pub fn crate_files(
+    crate_root: &Path,
+    read: impl Fn(&Path) -> Option<String> + Sync,
+) -> Vec<(PathBuf, String, ParsedModule)> {
+    load_crate_files(crate_root, |path| {
+        let text = read(path)?;
+        let (module, _) = parse_program(&text);
+        Some((text, module))
+    })
+}
+
+/// Every file of the crate starting at `crate_root`, with what `load` gives for it: its
+/// contents and a tree of it, which need not be parsed again when one is at hand.
+pub fn load_crate_files<T, M>(
+    crate_root: &Path,
+    load: impl Fn(&Path) -> Option<(T, M)> + Sync,
+) -> Vec<(PathBuf, T, M)>
+where
+    T: Send,
+    M: Borrow<ParsedModule> + Send,
+{
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    let mut level = vec![crate_root.to_path_buf()];
+    while !level.is_empty() {
+        level.retain(|path| seen.insert(path.clone()));
+        let loaded = level
+            .par_iter()
+            .filter_map(|path| {
+                let (contents, module) = load(path)?;
+                Some((path.clone(), contents, module))
+            })
+            .collect::<Vec<_>>();
+        level = loaded
+            .iter()
+            .flat_map(|(path, _, module)| {
+                module
+                    .borrow()
+                    .module_decls
+                    .iter()
+                    .filter_map(|decl| module_file(path, &decl.0.contents))
+            })
+            .collect();
+        files.extend(loaded);
+    }
+    files
+}
+
+/// The file `mod name;` in `parent` refers to: `name.nr` or `name/mod.nr` in the directory
+/// holding `parent`'s submodules.
+fn module_file(parent: &Path, name: &str) -> Option<PathBuf> {
+    let dir = match parent.file_stem()?.to_str()? {
+        "main" | "lib" | "mod" => parent.parent()?.to_path_buf(),
+        stem => parent.parent()?.join(stem),
+    };
+    [
+        dir.join(format!("{}.nr", name)),
+        dir.join(name).join("mod.nr"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+/// Where a dependency declared as `{ path = ".." }` or `{ git = "..", tag = ".." }` lives.
+/// nargo clones git dependencies to `~/nargo/<host>/<repository>/<tag>`.
+fn dependency_root(package_root: &Path, source: &str) -> Option<PathBuf> {
+    let fields = inline_table(source);
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    };
+    if let Some(path) = field("path") {
+        return Some(package_root.join(path));
+    }
+    let repository = field("git")?
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    Some(
+        home.join("nargo")
+            .join(repository)
+            .join(field("tag").unwrap_or("main")),
+    )
+}
+
+/// The `key = "value"` pairs of an inline table such as `{ git = "..", tag = "v0.1" }`.
+fn inline_table(table: &str) -> Vec<(String, String)> {
+    table
+        .trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .filter_map(|field| {
+            let (key, value) = field.split_once('=')?;
+            Some((
+                key.trim().to_string(),
+                value.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// The string or bare value of `key` in the `[section]` table of a TOML document.
+pub fn manifest_value(manifest: &str, section: &str, key: &str) -> Option<String> {
+    manifest_entries(manifest, section)
+        .into_iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.trim_matches('"').to_string())
+}
+
+/// The `key = value` lines of the `[section]` table of a TOML document, values as written.
+/// Only the flat tables nargo manifests consist of are understood.
+fn manifest_entries(manifest: &str, section: &str) -> Vec<(String, String)> {
+    let mut current = None;
+    let mut entries = Vec::new();
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(header) = line.strip_prefix('[') {
+            current = header.strip_suffix(']').map(str::trim);
+            continue;
+        }
+        if current != Some(section) {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_package_pins_its_own_toolchain() {
+        let dir = std::env::temp_dir().join(format!("noir-lsp-toolchains-{}", std::process::id()));
+        let (old, new, unpinned) = (dir.join("old"), dir.join("new"), dir.join("unpinned"));
+        for (root, toolchain) in [
+            (&old, Some("0.6.0")),
+            (&new, Some("0.7.1")),
+            (&unpinned, None),
+        ] {
+            std::fs::create_dir_all(root).unwrap();
+            if let Some(version) = toolchain {
+                let file = format!("[toolchain]\nversion = \"{}\"\n", version);
+                std::fs::write(root.join(TOOLCHAIN_FILE), file).unwrap();
+            }
+        }
+        assert_eq!(pinned_toolchain(&old).as_deref(), Some("0.6.0"));
+        assert_eq!(pinned_toolchain(&new).as_deref(), Some("0.7.1"));
+        assert_eq!(pinned_toolchain(&unpinned), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}